@@ -0,0 +1,120 @@
+//! The times of the week when a monitor is active, for `active`.
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::platform;
+
+const DAYS: [&str; 7] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+const DAY_MINUTES: u32 = 24 * 60;
+
+/// A time range on some days of the week, like `Mon-Fri 08:00-18:00`.
+pub struct TimeWindow {
+    /// Whether the window applies to each day, starting with Monday.
+    days: [bool; 7],
+    /// In minutes since midnight.
+    start: u32,
+    /// In minutes since midnight. If it isn't after `start`, the window ends on the next day.
+    end: u32,
+}
+
+impl TimeWindow {
+    /// Returns whether the window contains this day (0 for Monday) and minute.
+    fn contains(&self, day: usize, minute: u32) -> bool {
+        if self.start < self.end {
+            return self.days[day] && (self.start..self.end).contains(&minute);
+        }
+        // The window crosses midnight, so its days are those it starts on.
+        let previous_day = (day + 6) % 7;
+        (self.days[day] && minute >= self.start) || (self.days[previous_day] && minute < self.end)
+    }
+}
+
+/// Returns whether the current local time is within any of the windows.
+pub fn is_active(windows: &[TimeWindow]) -> bool {
+    let (day, minute) = platform::local_time();
+    windows.iter().any(|window| window.contains(day, minute))
+}
+
+/// Parses a time window made of days (e.g. `Mon-Fri` or `Sat,Sun`), a time range (e.g.
+/// `22:00-06:00`), or both. Without days, the window applies to every day, and without a time
+/// range, it lasts all day.
+pub fn parse(window: &str) -> Result<TimeWindow> {
+    let mut days = None;
+    let mut times = None;
+    for part in window.split_whitespace() {
+        if part.starts_with(|c: char| c.is_ascii_digit()) {
+            if times.is_some() {
+                bail!("Multiple time ranges in {window:?}.");
+            }
+            times = Some(parse_times(part)?);
+        } else {
+            if days.is_some() {
+                bail!("Multiple lists of days in {window:?}.");
+            }
+            days = Some(parse_days(part)?);
+        }
+    }
+    if days.is_none() && times.is_none() {
+        bail!("{window:?} has neither days nor a time range.");
+    }
+    let (start, end) = times.unwrap_or((0, DAY_MINUTES));
+    Ok(TimeWindow {
+        days: days.unwrap_or([true; 7]),
+        start,
+        end,
+    })
+}
+
+/// Parses days and ranges of days separated by commas, like `Mon-Wed,Fri`.
+fn parse_days(days: &str) -> Result<[bool; 7]> {
+    let mut parsed = [false; 7];
+    for range in days.split(',') {
+        let (first, last) = range.split_once('-').unwrap_or((range, range));
+        let (first, last) = (parse_day(first)?, parse_day(last)?);
+        // Ranges may wrap around the week, like `Fri-Mon`.
+        let len = (last + 7 - first) % 7 + 1;
+        for day in (first..first + len).map(|day| day % 7) {
+            parsed[day] = true;
+        }
+    }
+    Ok(parsed)
+}
+
+/// Parses the name of a day, which may be abbreviated to its first three letters or more.
+fn parse_day(day: &str) -> Result<usize> {
+    let lowercase = day.to_lowercase();
+    DAYS.iter()
+        .position(|name| lowercase.len() >= 3 && name.starts_with(&lowercase))
+        .ok_or(anyhow!("Unknown day {day:?}."))
+}
+
+/// Parses a time range like `08:00-18:00` into minutes since midnight.
+fn parse_times(times: &str) -> Result<(u32, u32)> {
+    let (start, end) = times.split_once('-').ok_or(anyhow!(
+        "Time range {times:?} must look like `08:00-18:00`."
+    ))?;
+    let (start, end) = (parse_time(start)?, parse_time(end)?);
+    if start == end {
+        bail!("Time range {times:?} is empty.");
+    }
+    Ok((start, end))
+}
+
+fn parse_time(time: &str) -> Result<u32> {
+    let invalid = || anyhow!("Time {time:?} must look like `08:00`.");
+    let (hours, minutes) = time.split_once(':').ok_or_else(invalid)?;
+    let hours = hours.parse::<u32>().map_err(|_| invalid())?;
+    let minutes = minutes.parse::<u32>().map_err(|_| invalid())?;
+    if minutes >= 60 || hours * 60 + minutes > DAY_MINUTES {
+        return Err(invalid());
+    }
+    Ok(hours * 60 + minutes)
+}
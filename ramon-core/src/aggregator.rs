@@ -0,0 +1,652 @@
+//! Delivers notifications through the channels of a notification config, aggregating them into
+//! digests, rate limiting them, and retrying and falling back when delivery fails.
+
+use anyhow::Result;
+use log::{debug, error, info, warn};
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    hash::{BuildHasher, Hasher, RandomState},
+    mem::take,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    select,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Mutex, Notify,
+    },
+    time::Instant,
+};
+use tracing::{field, instrument, Span};
+
+use crate::{
+    audit,
+    clock::Ticker,
+    config::{Notification, NotificationConfig, Severity},
+    error::{Error, ErrorKind},
+    events, health,
+    highlight::{self, Highlight},
+    meta,
+    notifier::{self, Delivery, Notifier},
+    rate_limit::RateLimit,
+    source::BoxFuture,
+    state::Format,
+    status,
+};
+
+/// How many notifications, or digests, may wait to be delivered before more are dropped.
+const MAX_OUTGOING: usize = 1000;
+
+/// One JSON notification per line.
+const QUEUE_FORMAT: Format = Format {
+    kind: "queue",
+    migrations: &[],
+    essential: true,
+};
+
+/// Receives the notifications of a notification config, and delivers them through its channels,
+/// either as they arrive or aggregated at an interval.
+pub struct Aggregator {
+    notify_rx: Receiver<Notification>,
+    config: NotificationConfig,
+    interval: Option<Ticker>,
+    rate_limit: Option<RateLimit>,
+    /// Receives notifications that could not be delivered.
+    fallback: Option<Sender<Notification>>,
+}
+
+/// The notifications of an aggregator that haven't been delivered yet, which are stored together
+/// so that none are lost if ramon stops first.
+struct Pending {
+    /// The name of the notification config.
+    name: String,
+    /// Notifications waiting for the next aggregate.
+    queue: Vec<Notification>,
+    /// Notifications, or digests of several, waiting to be delivered, oldest first. The first is
+    /// being delivered.
+    outgoing: VecDeque<Vec<Notification>>,
+    /// Whether the aggregator has stopped, so that nothing more will be delivered once `outgoing`
+    /// is empty.
+    closed: bool,
+}
+
+impl Pending {
+    /// Reads the notifications that weren't delivered when ramon last stopped into the queue.
+    async fn load(name: &str) -> Self {
+        let queue = match QUEUE_FORMAT.read(name).await {
+            Ok(None) => Vec::new(),
+            Ok(Some(contents)) => contents
+                .lines()
+                .filter_map(|line| match serde_json::from_str(line) {
+                    Ok(notification) => Some(notification),
+                    Err(err) => {
+                        warn!("[{name}] Discarding invalid queued notification: {err}");
+                        None
+                    }
+                })
+                .collect::<Vec<Notification>>(),
+            Err(err) => {
+                error!("[{name}] Failed to load queued notifications: {err}");
+                Vec::new()
+            }
+        };
+        if !queue.is_empty() {
+            info!("[{name}] Restored {} queued notifications", queue.len());
+        }
+        status::set_queue(name, queue.len());
+        Self {
+            name: name.to_owned(),
+            queue,
+            outgoing: VecDeque::new(),
+            closed: false,
+        }
+    }
+
+    /// Hands notifications to delivery, unless `MAX_OUTGOING` deliveries are already waiting, in
+    /// which case they're dropped and counted.
+    fn push_outgoing(&mut self, notifications: Vec<Notification>, wake: &Notify) {
+        if self.outgoing.len() >= MAX_OUTGOING {
+            warn!(
+                "[{}] Dropping {} notifications: {MAX_OUTGOING} deliveries are already waiting",
+                self.name,
+                notifications.len()
+            );
+            status::add_dropped(&self.name, notifications.len() as u64);
+            return;
+        }
+        self.outgoing.push_back(notifications);
+        wake.notify_one();
+    }
+
+    /// Stores the undelivered notifications, oldest first, as one queue.
+    async fn store(&self) {
+        let notifications = self.outgoing.iter().flatten().chain(&self.queue);
+        let mut len = 0;
+        let mut contents = String::new();
+        for notification in notifications {
+            len += 1;
+            match serde_json::to_string(notification) {
+                Ok(line) => contents += &(line + "\n"),
+                Err(err) => warn!(
+                    "[{}] Failed to serialize queued notification: {err}",
+                    self.name
+                ),
+            }
+        }
+        status::set_queue(&self.name, len);
+        if let Err(err) = QUEUE_FORMAT.write(&self.name, &contents).await {
+            let err = Error::new(
+                ErrorKind::CacheWrite,
+                format!("Failed to store queued notifications: {err}"),
+            );
+            warn!("[{}] {err}", self.name);
+        }
+    }
+}
+
+impl Aggregator {
+    /// Returns the aggregator, which does nothing until it's spawned, and the sender for its
+    /// notifications.
+    pub fn new(
+        notify_config: NotificationConfig,
+        interval: Option<Ticker>,
+        rate_limit: Option<RateLimit>,
+    ) -> (Self, Sender<Notification>) {
+        let (notify_tx, notify_rx) = channel(1);
+
+        let aggregator = Self {
+            notify_rx,
+            config: notify_config,
+            interval,
+            rate_limit,
+            fallback: None,
+        };
+
+        (aggregator, notify_tx)
+    }
+
+    /// Returns the notification config, for delivering without running the aggregator.
+    pub fn into_config(self) -> NotificationConfig {
+        self.config
+    }
+
+    /// Sends notifications that fail to deliver after every retry to `fallback`.
+    pub fn set_fallback(&mut self, fallback: Sender<Notification>) {
+        self.fallback = Some(fallback);
+    }
+
+    /// Runs the aggregator in the background until every sender is dropped.
+    pub fn spawn(self) {
+        let health = health::aggregate(&self.config.name);
+        tokio::spawn(async move {
+            let _health = health;
+            self.start().await
+        });
+    }
+
+    async fn start(self) -> Result<()> {
+        let config = Arc::new(self.config);
+        let mut interval = self.interval;
+        let pending = Arc::new(Mutex::new(Pending::load(&config.name).await));
+        // Restored notifications are sent with the first aggregate, which is immediate, or
+        // individually if notifications aren't aggregated.
+        if interval.is_none() {
+            let mut pending = pending.lock().await;
+            for notification in take(&mut pending.queue) {
+                pending.outgoing.push_back(vec![notification]);
+            }
+        }
+        // Deliveries and their retries run in their own task, so that a channel that's down
+        // doesn't stop monitors from sending notifications.
+        let wake = Arc::new(Notify::new());
+        wake.notify_one();
+        tokio::spawn(Self::deliver_outgoing(
+            pending.clone(),
+            wake.clone(),
+            config.clone(),
+            self.fallback,
+        ));
+        let mut notify_rx = self.notify_rx;
+        let mut rate_limit = self.rate_limit;
+        let res = loop {
+            select! {
+                notification = notify_rx.recv() => {
+                    // Every monitor has stopped.
+                    let Some(notification) = notification else {
+                        break Ok(());
+                    };
+                    if notification.severity < config.min_severity {
+                        debug!(
+                            "[{}] Dropping {} notification from `{}`",
+                            config.name,
+                            notification.severity.as_str(),
+                            notification.monitor
+                        );
+                        continue;
+                    }
+                    if let Some(rate_limit) = &mut rate_limit {
+                        let Some(suppressed) = rate_limit.allow(&notification.monitor) else {
+                            debug!(
+                                "[{}] Dropping notification from `{}`: more than {} per hour",
+                                config.name,
+                                notification.monitor,
+                                rate_limit.max_per_hour()
+                            );
+                            continue;
+                        };
+                        if suppressed > 0 {
+                            let summary = Self::suppressed(&notification.monitor, suppressed, &config);
+                            Self::dispatch(summary, &interval, &pending, &wake).await;
+                        }
+                    }
+                    info!("Received notification");
+                    Self::dispatch(notification, &interval, &pending, &wake).await;
+                }
+                Some(suppressed) = Self::refill(&mut rate_limit) => {
+                    for (monitor, suppressed) in suppressed {
+                        let summary = Self::suppressed(&monitor, suppressed, &config);
+                        Self::dispatch(summary, &interval, &pending, &wake).await;
+                    }
+                }
+                Some(_) = Self::tick(&mut interval) => {
+                    let mut pending = pending.lock().await;
+                    if pending.queue.is_empty() {
+                        info!("Tick...");
+                        continue;
+                    }
+                    if pending.queue.len() > 1 {
+                        info!("Sending aggregate");
+                    }
+                    let queue = take(&mut pending.queue);
+                    pending.push_outgoing(queue, &wake);
+                    pending.store().await;
+                }
+            }
+        };
+        pending.lock().await.closed = true;
+        wake.notify_one();
+        res
+    }
+
+    /// Sends a notification now, or queues it if notifications are aggregated.
+    async fn dispatch(
+        notification: Notification,
+        interval: &Option<Ticker>,
+        pending: &Mutex<Pending>,
+        wake: &Notify,
+    ) {
+        let mut pending = pending.lock().await;
+        match interval {
+            None => pending.push_outgoing(vec![notification], wake),
+            Some(_) => pending.queue.push(notification),
+        }
+        pending.store().await;
+    }
+
+    /// Sends each notification, or a digest of several, in the order they were dispatched, until
+    /// the aggregator stops. Each stays in the stored queue until it's delivered or its retries
+    /// are exhausted, so that it's sent again if ramon stops first.
+    async fn deliver_outgoing(
+        pending: Arc<Mutex<Pending>>,
+        wake: Arc<Notify>,
+        config: Arc<NotificationConfig>,
+        fallback: Option<Sender<Notification>>,
+    ) {
+        loop {
+            let next = {
+                let pending = pending.lock().await;
+                match pending.outgoing.front() {
+                    Some(notifications) => Some(notifications.clone()),
+                    None if pending.closed => return,
+                    None => None,
+                }
+            };
+            let Some(mut notifications) = next else {
+                wake.notified().await;
+                continue;
+            };
+            match notifications.len() {
+                1 => Self::send(notifications.pop().unwrap(), &config, &fallback).await,
+                _ => Self::send_digest(notifications, &config, &fallback).await,
+            }
+            let mut pending = pending.lock().await;
+            pending.outgoing.pop_front();
+            pending.store().await;
+        }
+    }
+
+    /// Returns a notification summarizing the notifications from `monitor` that were dropped by
+    /// the rate limit.
+    fn suppressed(monitor: &str, suppressed: usize, config: &NotificationConfig) -> Notification {
+        let plural = match suppressed {
+            1 => "",
+            _ => "s",
+        };
+        Notification {
+            r#type: config.name.clone(),
+            monitor: monitor.to_owned(),
+            severity: Severity::Warning,
+            title: format!("Suppressed {suppressed} notification{plural} from {monitor}"),
+            body: format!(
+                "`{monitor}` sent more notifications than the limit of `max_per_hour` allows."
+            ),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or_default(),
+            variables: HashMap::from([("suppressed".into(), (suppressed as i64).into())]),
+            highlight: None,
+            resolved: false,
+        }
+    }
+
+    /// Sends queued notifications. Channels that accept batches receive every notification
+    /// individually in one payload; all other channels receive one concatenated notification.
+    async fn send_digest(
+        queue: Vec<Notification>,
+        config: &NotificationConfig,
+        fallback: &Option<Sender<Notification>>,
+    ) {
+        let (title, body) = match config.group_by_monitor {
+            false => (
+                "Ramon Aggregated Notification".into(),
+                queue
+                    .iter()
+                    .map(|notification| {
+                        highlight::append(
+                            &notification.body,
+                            notification.highlight.as_ref(),
+                            Highlight::plain,
+                        )
+                    })
+                    .collect::<Vec<String>>()
+                    .join("\n"),
+            ),
+            true => Self::group_by_monitor(&queue),
+        };
+        let digest = Notification {
+            r#type: config.name.clone(),
+            monitor: String::new(),
+            severity: queue
+                .iter()
+                .map(|notification| notification.severity)
+                .max()
+                .unwrap_or(Severity::Info),
+            title,
+            body,
+            timestamp: queue.last().map(|n| n.timestamp).unwrap_or_default(),
+            variables: HashMap::new(),
+            highlight: None,
+            resolved: queue.iter().all(|notification| notification.resolved),
+        };
+
+        let id = notifier::delivery_id();
+        let delivery = Delivery {
+            name: &config.name,
+            id: &id,
+        };
+        let mut delivered = true;
+        for notifier in &config.notifiers {
+            if notifier.splits_digests() {
+                for notification in queue.iter().filter(|n| notifier.accepts(n)) {
+                    delivered &= attempt(config, notifier.as_ref(), || {
+                        notifier.send(notification, &delivery)
+                    })
+                    .await
+                    .is_ok();
+                }
+            } else if notifier.accepts(&digest) {
+                delivered &= attempt(config, notifier.as_ref(), || {
+                    notifier.send_digest(&digest, &queue, &delivery)
+                })
+                .await
+                .is_ok();
+            }
+        }
+
+        let internal = queue
+            .iter()
+            .any(|notification| notification.monitor == meta::MONITOR);
+        if !delivered {
+            Self::fall_back(queue, config, fallback).await;
+        }
+        if internal {
+            meta::delivered();
+        }
+    }
+
+    /// Returns the title and body of a digest with one section per monitor, in the order that
+    /// each monitor first sent a notification.
+    fn group_by_monitor(queue: &[Notification]) -> (String, String) {
+        let mut sections: Vec<(&str, Vec<&Notification>)> = Vec::new();
+        for notification in queue {
+            match sections
+                .iter_mut()
+                .find(|(monitor, _)| *monitor == notification.monitor)
+            {
+                Some((_, notifications)) => notifications.push(notification),
+                None => sections.push((&notification.monitor, vec![notification])),
+            }
+        }
+
+        let title = format!(
+            "Ramon digest: {} notifications from {} monitors",
+            queue.len(),
+            sections.len()
+        );
+        let body = sections
+            .iter()
+            .map(|(monitor, notifications)| {
+                let heading = format!("{monitor} ({})", notifications.len());
+                let mut section = format!("{heading}\n{}", "=".repeat(heading.chars().count()));
+                for notification in notifications {
+                    section += &format!("\n\n- {}", notification.title);
+                    let body = highlight::append(
+                        &notification.body,
+                        notification.highlight.as_ref(),
+                        Highlight::plain,
+                    );
+                    for line in body.lines() {
+                        match line.is_empty() {
+                            true => section += "\n",
+                            false => section += &format!("\n  {line}"),
+                        }
+                    }
+                }
+                section
+            })
+            .collect::<Vec<String>>()
+            .join("\n\n");
+        (title, body)
+    }
+
+    async fn send(
+        notification: Notification,
+        config: &NotificationConfig,
+        fallback: &Option<Sender<Notification>>,
+    ) {
+        info!("Sending notification '{}'", notification.title);
+
+        let delivered = Self::deliver(&notification, config)
+            .await
+            .iter()
+            .all(|(_, res)| res.is_ok());
+        let internal = notification.monitor == meta::MONITOR;
+        if !delivered {
+            Self::fall_back(vec![notification], config, fallback).await;
+        }
+        if internal {
+            meta::delivered();
+        }
+    }
+
+    /// Delivers a notification through every channel of `config`, returning the result of each.
+    #[instrument(name = "deliver", skip_all, fields(notify = %config.name, monitor = %notification.monitor, failed = field::Empty))]
+    pub async fn deliver(
+        notification: &Notification,
+        config: &NotificationConfig,
+    ) -> Vec<(&'static str, Result<()>)> {
+        let id = notifier::delivery_id();
+        let delivery = Delivery {
+            name: &config.name,
+            id: &id,
+        };
+        let mut results = Vec::new();
+        for notifier in &config.notifiers {
+            if !notifier.accepts(notification) {
+                continue;
+            }
+            let res = attempt(config, notifier.as_ref(), || {
+                notifier.send(notification, &delivery)
+            })
+            .await;
+            results.push((notifier.channel(), res));
+        }
+
+        for (channel, res) in &results {
+            let mut details = serde_json::json!({
+                "notify": config.name,
+                "channel": channel,
+                "title": notification.title,
+                "success": res.is_ok(),
+            });
+            if let Err(err) = res {
+                details["error"] = err.to_string().into();
+            }
+            events::record("delivery", &notification.monitor, &details);
+            audit::record("delivery", &notification.monitor, details);
+        }
+        let failed = results
+            .iter()
+            .filter(|(_, res)| res.is_err())
+            .map(|(channel, _)| *channel)
+            .collect::<Vec<&str>>();
+        if !failed.is_empty() {
+            Span::current().record("failed", failed.join(","));
+        }
+        results
+    }
+
+    /// Forwards notifications that could not be delivered to the fallback config, if any.
+    async fn fall_back(
+        notifications: Vec<Notification>,
+        config: &NotificationConfig,
+        fallback: &Option<Sender<Notification>>,
+    ) {
+        let Some(fallback) = fallback else {
+            if !meta::is_target(&config.name) {
+                let title = format!("Notifications to `{}` could not be delivered", config.name);
+                let err = format!(
+                    "{} notifications could not be delivered after {} retries. Check ramon's log for the errors.",
+                    notifications.len(),
+                    config.retries
+                );
+                meta::report(&config.name, Severity::Warning, title, &err);
+            }
+            return;
+        };
+        info!(
+            "[{}] Sending {} undelivered notifications to the fallback",
+            config.name,
+            notifications.len()
+        );
+        for notification in notifications {
+            if fallback.send(notification).await.is_err() {
+                break;
+            }
+        }
+    }
+
+    /// Waits until a monitor whose notifications were dropped may send again, and returns the
+    /// number each one dropped.
+    async fn refill(rate_limit: &mut Option<RateLimit>) -> Option<Vec<(String, usize)>> {
+        let rate_limit = rate_limit.as_mut()?;
+        let refill = rate_limit.next_refill()?;
+        rate_limit.clock().sleep_until(refill).await;
+        Some(rate_limit.take_suppressed())
+    }
+
+    async fn tick(interval: &mut Option<Ticker>) -> Option<Instant> {
+        match interval {
+            None => None,
+            Some(interval) => Some(interval.tick().await),
+        }
+    }
+}
+
+/// Sends through `notifier`, retrying if it allows.
+async fn attempt<'a>(
+    config: &NotificationConfig,
+    notifier: &dyn Notifier,
+    mut send: impl FnMut() -> BoxFuture<'a, Result<()>>,
+) -> Result<()> {
+    match notifier.retries() {
+        true => try_with_retries(config, send).await,
+        false => send().await,
+    }
+}
+
+/// Runs `send` until it succeeds or every retry has failed, logging each failure, and returns the
+/// last error. The delay between attempts doubles each time, with up to 50% jitter so that many
+/// instances don't retry in lockstep.
+async fn try_with_retries<F, Fut>(config: &NotificationConfig, mut send: F) -> Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let mut delay = config.retry_delay;
+    let mut retries_left = config.retries;
+    loop {
+        let err = match send().await {
+            Ok(()) => return Ok(()),
+            Err(err) => err,
+        };
+        if retries_left == 0 {
+            error!("[{}] {err}", config.name);
+            return Err(err);
+        }
+        retries_left -= 1;
+        let jitter = RandomState::new().build_hasher().finish() % 1000;
+        let jittered = delay.mul_f64(1.0 + jitter as f64 / 2000.0);
+        warn!(
+            "[{}] {err} (retrying in {:.1}s)",
+            config.name,
+            jittered.as_secs_f64()
+        );
+        config
+            .clock
+            .sleep_until(config.clock.now() + jittered)
+            .await;
+        delay *= 2;
+    }
+}
+
+/// Formats seconds since the Unix epoch as `YYYY-MM-DDTHH:MM:SSZ` (RFC 3339).
+pub fn format_rfc3339(timestamp: u64) -> String {
+    format_utc(timestamp)
+        .replacen(' ', "T", 1)
+        .replace(" UTC", "Z")
+}
+
+/// Formats seconds since the Unix epoch as `YYYY-MM-DD HH:MM:SS UTC`.
+pub fn format_utc(timestamp: u64) -> String {
+    let (days, seconds) = (timestamp / 86400, timestamp % 86400);
+    // Howard Hinnant's `civil_from_days`.
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
@@ -0,0 +1,102 @@
+//! Posts notifications to Prometheus Alertmanager as alerts, through its v2 API.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde_json::{json, Value};
+
+use crate::{
+    aggregator::format_rfc3339,
+    config::Notification,
+    error::{Error, ErrorKind},
+    highlight::{self, Highlight},
+    notifier::{Delivery, Notifier},
+    source::BoxFuture,
+};
+
+/// The `alertmanager` channel.
+pub struct AlertmanagerConfig {
+    /// The base URL of Alertmanager (e.g. `http://localhost:9093`).
+    pub url: String,
+    /// Labels added to every alert (e.g. `env = "prod"`).
+    pub labels: BTreeMap<String, String>,
+}
+
+/// Aggregated notifications are posted as separate alerts rather than as a digest.
+impl Notifier for AlertmanagerConfig {
+    fn channel(&self) -> &'static str {
+        "alertmanager"
+    }
+
+    fn send<'a>(
+        &'a self,
+        notification: &'a Notification,
+        _delivery: &'a Delivery,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(send(std::slice::from_ref(notification), self))
+    }
+
+    fn send_digest<'a>(
+        &'a self,
+        _digest: &'a Notification,
+        queue: &'a [Notification],
+        _delivery: &'a Delivery,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(send(queue, self))
+    }
+}
+
+/// Posts one alert per notification.
+async fn send(notifications: &[Notification], config: &AlertmanagerConfig) -> Result<()> {
+    let alerts = notifications
+        .iter()
+        .map(|notification| alert(notification, config))
+        .collect::<Vec<Value>>();
+    let res = reqwest::Client::new()
+        .post(format!(
+            "{}/api/v2/alerts",
+            config.url.trim_end_matches('/')
+        ))
+        .json(&alerts)
+        .send()
+        .await
+        .and_then(|res| res.error_for_status());
+    match res {
+        Ok(_) => Ok(()),
+        Err(err) => Err(Error::new(
+            ErrorKind::Alertmanager,
+            format!("Failed to post to Alertmanager: {err}"),
+        )
+        .into()),
+    }
+}
+
+/// Alertmanager identifies alerts by their labels, so they only include the monitor and its
+/// severity; a resolve notification has the same labels as the alert it resolves.
+fn alert(notification: &Notification, config: &AlertmanagerConfig) -> Value {
+    let mut labels = config.labels.clone();
+    let alertname = match notification.monitor.is_empty() {
+        true => &notification.r#type,
+        false => &notification.monitor,
+    };
+    labels.insert("alertname".into(), alertname.clone());
+    labels.insert("severity".into(), notification.severity.as_str().into());
+
+    let mut annotations = BTreeMap::from([("summary", notification.title.clone())]);
+    let description = highlight::append(
+        &notification.body,
+        notification.highlight.as_ref(),
+        Highlight::plain,
+    );
+    if !description.is_empty() {
+        annotations.insert("description", description);
+    }
+
+    let time = format_rfc3339(notification.timestamp);
+    // Without `endsAt`, Alertmanager resolves the alert after its `resolve_timeout`, unless it's
+    // posted again.
+    match notification.resolved {
+        true => json!({ "labels": labels, "annotations": annotations, "endsAt": time }),
+        false => json!({ "labels": labels, "annotations": annotations, "startsAt": time }),
+    }
+}
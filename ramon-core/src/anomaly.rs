@@ -0,0 +1,108 @@
+//! Detects spikes in the rate of lines, for `anomaly`.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+use tokio::time::Instant;
+use toml::Value;
+
+const MINUTE: Duration = Duration::from_secs(60);
+/// How many minutes of history are needed before a rate can be anomalous.
+const MIN_HISTORY: usize = 10;
+
+/// `anomaly`.
+pub struct AnomalyConfig {
+    /// The rate is anomalous if it's this many times the baseline.
+    pub factor: Option<f64>,
+    /// The rate is anomalous if it's this many standard deviations above the baseline.
+    pub z_score: Option<f64>,
+    /// How much history the baseline is computed from.
+    pub baseline: Duration,
+}
+
+/// Counts lines per minute and compares the rate of the current minute to the rates of the
+/// previous minutes.
+pub struct Anomaly {
+    config: AnomalyConfig,
+    /// The counts of the previous minutes, oldest first.
+    history: VecDeque<u32>,
+    /// When the current minute started.
+    minute_start: Option<Instant>,
+    count: u32,
+    /// Whether the current minute already fired, so that each minute fires at most once.
+    fired: bool,
+}
+
+impl Anomaly {
+    /// Starts without any history.
+    pub fn new(config: AnomalyConfig) -> Self {
+        Self {
+            config,
+            history: VecDeque::new(),
+            minute_start: None,
+            count: 0,
+            fired: false,
+        }
+    }
+
+    /// Counts a line. Returns the variables `rate`, `baseline`, and `z_score` the first time the
+    /// rate of the current minute is anomalous.
+    pub fn record(&mut self, now: Instant) -> Option<HashMap<String, Value>> {
+        self.advance(now);
+        self.count += 1;
+        if self.fired || self.history.len() < MIN_HISTORY {
+            return None;
+        }
+
+        let len = self.history.len() as f64;
+        let mean = self.history.iter().map(|&count| count as f64).sum::<f64>() / len;
+        let variance = self
+            .history
+            .iter()
+            .map(|&count| (count as f64 - mean).powi(2))
+            .sum::<f64>()
+            / len;
+        // A flat baseline would make any increase infinitely anomalous.
+        let std_dev = variance.sqrt().max(1.0);
+        let rate = self.count as f64;
+        let z_score = (rate - mean) / std_dev;
+
+        // Every criterion that's set must be exceeded.
+        let below_factor = self
+            .config
+            .factor
+            .is_some_and(|factor| rate <= mean.max(1.0) * factor);
+        let below_z_score = self.config.z_score.is_some_and(|z| z_score <= z);
+        if below_factor || below_z_score {
+            return None;
+        }
+        self.fired = true;
+        Some(HashMap::from([
+            ("rate".into(), (self.count as i64).into()),
+            ("baseline".into(), mean.into()),
+            ("z_score".into(), z_score.into()),
+        ]))
+    }
+
+    /// Moves the finished minutes into the history, including minutes without any lines.
+    fn advance(&mut self, now: Instant) {
+        let minute_start = *self.minute_start.get_or_insert(now);
+        let elapsed = now.duration_since(minute_start).as_secs() / MINUTE.as_secs();
+        if elapsed == 0 {
+            return;
+        }
+        let max_len = (self.config.baseline.as_secs() / MINUTE.as_secs()).max(1) as usize;
+        self.history.push_back(self.count);
+        for _ in 1..elapsed.min(max_len as u64) {
+            self.history.push_back(0);
+        }
+        while self.history.len() > max_len {
+            self.history.pop_front();
+        }
+        self.minute_start = Some(minute_start + MINUTE * elapsed as u32);
+        self.count = 0;
+        self.fired = false;
+    }
+}
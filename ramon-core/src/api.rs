@@ -0,0 +1,241 @@
+//! An HTTP server that lets automation control a running instance: listing monitors, pausing and
+//! resuming them, overriding their cooldowns, silencing them, and injecting test lines.
+
+use std::{net::SocketAddr, path::PathBuf};
+
+use anyhow::{anyhow, Result};
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::{
+    body::{Bytes, Incoming},
+    header::CONTENT_TYPE,
+    server::conn::http1,
+    service::service_fn,
+    Method, Request, Response, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use log::{debug, info, warn};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tokio::net::TcpListener;
+
+use crate::{
+    error::{Error, ErrorKind},
+    ingest,
+    monitor::Event,
+    overrides, silence, status,
+};
+
+/// Larger request bodies are rejected.
+const MAX_BODY_LEN: usize = 1024 * 1024;
+
+/// `api`, the server that controls monitors at runtime.
+pub struct ApiConfig {
+    /// The address the server listens on.
+    pub bind: SocketAddr,
+    /// The bearer token that every request must have.
+    pub token: String,
+    /// The file that changes are saved to, so that they outlast a restart.
+    pub overrides: Option<PathBuf>,
+}
+
+/// The server that pauses, resumes, silences, and tunes monitors.
+pub struct ApiServer {
+    listener: TcpListener,
+    token: String,
+}
+
+impl ApiServer {
+    /// Binds `bind`, requiring `token` on every request.
+    pub async fn new(bind: SocketAddr, token: String) -> Result<Self> {
+        let listener = TcpListener::bind(bind).await.map_err(|err| {
+            Error::new(
+                ErrorKind::ApiBind,
+                format!("Failed to bind API server to {bind}: {err}"),
+            )
+        })?;
+        info!("Listening for API requests on {bind}");
+        Ok(Self { listener, token })
+    }
+
+    /// Serves requests until the listener fails.
+    pub async fn start(self) -> Result<()> {
+        let token_hash = Sha256::digest(&self.token);
+        loop {
+            let (stream, peer) = self.listener.accept().await?;
+            let service = service_fn(move |req| handle(req, peer, token_hash));
+            tokio::spawn(async move {
+                if let Err(err) = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    debug!("API connection from {peer}: {err}");
+                }
+            });
+        }
+    }
+}
+
+async fn handle(
+    req: Request<Incoming>,
+    peer: SocketAddr,
+    token_hash: impl AsRef<[u8]>,
+) -> Result<Response<Full<Bytes>>> {
+    if !ingest::authorized(req.headers(), token_hash.as_ref()) {
+        warn!("Rejected API request from {peer} with a missing or invalid token");
+        return Ok(respond(
+            StatusCode::UNAUTHORIZED,
+            json!({ "error": "Invalid token" }),
+        ));
+    }
+
+    let path = req.uri().path().to_owned();
+    let method = req.method().clone();
+    let body = match Limited::new(req.into_body(), MAX_BODY_LEN).collect().await {
+        Ok(body) => body.to_bytes(),
+        Err(_) => {
+            return Ok(respond(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                json!({ "error": "Body is too large" }),
+            ))
+        }
+    };
+    let body = String::from_utf8_lossy(&body);
+
+    let res = match path.as_str() {
+        "/monitors" if method == Method::GET => Ok(monitors()),
+        "/monitors" => return Ok(method_not_allowed()),
+        _ => {
+            let Some((name, action)) = path
+                .strip_prefix("/monitors/")
+                .and_then(|path| path.rsplit_once('/'))
+            else {
+                return Ok(respond(
+                    StatusCode::NOT_FOUND,
+                    json!({ "error": "Not found" }),
+                ));
+            };
+            debug!("API request from {peer}: {method} {path}");
+            match (&method, action) {
+                (&Method::POST, "pause") => pause(name, true),
+                (&Method::POST, "resume") => pause(name, false),
+                (&Method::PUT, "cooldown") => set_cooldown(name, Some(body.trim())),
+                (&Method::DELETE, "cooldown") => set_cooldown(name, None),
+                (&Method::PUT, "silence") => set_silence(name, body.trim()),
+                (&Method::DELETE, "silence") => end_silence(name),
+                (&Method::POST, "lines") => return inject(name, &body).await,
+                (_, "pause" | "resume" | "cooldown" | "silence" | "lines") => {
+                    return Ok(method_not_allowed())
+                }
+                _ => {
+                    return Ok(respond(
+                        StatusCode::NOT_FOUND,
+                        json!({ "error": "Not found" }),
+                    ))
+                }
+            }
+        }
+    };
+    Ok(match res {
+        Ok(value) => respond(StatusCode::OK, value),
+        Err(err) => respond(StatusCode::BAD_REQUEST, json!({ "error": err.to_string() })),
+    })
+}
+
+/// Lists the status of each monitor, along with its cooldown in seconds.
+fn monitors() -> Value {
+    let mut monitors = status::snapshot().monitors;
+    let cooldowns = overrides::cooldowns();
+    let monitors = cooldowns
+        .into_iter()
+        .map(|(name, cooldown)| {
+            let mut monitor = serde_json::to_value(monitors.remove(&name).unwrap_or_default())
+                .unwrap_or_default();
+            monitor["cooldown"] = cooldown.map(|cooldown| cooldown.as_secs()).into();
+            (name, monitor)
+        })
+        .collect::<serde_json::Map<String, Value>>();
+    json!({ "monitors": monitors })
+}
+
+fn pause(name: &str, paused: bool) -> Result<Value> {
+    overrides::set_paused(name, paused)?;
+    overrides::save();
+    Ok(json!({ "paused": paused }))
+}
+
+/// Overrides the cooldown of `name` with a duration such as `5m`, or restores the configured
+/// cooldown.
+fn set_cooldown(name: &str, cooldown: Option<&str>) -> Result<Value> {
+    let cooldown = cooldown
+        .map(|cooldown| {
+            duration_str::parse(cooldown).map_err(|err| anyhow!("Failed to parse duration: {err}"))
+        })
+        .transpose()?;
+    overrides::set_cooldown(name, cooldown)?;
+    overrides::save();
+    let cooldown = overrides::cooldowns().remove(name).flatten();
+    Ok(json!({ "cooldown": cooldown.map(|cooldown| cooldown.as_secs()) }))
+}
+
+fn set_silence(name: &str, duration: &str) -> Result<Value> {
+    let duration =
+        duration_str::parse(duration).map_err(|err| anyhow!("Failed to parse duration: {err}"))?;
+    let until = silence::silence(name, duration)?;
+    overrides::save();
+    Ok(json!({ "until": until }))
+}
+
+fn end_silence(name: &str) -> Result<Value> {
+    let suppressed = silence::unsilence(name)?;
+    overrides::save();
+    Ok(json!({ "suppressed": suppressed }))
+}
+
+/// Sends each line of `body` to `name` as if it had been read from its log.
+async fn inject(name: &str, body: &str) -> Result<Response<Full<Bytes>>> {
+    let event_tx = match overrides::sender(name) {
+        Ok(event_tx) => event_tx,
+        Err(err) => {
+            return Ok(respond(
+                StatusCode::NOT_FOUND,
+                json!({ "error": err.to_string() }),
+            ))
+        }
+    };
+    let lines = body
+        .lines()
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<&str>>();
+    info!("[{name}] Injecting {} test lines", lines.len());
+    for line in &lines {
+        if event_tx
+            .send(Event::NewLogLine((*line).to_owned()))
+            .await
+            .is_err()
+        {
+            return Ok(respond(
+                StatusCode::SERVICE_UNAVAILABLE,
+                json!({ "error": "Monitor has exited" }),
+            ));
+        }
+    }
+    Ok(respond(
+        StatusCode::ACCEPTED,
+        json!({ "injected": lines.len() }),
+    ))
+}
+
+fn method_not_allowed() -> Response<Full<Bytes>> {
+    respond(
+        StatusCode::METHOD_NOT_ALLOWED,
+        json!({ "error": "Method not allowed" }),
+    )
+}
+
+fn respond(status: StatusCode, body: Value) -> Response<Full<Bytes>> {
+    let mut res = Response::new(Full::new(Bytes::from(format!("{body}\n"))));
+    *res.status_mut() = status;
+    res.headers_mut()
+        .insert(CONTENT_TYPE, "application/json".parse().unwrap());
+    res
+}
@@ -0,0 +1,80 @@
+//! An append-only log of every match, command run, and notification delivery, as JSON lines, for
+//! keeping a record of what ramon did on its own (e.g. banning an address).
+
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::OnceLock,
+    thread,
+};
+
+use anyhow::Result;
+use log::{debug, warn};
+use serde_json::{Map, Value};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+use crate::{
+    error::{Error, ErrorKind},
+    state, status,
+};
+
+/// Sends lines to the thread that writes them, so that monitors don't wait on the disk.
+static AUDIT: OnceLock<UnboundedSender<String>> = OnceLock::new();
+
+/// Opens the audit log at `path`, creating it if it doesn't exist.
+pub fn init(path: &Path) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    // Matched lines may be sensitive, so only ramon's user can read the log.
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let file = options.open(path).map_err(|err| {
+        Error::new(
+            ErrorKind::AuditOpen,
+            format!("Failed to open audit log {path:?}: {err}"),
+        )
+    })?;
+    let (line_tx, line_rx) = unbounded_channel();
+    if AUDIT.set(line_tx).is_ok() {
+        thread::Builder::new()
+            .name("audit".into())
+            .spawn(move || write_lines(file, line_rx))?;
+    }
+    Ok(())
+}
+
+/// Returns whether there's an audit log, so that entries are only built if they're written.
+pub fn enabled() -> bool {
+    AUDIT.get().is_some()
+}
+
+/// Appends an entry with the keys `timestamp`, `event`, and `monitor`, along with the keys of
+/// `details`, which must be an object.
+pub fn record(event: &str, monitor: &str, details: Value) {
+    let Some(audit) = AUDIT.get() else {
+        return;
+    };
+    if state::under_disk_pressure() {
+        debug!("Skipping audit entry for `{monitor}`.");
+        return;
+    }
+    let mut entry = match details {
+        Value::Object(details) => details,
+        _ => Map::new(),
+    };
+    entry.insert("timestamp".into(), status::now().into());
+    entry.insert("event".into(), event.into());
+    entry.insert("monitor".into(), monitor.into());
+    let _ = audit.send(format!("{}\n", Value::Object(entry)));
+}
+
+/// Appends each line to the audit log, in the order they were recorded.
+fn write_lines(mut file: File, mut line_rx: UnboundedReceiver<String>) {
+    while let Some(line) = line_rx.blocking_recv() {
+        // Lines are written whole so that concurrent entries don't interleave.
+        if let Err(err) = file.write_all(line.as_bytes()) {
+            warn!("Failed to write to the audit log: {err}");
+        }
+    }
+}
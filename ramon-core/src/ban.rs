@@ -0,0 +1,215 @@
+//! Bans values that a monitor sees too often (usually IPs), through nftables, ipset, or commands,
+//! and unbans them once their ban expires.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+    num::NonZeroUsize,
+    process::Stdio,
+    time::Duration,
+};
+
+use anyhow::{bail, Result};
+use log::{error, info, warn};
+use tera::{Context, Tera};
+use tokio::{process::Command, time::Instant};
+use toml::Value;
+
+use crate::{
+    audit,
+    clock::SharedClock,
+    config::value_to_string,
+    error::{Error, ErrorKind},
+    platform::Shell,
+    template,
+};
+
+/// The `ban` action.
+pub struct BanConfig {
+    /// The variable to ban (e.g. `ip`).
+    pub variable: String,
+    /// Ban a value once it has been seen this many times within this duration.
+    pub threshold: Option<(NonZeroUsize, Duration)>,
+    /// How long values stay banned.
+    pub time: Duration,
+    /// How values are banned.
+    pub backend: BanBackend,
+}
+
+/// How values are banned.
+pub enum BanBackend {
+    /// Adds banned IPs to an nftables set (e.g. `inet filter ramon`).
+    Nftables(String),
+    /// Adds banned IPs to an ipset, for use with iptables.
+    Ipset(String),
+    /// Runs shell commands to ban and unban values.
+    Command {
+        /// Bans the value, which is in the environment variable named after `variable`.
+        ban: String,
+        /// Unbans the value, like `ban`.
+        unban: String,
+    },
+}
+
+/// Bans values (usually IP addresses) that exceed a threshold, and unbans them after some time.
+pub struct Ban {
+    config: BanConfig,
+    templates: Tera,
+    history: HashMap<String, VecDeque<Instant>>,
+    banned: HashMap<String, Instant>,
+    clock: SharedClock,
+    shell: Shell,
+}
+
+impl Ban {
+    /// Parses the templates of the backend.
+    pub fn new(config: BanConfig, clock: SharedClock, shell: Shell) -> Result<Self> {
+        let mut templates = template::new_engine();
+        if let BanBackend::Command { ban, unban } = &config.backend {
+            template::add(&mut templates, "ban", ban)?;
+            template::add(&mut templates, "unban", unban)?;
+        }
+        Ok(Self {
+            config,
+            templates,
+            history: HashMap::new(),
+            banned: HashMap::new(),
+            clock,
+            shell,
+        })
+    }
+
+    /// Records an occurrence of the configured variable, and bans its value if the threshold is
+    /// exceeded.
+    pub async fn record(&mut self, name: &str, variables: &HashMap<String, Value>) {
+        let value = match variables.get(&self.config.variable) {
+            None => {
+                warn!(
+                    "[{name}] Cannot ban: `{}` is not set.",
+                    self.config.variable
+                );
+                return;
+            }
+            Some(value) => value_to_string(value.clone()),
+        };
+        if self.banned.contains_key(&value) {
+            return;
+        }
+
+        if let Some((threshold, duration)) = self.config.threshold {
+            let now = self.clock.now();
+            // Forget values that have not been seen recently.
+            self.history.retain(|_, history| {
+                history
+                    .back()
+                    .is_some_and(|time| now.duration_since(*time) <= duration)
+            });
+            let history = self.history.entry(value.clone()).or_default();
+            history.push_back(now);
+            while history
+                .front()
+                .is_some_and(|time| now.duration_since(*time) > duration)
+            {
+                history.pop_front();
+            }
+            if history.len() < threshold.get() {
+                return;
+            }
+        }
+        self.history.remove(&value);
+
+        info!("[{name}] Banning {value} for {:?}.", self.config.time);
+        let res = self.run(true, &value).await;
+        audit(name, "ban", &value, &res);
+        if let Err(err) = res {
+            error!("[{name}] Failed to ban {value}: {err}");
+            return;
+        }
+        self.banned
+            .insert(value, self.clock.now() + self.config.time);
+    }
+
+    /// Returns when the next value should be unbanned.
+    pub fn next_unban(&self) -> Option<Instant> {
+        self.banned.values().min().copied()
+    }
+
+    /// Unbans every value whose ban has expired.
+    pub async fn unban_expired(&mut self, name: &str) {
+        let now = self.clock.now();
+        let expired = self
+            .banned
+            .iter()
+            .filter(|(_, until)| **until <= now)
+            .map(|(value, _)| value.clone())
+            .collect::<Vec<String>>();
+        for value in expired {
+            self.banned.remove(&value);
+            info!("[{name}] Unbanning {value}.");
+            let res = self.run(false, &value).await;
+            audit(name, "unban", &value, &res);
+            if let Err(err) = res {
+                error!("[{name}] Failed to unban {value}: {err}");
+            }
+        }
+    }
+
+    async fn run(&self, ban: bool, value: &str) -> Result<()> {
+        let mut command = match &self.config.backend {
+            BanBackend::Nftables(set) => {
+                let ip = parse_ip(value)?;
+                let mut command = Command::new("nft");
+                command.args([if ban { "add" } else { "delete" }, "element"]);
+                command.args(set.split_whitespace());
+                command.arg(format!("{{ {ip} }}"));
+                command
+            }
+            BanBackend::Ipset(set) => {
+                let ip = parse_ip(value)?;
+                let mut command = Command::new("ipset");
+                command.args([if ban { "add" } else { "del" }, set, &ip.to_string()]);
+                command.arg("-exist");
+                command
+            }
+            // The value is only passed in the environment, since captured text rendered into the
+            // script could run commands of its own.
+            BanBackend::Command { .. } => {
+                let template_name = if ban { "ban" } else { "unban" };
+                let mut command = self.shell.command(&template::render(
+                    &self.templates,
+                    template_name,
+                    &Context::new(),
+                )?);
+                command.env(&self.config.variable, value);
+                command
+            }
+        };
+
+        let status =
+            command.stdin(Stdio::null()).status().await.map_err(|err| {
+                Error::new(ErrorKind::ExecSpawn, format!("Failed to spawn: {err}"))
+            })?;
+        if !status.success() {
+            bail!("Command exited with {status}");
+        }
+        Ok(())
+    }
+}
+
+/// Records a ban or unban in the audit log.
+fn audit(name: &str, event: &str, value: &str, res: &Result<()>) {
+    let mut details = serde_json::json!({ "value": value, "success": res.is_ok() });
+    if let Err(err) = res {
+        details["error"] = err.to_string().into();
+    }
+    audit::record(event, name, details);
+}
+
+/// Values are parsed as IP addresses before being passed to firewall commands so that captured
+/// text cannot alter the command.
+fn parse_ip(value: &str) -> Result<IpAddr> {
+    match value.parse() {
+        Ok(ip) => Ok(ip),
+        Err(_) => bail!("{value:?} is not an IP address."),
+    }
+}
@@ -0,0 +1,53 @@
+//! The internal event bus, which lets a monitor fire others with `emit`.
+
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+};
+
+use log::{debug, warn};
+use tokio::sync::mpsc::Sender;
+use toml::Value;
+
+use crate::monitor::Event;
+
+/// The monitors whose source is `{ event = "<name>" }`, by event name. Event names are prefixed
+/// with the name of their tenant, like monitor names.
+static SUBSCRIBERS: LazyLock<Mutex<HashMap<String, Vec<Sender<Event>>>>> =
+    LazyLock::new(Default::default);
+
+/// Sends the events named `event` to `event_tx`.
+pub fn subscribe(event: String, event_tx: Sender<Event>) {
+    SUBSCRIBERS
+        .lock()
+        .unwrap()
+        .entry(event)
+        .or_default()
+        .push(event_tx);
+}
+
+/// Sends an event with these variables to each monitor subscribed to `event`.
+pub fn emit(emitter: &str, event: &str, mut variables: HashMap<String, Value>) {
+    let subscribers = SUBSCRIBERS
+        .lock()
+        .unwrap()
+        .get(event)
+        .cloned()
+        .unwrap_or_default();
+    if subscribers.is_empty() {
+        debug!("[{emitter}] No monitor listens to the event `{event}`.");
+        return;
+    }
+    variables.insert("emitter".into(), emitter.into());
+    for event_tx in subscribers {
+        let variables = variables.clone();
+        let emitter = emitter.to_owned();
+        let event = event.to_owned();
+        // Sending in the background keeps monitors that emit to each other from deadlocking.
+        tokio::spawn(async move {
+            if let Err(err) = event_tx.send(Event::Internal(variables)).await {
+                warn!("[{emitter}] Failed to emit `{event}`: {err}");
+            }
+        });
+    }
+}
@@ -0,0 +1,137 @@
+//! Clocks that monitors and aggregators wait on, so that `replay` can run time faster than the
+//! system clock.
+
+use std::{future::Future, pin::Pin, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use tokio::{
+    sync::{mpsc::Sender, watch},
+    time::Instant,
+};
+
+use crate::{
+    monitor::Event,
+    source::{BoxFuture, Source},
+};
+
+/// A source of time. Cooldowns, thresholds, aggregation windows, and schedules read the time
+/// through a clock so that it can be simulated.
+pub trait Clock: Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> Instant;
+
+    /// Completes once the time reaches `deadline`.
+    fn sleep_until(&self, deadline: Instant) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+}
+
+/// A clock shared by the monitors and aggregators.
+pub type SharedClock = Arc<dyn Clock>;
+
+/// The real time.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(tokio::time::sleep_until(deadline))
+    }
+}
+
+/// A clock that only moves when it is advanced, waking every sleep whose deadline has passed.
+pub struct SimulatedClock {
+    now: watch::Sender<Instant>,
+}
+
+impl SimulatedClock {
+    /// Creates a clock that starts at `start`.
+    pub fn new(start: Instant) -> Self {
+        Self {
+            now: watch::Sender::new(start),
+        }
+    }
+
+    /// Moves the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        self.now.send_modify(|now| *now += duration);
+    }
+
+    /// Moves the clock forward to `time`. Moving backward has no effect.
+    pub fn set(&self, time: Instant) {
+        self.now.send_if_modified(|now| {
+            if time > *now {
+                *now = time;
+                return true;
+            }
+            false
+        });
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now(&self) -> Instant {
+        *self.now.borrow()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        let mut now_rx = self.now.subscribe();
+        Box::pin(async move {
+            // If the clock is dropped, sleep forever like a paused timer.
+            if now_rx.wait_for(|now| *now >= deadline).await.is_err() {
+                std::future::pending::<()>().await;
+            }
+        })
+    }
+}
+
+/// Like `tokio::time::Interval`, but driven by a clock. The first tick completes immediately.
+pub struct Ticker {
+    clock: SharedClock,
+    period: Duration,
+    next: Instant,
+}
+
+impl Ticker {
+    /// Ticks every `period` of `clock`, starting now.
+    pub fn new(clock: SharedClock, period: Duration) -> Self {
+        let next = clock.now();
+        Self {
+            clock,
+            period,
+            next,
+        }
+    }
+
+    /// Returns the time between ticks.
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Completes at the next tick, returning when it was due.
+    pub async fn tick(&mut self) -> Instant {
+        let tick = self.next;
+        self.clock.sleep_until(tick).await;
+        self.next = tick + self.period;
+        tick
+    }
+}
+
+/// The source of `every`, which sends a tick each period.
+impl Source for Ticker {
+    fn kind(&self) -> &'static str {
+        "every"
+    }
+
+    fn run(mut self: Box<Self>, event_tx: Sender<Event>) -> BoxFuture<'static, Result<()>> {
+        Box::pin(async move {
+            loop {
+                self.tick().await;
+                if event_tx.send(Event::Tick).await.is_err() {
+                    return Ok(());
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,207 @@
+//! `exec_check`, which runs a command periodically and tracks how long it takes.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    process::Stdio,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use log::warn;
+use tokio::time::{timeout, Instant};
+use toml::Value;
+
+use crate::{
+    error::{Error, ErrorKind},
+    platform::Shell,
+    state::{self, Format},
+};
+
+/// The number of runs kept in the history.
+const HISTORY_LEN: usize = 100;
+/// The number of most recent runs compared against the rest of the history by `duration_trend`.
+const RECENT_LEN: usize = 10;
+/// One `<unix timestamp> <duration in seconds> <exit code>` run per line.
+const FORMAT: Format = Format {
+    kind: "runs",
+    migrations: &[state::unchanged],
+    essential: false,
+};
+
+/// Runs a command each time `every` fires and records how long it took and whether it succeeded,
+/// so that checks that slowly degrade can be noticed before they fail outright.
+///
+/// The last 100 runs are persisted to `runs_<monitor name>` in the cache directory.
+pub struct CommandCheck {
+    command: String,
+    shell: Shell,
+    /// Commands that run for longer than this are killed and count as failures.
+    timeout: Duration,
+    history: VecDeque<Run>,
+}
+
+struct Run {
+    timestamp: u64,
+    duration: f64,
+    /// -1 if the command could not be spawned, timed out, or was killed by a signal.
+    exit_code: i64,
+}
+
+impl CommandCheck {
+    /// Loads the history of runs of the monitor `name`.
+    pub async fn load(
+        name: &str,
+        command: String,
+        shell: Shell,
+        timeout: Duration,
+    ) -> Result<Self> {
+        let mut history = VecDeque::new();
+        if let Some(contents) = FORMAT.read(name).await? {
+            for line in contents.lines() {
+                let mut fields = line.split(' ');
+                let (Some(timestamp), Some(duration), Some(exit_code)) = (
+                    fields.next().and_then(|field| field.parse().ok()),
+                    fields.next().and_then(|field| field.parse().ok()),
+                    fields.next().and_then(|field| field.parse().ok()),
+                ) else {
+                    continue;
+                };
+                history.push_back(Run {
+                    timestamp,
+                    duration,
+                    exit_code,
+                });
+            }
+        }
+        while history.len() > HISTORY_LEN {
+            history.pop_front();
+        }
+        Ok(Self {
+            command,
+            shell,
+            timeout,
+            history,
+        })
+    }
+
+    /// Runs the command and returns whether it succeeded, along with the variables describing
+    /// this run and the history.
+    pub async fn run(&mut self, name: &str) -> (bool, HashMap<String, Value>) {
+        let mut variables = HashMap::new();
+        let started = Instant::now();
+        let output = self
+            .shell
+            .command(&self.command)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .output();
+        let exit_code = match timeout(self.timeout, output).await {
+            Err(_) => {
+                let err = format!("Timed out after {:?}", self.timeout);
+                variables.insert("err".into(), err.into());
+                -1
+            }
+            Ok(Err(err)) => {
+                let err = Error::new(
+                    ErrorKind::SourceSpawn,
+                    format!(
+                        "Failed to spawn {:?} with `{}`: {err}",
+                        self.command,
+                        self.shell.program()
+                    ),
+                );
+                variables.insert("err".into(), err.to_string().into());
+                -1
+            }
+            Ok(Ok(output)) => {
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                variables.insert("output".into(), stdout.trim_end().into());
+                variables.insert("stderr".into(), stderr.trim_end().into());
+                output.status.code().map_or(-1, i64::from)
+            }
+        };
+        let duration = started.elapsed().as_secs_f64();
+
+        self.history.push_back(Run {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or_default(),
+            duration,
+            exit_code,
+        });
+        if self.history.len() > HISTORY_LEN {
+            self.history.pop_front();
+        }
+        if let Err(err) = self.store(name).await {
+            let err = Error::new(
+                ErrorKind::CacheWrite,
+                format!("Failed to store command history: {err}"),
+            );
+            warn!("[{name}] {err}");
+        }
+
+        variables.insert("duration".into(), Value::Float(duration));
+        variables.insert("exit_code".into(), exit_code.into());
+        variables.extend(self.variables());
+        (exit_code == 0, variables)
+    }
+
+    /// Returns the statistics of the history for use in conditions and templates.
+    fn variables(&self) -> HashMap<String, Value> {
+        let failures_in_row = self
+            .history
+            .iter()
+            .rev()
+            .take_while(|run| run.exit_code != 0)
+            .count();
+        let failures = self.history.iter().filter(|run| run.exit_code != 0).count();
+        let durations = self.history.iter().map(|run| run.duration);
+        let duration_avg = average(durations.clone());
+        let duration_max = durations.fold(0.0, f64::max);
+
+        let mut variables = HashMap::from([
+            ("failures_in_row".into(), (failures_in_row as i64).into()),
+            ("runs".into(), (self.history.len() as i64).into()),
+            (
+                "success_rate".into(),
+                Value::Float(
+                    (self.history.len() - failures) as f64 * 100.0 / self.history.len() as f64,
+                ),
+            ),
+            ("duration_avg".into(), Value::Float(duration_avg)),
+            ("duration_max".into(), Value::Float(duration_max)),
+        ]);
+        // Compare the most recent runs with the ones before them.
+        if self.history.len() > RECENT_LEN {
+            let split = self.history.len() - RECENT_LEN;
+            let older = average(self.history.range(..split).map(|run| run.duration));
+            let recent = average(self.history.range(split..).map(|run| run.duration));
+            if older > 0.0 {
+                let trend = (recent - older) * 100.0 / older;
+                variables.insert("duration_trend".into(), Value::Float(trend));
+            }
+        }
+        variables
+    }
+
+    async fn store(&self, name: &str) -> Result<()> {
+        let contents = self
+            .history
+            .iter()
+            .map(|run| format!("{} {} {}\n", run.timestamp, run.duration, run.exit_code))
+            .collect::<String>();
+        FORMAT.write(name, &contents).await
+    }
+}
+
+fn average(values: impl ExactSizeIterator<Item = f64>) -> f64 {
+    let len = values.len();
+    match len {
+        0 => 0.0,
+        len => values.sum::<f64>() / len as f64,
+    }
+}
@@ -0,0 +1,107 @@
+//! `source = { command = ... }`, which reads lines from the stdout of a long-running command.
+
+use std::{process::Stdio, time::Duration};
+
+use anyhow::{anyhow, Result};
+use log::{error, info, warn};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    sync::mpsc::Sender,
+    time::{sleep, Instant},
+};
+
+use crate::{
+    error::{Error, ErrorKind},
+    monitor::Event,
+    platform::Shell,
+    source::{BoxFuture, Source},
+};
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+/// Runs a long-running shell command and sends each line of its stdout as a log line, restarting
+/// it with exponential backoff whenever it exits.
+pub struct CommandSource {
+    name: String,
+    command: String,
+    shell: Shell,
+}
+
+impl CommandSource {
+    /// `command` is run by `shell`. Errors are reported as the monitor `name`.
+    pub fn new(name: String, command: String, shell: Shell) -> Self {
+        Self {
+            name,
+            command,
+            shell,
+        }
+    }
+
+    async fn start(self, event_tx: Sender<Event>) {
+        let mut backoff = MIN_BACKOFF;
+        loop {
+            let started = Instant::now();
+            match self.run_once(&event_tx).await {
+                Ok(true) => {}
+                Ok(false) => return,
+                Err(err) => error!("[{}] {err}", self.name),
+            }
+
+            // A command that ran for a while is restarted quickly.
+            if started.elapsed() > MAX_BACKOFF {
+                backoff = MIN_BACKOFF;
+            }
+            info!("[{}] Restarting source command in {backoff:?}.", self.name);
+            sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+
+    /// Runs the command until it exits. Returns false if the monitor has stopped.
+    async fn run_once(&self, event_tx: &Sender<Event>) -> Result<bool> {
+        let mut child = self
+            .shell
+            .command(&self.command)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|err| {
+                Error::new(
+                    ErrorKind::SourceSpawn,
+                    format!(
+                        "Failed to spawn {:?} with `{}`: {err}",
+                        self.command,
+                        self.shell.program()
+                    ),
+                )
+            })?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or(anyhow!("Failed to capture stdout."))?;
+        let mut lines = BufReader::new(stdout).lines();
+        while let Some(line) = lines.next_line().await? {
+            if event_tx.send(Event::NewLogLine(line)).await.is_err() {
+                return Ok(false);
+            }
+        }
+        let status = child.wait().await?;
+        warn!("[{}] Source command exited with {status}.", self.name);
+        Ok(true)
+    }
+}
+
+impl Source for CommandSource {
+    fn kind(&self) -> &'static str {
+        "source"
+    }
+
+    fn run(self: Box<Self>, event_tx: Sender<Event>) -> BoxFuture<'static, Result<()>> {
+        Box::pin(async move {
+            self.start(event_tx).await;
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,93 @@
+//! Desktop notifications (e.g. libnotify over D-Bus), for running ramon on a workstation.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
+
+use crate::{
+    config::Notification,
+    error::{Error, ErrorKind},
+    highlight::{self, Highlight},
+    notifier::{Delivery, Notifier},
+    source::BoxFuture,
+};
+
+/// Whether showing a notification has failed, so that a missing session bus is only reported
+/// once.
+static UNAVAILABLE: AtomicBool = AtomicBool::new(false);
+
+/// The `desktop` channel, which shows notifications on the desktop of the user running ramon.
+pub struct Desktop;
+
+impl Notifier for Desktop {
+    fn channel(&self) -> &'static str {
+        "desktop"
+    }
+
+    fn send<'a>(
+        &'a self,
+        notification: &'a Notification,
+        delivery: &'a Delivery,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            match show(notification, delivery.name).await {
+                true => Ok(()),
+                false => Err(anyhow!("Failed to show a desktop notification")),
+            }
+        })
+    }
+
+    /// Failures aren't retried, because they usually mean that there is no desktop session (e.g.
+    /// when ramon runs as a system service).
+    fn retries(&self) -> bool {
+        false
+    }
+}
+
+/// Shows a desktop notification and returns whether it was shown.
+async fn show(notification: &Notification, name: &str) -> bool {
+    let mut desktop = notify_rust::Notification::new();
+    desktop
+        .appname("ramon")
+        .summary(&notification.title)
+        .body(&highlight::append(
+            &notification.body,
+            notification.highlight.as_ref(),
+            Highlight::plain,
+        ));
+    // Only the freedesktop.org specification has urgency levels.
+    #[cfg(all(unix, not(target_os = "macos")))]
+    desktop.urgency(match notification.severity {
+        crate::config::Severity::Info => notify_rust::Urgency::Low,
+        crate::config::Severity::Warning => notify_rust::Urgency::Normal,
+        crate::config::Severity::Critical => notify_rust::Urgency::Critical,
+    });
+
+    let res = tokio::task::spawn_blocking(move || {
+        desktop.show().map(|_| ()).map_err(|err| err.to_string())
+    })
+    .await
+    .unwrap_or_else(|err| Err(err.to_string()));
+    match res {
+        Ok(()) => {
+            if UNAVAILABLE.swap(false, Ordering::Relaxed) {
+                debug!("[{name}] Desktop notifications are available again.");
+            }
+            true
+        }
+        Err(err) => {
+            match UNAVAILABLE.swap(true, Ordering::Relaxed) {
+                false => {
+                    let err = Error::new(
+                        ErrorKind::Desktop,
+                        format!("Failed to show desktop notification: {err}"),
+                    );
+                    warn!("[{name}] {err}");
+                }
+                true => debug!("[{name}] Failed to show desktop notification: {err}"),
+            }
+            false
+        }
+    }
+}
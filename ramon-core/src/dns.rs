@@ -0,0 +1,93 @@
+//! Resolves captured IP addresses to hostnames for `reverse_dns`, and describes the machine ramon
+//! runs on for the `host`, `fqdn`, and `local_ip` variables.
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, UdpSocket},
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+use dns_lookup::AddrInfoHints;
+use log::debug;
+use tokio::{task, time::timeout};
+use toml::Value;
+
+/// How long a lookup may take before the address is treated as unresolved.
+const TIMEOUT: Duration = Duration::from_secs(2);
+/// How long lookups are cached, including failed ones.
+const CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+/// The `getaddrinfo` flag that requests the canonical name, which is 2 on every supported platform.
+const AI_CANONNAME: i32 = 2;
+
+/// When each address was looked up, and its hostname.
+type Cache = HashMap<IpAddr, (Instant, Option<String>)>;
+
+static CACHE: LazyLock<Mutex<Cache>> = LazyLock::new(Default::default);
+
+/// The variables `host`, `fqdn`, and `local_ip`, which are the same for every template.
+pub static HOST: LazyLock<HashMap<String, Value>> = LazyLock::new(|| {
+    let host = dns_lookup::get_hostname().unwrap_or_default();
+    let fqdn = fqdn(&host).unwrap_or_else(|| host.clone());
+    let mut variables = HashMap::from([
+        ("host".to_owned(), Value::String(host)),
+        ("fqdn".to_owned(), Value::String(fqdn)),
+    ]);
+    if let Some(local_ip) = local_ip() {
+        variables.insert("local_ip".into(), local_ip.to_string().into());
+    }
+    variables
+});
+
+/// Returns the hostname of `ip`, or `None` if it has no PTR record or the lookup timed out.
+pub async fn reverse(ip: IpAddr) -> Option<String> {
+    if let Some((time, hostname)) = CACHE.lock().unwrap().get(&ip) {
+        if time.elapsed() < CACHE_TTL {
+            return hostname.clone();
+        }
+    }
+
+    let lookup = task::spawn_blocking(move || dns_lookup::lookup_addr(&ip));
+    let hostname = match timeout(TIMEOUT, lookup).await {
+        Err(_) => {
+            debug!("Reverse lookup of {ip} timed out");
+            None
+        }
+        Ok(Err(err)) => {
+            debug!("Reverse lookup of {ip} panicked: {err}");
+            None
+        }
+        Ok(Ok(Err(err))) => {
+            debug!("Reverse lookup of {ip} failed: {err}");
+            None
+        }
+        // Without a PTR record, the address itself is returned.
+        Ok(Ok(Ok(hostname))) if hostname.parse::<IpAddr>().is_ok() => None,
+        Ok(Ok(Ok(hostname))) => Some(hostname),
+    };
+    CACHE
+        .lock()
+        .unwrap()
+        .insert(ip, (Instant::now(), hostname.clone()));
+    hostname
+}
+
+/// Returns the canonical name of `host`.
+fn fqdn(host: &str) -> Option<String> {
+    let hints = AddrInfoHints {
+        flags: AI_CANONNAME,
+        ..Default::default()
+    };
+    dns_lookup::getaddrinfo(Some(host), None, Some(hints))
+        .ok()?
+        .filter_map(Result::ok)
+        .find_map(|info| info.canonname)
+}
+
+/// Returns the address of the interface that would be used to reach the internet. Connecting a
+/// UDP socket only selects a route, so no packets are sent.
+fn local_ip() -> Option<IpAddr> {
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("192.0.2.1:9").ok()?;
+    Some(socket.local_addr().ok()?.ip())
+}
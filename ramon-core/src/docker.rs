@@ -0,0 +1,360 @@
+//! Starts, stops, and restarts containers, and runs commands in them, through the Docker Engine
+//! API, for the `docker` action.
+
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::{bail, Result};
+use tera::{Context, Tera};
+use toml::Value;
+
+use crate::template;
+
+/// How long an operation may take, including the command of `exec`.
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The containers that an action applies to.
+pub enum Target {
+    /// A container name or ID (template).
+    Container(String),
+    /// Every running container with this label (template), like `app=web`.
+    Label(String),
+}
+
+/// What an action does to each container.
+pub enum Operation {
+    /// Starts the container.
+    Start,
+    /// Stops the container.
+    Stop,
+    /// Restarts the container.
+    Restart,
+    /// A command run in the container, without a shell.
+    Exec(Vec<String>),
+}
+
+/// The `docker` action.
+pub struct DockerAction {
+    /// The containers to act on.
+    pub target: Target,
+    /// What to do to them.
+    pub operation: Operation,
+}
+
+/// The result of an action, set as local variables.
+#[derive(Default)]
+pub struct Outcome {
+    /// The containers the action was performed on.
+    pub containers: Vec<String>,
+    /// The output of `exec`, from every container.
+    pub output: String,
+    /// The exit code of `exec`, from the last container whose command failed.
+    pub exit_code: i64,
+    /// Why the action failed, if it did.
+    pub error: Option<String>,
+}
+
+impl Outcome {
+    /// Returns the local variables `docker_result`, `docker_containers`, `docker_output`, and
+    /// `docker_exit_code`.
+    pub fn variables(self) -> HashMap<String, Value> {
+        let result = self.error.unwrap_or("done".to_owned());
+        HashMap::from([
+            ("docker_result".into(), result.into()),
+            ("docker_containers".into(), self.containers.join(" ").into()),
+            ("docker_output".into(), self.output.into()),
+            ("docker_exit_code".into(), self.exit_code.into()),
+        ])
+    }
+}
+
+/// Registers the templates of the action under names starting with `prefix`.
+pub fn add_templates(templates: &mut Tera, prefix: &str, action: &DockerAction) -> Result<()> {
+    let target = match &action.target {
+        Target::Container(container) => container,
+        Target::Label(label) => label,
+    };
+    template::add(templates, &format!("{prefix}.target"), target)?;
+    if let Operation::Exec(args) = &action.operation {
+        for (i, arg) in args.iter().enumerate() {
+            template::add(templates, &format!("{prefix}.exec.{i}"), arg)?;
+        }
+    }
+    Ok(())
+}
+
+/// Describes the action for `ramon replay`.
+pub fn describe(
+    templates: &Tera,
+    prefix: &str,
+    action: &DockerAction,
+    context: &Context,
+) -> Result<String> {
+    let target = template::render(templates, &format!("{prefix}.target"), context)?;
+    let target = match action.target {
+        Target::Container(_) => target,
+        Target::Label(_) => format!("label {target}"),
+    };
+    Ok(match &action.operation {
+        Operation::Start => format!("docker: start {target}"),
+        Operation::Stop => format!("docker: stop {target}"),
+        Operation::Restart => format!("docker: restart {target}"),
+        Operation::Exec(_) => {
+            let args = render_args(templates, prefix, action, context)?;
+            format!("docker: exec {args:?} in {target}")
+        }
+    })
+}
+
+/// Renders the templates of the action and performs it. The outcome has an error if a request
+/// failed, no container has the label, or the command of `exec` failed.
+pub async fn perform(
+    templates: &Tera,
+    prefix: &str,
+    action: &DockerAction,
+    context: &Context,
+) -> Result<Outcome> {
+    let target = template::render(templates, &format!("{prefix}.target"), context)?;
+    let args = render_args(templates, prefix, action, context)?;
+    let mut outcome = Outcome::default();
+    let perform = api::perform(
+        &action.target,
+        target,
+        &action.operation,
+        &args,
+        &mut outcome,
+    );
+    let error = match tokio::time::timeout(TIMEOUT, perform).await {
+        Err(_) => Some(format!("Timed out after {TIMEOUT:?}.")),
+        Ok(Err(err)) => Some(format!("{err:#}")),
+        Ok(Ok(())) => None,
+    };
+    outcome.error = error;
+    Ok(outcome)
+}
+
+fn render_args(
+    templates: &Tera,
+    prefix: &str,
+    action: &DockerAction,
+    context: &Context,
+) -> Result<Vec<String>> {
+    let Operation::Exec(args) = &action.operation else {
+        return Ok(Vec::new());
+    };
+    (0..args.len())
+        .map(|i| template::render(templates, &format!("{prefix}.exec.{i}"), context))
+        .collect()
+}
+
+/// Parses the `docker` table, which has `container` or `label`, and either `operation` or `exec`.
+pub fn parse(value: Value) -> Result<DockerAction> {
+    let Value::Table(mut table) = value else {
+        bail!("Key `docker` must be a table.");
+    };
+    let target = match (table.remove("container"), table.remove("label")) {
+        (Some(Value::String(container)), None) => Target::Container(container),
+        (None, Some(Value::String(label))) => Target::Label(label),
+        (Some(_), Some(_)) => {
+            bail!("Keys `docker.container` and `docker.label` can't both be set.")
+        }
+        (None, None) => bail!("Key `docker.container` or `docker.label` must be set."),
+        _ => bail!("Keys `docker.container` and `docker.label` must be strings."),
+    };
+    let operation = match (table.remove("operation"), table.remove("exec")) {
+        (Some(Value::String(operation)), None) => match operation.as_str() {
+            "start" => Operation::Start,
+            "stop" => Operation::Stop,
+            "restart" => Operation::Restart,
+            _ => bail!("Key `docker.operation` must be `start`, `stop`, or `restart`."),
+        },
+        (Some(_), None) => bail!("Key `docker.operation` must be a string."),
+        (None, Some(Value::Array(args))) if !args.is_empty() => Operation::Exec(
+            args.into_iter()
+                .map(|arg| match arg {
+                    Value::String(arg) => Ok(arg),
+                    _ => bail!("Key `docker.exec` must be an array of strings."),
+                })
+                .collect::<Result<Vec<String>>>()?,
+        ),
+        (None, Some(_)) => bail!("Key `docker.exec` must be a non-empty array of strings."),
+        (Some(_), Some(_)) => bail!("Keys `docker.operation` and `docker.exec` can't both be set."),
+        (None, None) => bail!("Key `docker.operation` or `docker.exec` must be set."),
+    };
+    if let Some(unknown) = table.keys().next() {
+        bail!("Unknown key `docker.{unknown}`.");
+    }
+    Ok(DockerAction { target, operation })
+}
+
+#[cfg(unix)]
+mod api {
+    use std::env;
+
+    use anyhow::{anyhow, bail, Result};
+    use http_body_util::{BodyExt, Full};
+    use hyper::{body::Bytes, Method, Request, StatusCode};
+    use hyper_util::rt::TokioIo;
+    use serde_json::json;
+    use tokio::net::UnixStream;
+
+    use super::{Operation, Outcome, Target};
+
+    const DEFAULT_SOCKET: &str = "/var/run/docker.sock";
+
+    pub async fn perform(
+        target: &Target,
+        name: String,
+        operation: &Operation,
+        args: &[String],
+        outcome: &mut Outcome,
+    ) -> Result<()> {
+        outcome.containers = match target {
+            Target::Container(_) => vec![name],
+            Target::Label(_) => {
+                let containers = labeled(&name).await?;
+                if containers.is_empty() {
+                    bail!("No running container has the label {name:?}.");
+                }
+                containers
+            }
+        };
+
+        for container in outcome.containers.clone() {
+            let path = format!("/containers/{}", encode(&container));
+            let (method, path, body) = match operation {
+                Operation::Start => (Method::POST, format!("{path}/start"), None),
+                Operation::Stop => (Method::POST, format!("{path}/stop"), None),
+                Operation::Restart => (Method::POST, format!("{path}/restart"), None),
+                Operation::Exec(_) => {
+                    let (output, exit_code) = exec(&path, args).await?;
+                    outcome.output += &output;
+                    if exit_code != 0 {
+                        outcome.exit_code = exit_code;
+                    }
+                    continue;
+                }
+            };
+            let (status, body) = request(method, &path, body).await?;
+            // 304 means the container was already started or stopped.
+            if !status.is_success() && status != StatusCode::NOT_MODIFIED {
+                bail!("Failed to act on {container}: {}", message(status, &body));
+            }
+        }
+        if outcome.exit_code != 0 {
+            bail!("The command exited with {}.", outcome.exit_code);
+        }
+        Ok(())
+    }
+
+    /// Returns the IDs of the running containers with the label.
+    async fn labeled(label: &str) -> Result<Vec<String>> {
+        let filters = json!({ "label": [label] }).to_string();
+        let path = format!("/containers/json?filters={}", encode(&filters));
+        let (status, body) = request(Method::GET, &path, None).await?;
+        if !status.is_success() {
+            bail!("Failed to list containers: {}", message(status, &body));
+        }
+        let containers: Vec<serde_json::Value> = serde_json::from_slice(&body)?;
+        Ok(containers
+            .iter()
+            .filter_map(|container| Some(container["Id"].as_str()?.to_owned()))
+            .collect())
+    }
+
+    /// Runs a command in a container, returning its output and exit code.
+    async fn exec(container_path: &str, args: &[String]) -> Result<(String, i64)> {
+        let body = json!({ "Cmd": args, "AttachStdout": true, "AttachStderr": true, "Tty": true });
+        let (status, body) =
+            request(Method::POST, &format!("{container_path}/exec"), Some(body)).await?;
+        if !status.is_success() {
+            bail!("Failed to create exec: {}", message(status, &body));
+        }
+        let created: serde_json::Value = serde_json::from_slice(&body)?;
+        let id = created["Id"]
+            .as_str()
+            .ok_or(anyhow!("Docker didn't return the ID of the exec."))?;
+
+        // With a TTY, the output isn't multiplexed, and it's sent until the command exits.
+        let start = json!({ "Detach": false, "Tty": true });
+        let (status, output) =
+            request(Method::POST, &format!("/exec/{id}/start"), Some(start)).await?;
+        if !status.is_success() {
+            bail!("Failed to start exec: {}", message(status, &output));
+        }
+        let (status, body) = request(Method::GET, &format!("/exec/{id}/json"), None).await?;
+        if !status.is_success() {
+            bail!("Failed to inspect exec: {}", message(status, &body));
+        }
+        let inspected: serde_json::Value = serde_json::from_slice(&body)?;
+        let exit_code = inspected["ExitCode"].as_i64().unwrap_or(-1);
+        Ok((String::from_utf8_lossy(&output).into_owned(), exit_code))
+    }
+
+    /// Sends a request to the Docker socket, which is `$DOCKER_HOST` if it's a `unix://` URL.
+    async fn request(
+        method: Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<(StatusCode, Bytes)> {
+        let socket = env::var("DOCKER_HOST")
+            .ok()
+            .and_then(|host| Some(host.strip_prefix("unix://")?.to_owned()))
+            .unwrap_or(DEFAULT_SOCKET.to_owned());
+        let stream = UnixStream::connect(&socket)
+            .await
+            .map_err(|err| anyhow!("Failed to connect to {socket}: {err}"))?;
+        let (mut sender, connection) =
+            hyper::client::conn::http1::handshake(TokioIo::new(stream)).await?;
+        tokio::spawn(connection);
+
+        let body = body.map(|body| body.to_string()).unwrap_or_default();
+        let request = Request::builder()
+            .method(method)
+            .uri(path)
+            .header("Host", "docker")
+            .header("Content-Type", "application/json")
+            .body(Full::new(Bytes::from(body)))?;
+        let response = sender.send_request(request).await?;
+        let status = response.status();
+        let body = response.into_body().collect().await?.to_bytes();
+        Ok((status, body))
+    }
+
+    /// Returns the error message of a response, or its status.
+    fn message(status: StatusCode, body: &[u8]) -> String {
+        serde_json::from_slice::<serde_json::Value>(body)
+            .ok()
+            .and_then(|body| Some(body["message"].as_str()?.to_owned()))
+            .unwrap_or(status.to_string())
+    }
+
+    /// Percent-encodes everything except unreserved characters.
+    fn encode(component: &str) -> String {
+        component
+            .bytes()
+            .map(|byte| match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                    (byte as char).to_string()
+                }
+                _ => format!("%{byte:02X}"),
+            })
+            .collect()
+    }
+}
+
+#[cfg(not(unix))]
+mod api {
+    use anyhow::{bail, Result};
+
+    use super::{Operation, Outcome, Target};
+
+    pub async fn perform(
+        _target: &Target,
+        _name: String,
+        _operation: &Operation,
+        _args: &[String],
+        _outcome: &mut Outcome,
+    ) -> Result<()> {
+        bail!("The `docker` action is only supported on Unix.")
+    }
+}
@@ -0,0 +1,145 @@
+//! Sends notifications as emails over SMTP, with a plaintext body and an HTML body.
+
+use anyhow::{anyhow, Result};
+use lettre::{
+    message::{header::ContentType, MultiPart},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use log::error;
+use tera::Context;
+
+use crate::{
+    config::{Notification, SmtpConfig, SmtpTls},
+    error::{Error, ErrorKind},
+    highlight::{self, Highlight},
+    notifier::{Delivery, Notifier},
+    source::BoxFuture,
+    template::{self, EMAIL_TEMPLATE},
+};
+
+impl Notifier for SmtpConfig {
+    fn channel(&self) -> &'static str {
+        "email"
+    }
+
+    fn send<'a>(
+        &'a self,
+        notification: &'a Notification,
+        delivery: &'a Delivery,
+    ) -> BoxFuture<'a, Result<()>> {
+        let plain = highlight::append(
+            &notification.body,
+            notification.highlight.as_ref(),
+            Highlight::plain,
+        );
+        Box::pin(send(
+            &notification.title,
+            plain,
+            std::slice::from_ref(notification),
+            self,
+            delivery.name,
+        ))
+    }
+
+    /// The HTML body is rendered from each aggregated notification.
+    fn send_digest<'a>(
+        &'a self,
+        digest: &'a Notification,
+        queue: &'a [Notification],
+        delivery: &'a Delivery,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(send(
+            &digest.title,
+            digest.body.clone(),
+            queue,
+            self,
+            delivery.name,
+        ))
+    }
+}
+
+/// Sends an email with a plaintext body and an HTML body rendered from `notifications`.
+async fn send(
+    subject: &str,
+    plain: String,
+    notifications: &[Notification],
+    smtp: &SmtpConfig,
+    name: &str,
+) -> Result<()> {
+    let recipients = smtp.to.resolve(name).await.map_err(|err| {
+        Error::new(
+            ErrorKind::Recipients,
+            format!("Failed to resolve recipients: {err}"),
+        )
+    })?;
+    let mut builder = Message::builder().from(smtp.from.clone());
+    for to in recipients {
+        builder = builder.to(to);
+    }
+    for cc in &smtp.cc {
+        builder = builder.cc(cc.clone());
+    }
+    for bcc in &smtp.bcc {
+        builder = builder.bcc(bcc.clone());
+    }
+    if let Some(reply_to) = &smtp.reply_to {
+        builder = builder.reply_to(reply_to.clone());
+    }
+    let builder = builder.subject(subject);
+    let email = match render_html(subject, notifications, smtp) {
+        Ok(html) => builder.multipart(MultiPart::alternative_plain_html(plain, html)),
+        Err(err) => {
+            error!("[{name}] {err}");
+            builder.header(ContentType::TEXT_PLAIN).body(plain)
+        }
+    }
+    .map_err(|err| anyhow!("Failed to build email: {err}"))?;
+    let host = smtp
+        .login
+        .as_ref()
+        .map_or("localhost", |login| login.host.as_str());
+    let mut builder = match smtp.tls {
+        SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+        SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+            .map_err(|err| anyhow!("Failed to parse {host:?}: {err}"))?,
+        SmtpTls::Implicit => AsyncSmtpTransport::<Tokio1Executor>::relay(host)
+            .map_err(|err| anyhow!("Failed to parse {host:?}: {err}"))?,
+    };
+    if let Some(login) = &smtp.login {
+        builder = builder.credentials(Credentials::new(
+            login.username.clone(),
+            login.password.clone(),
+        ));
+    }
+    if let Some(port) = smtp.port {
+        builder = builder.port(port);
+    }
+    let mailer = builder.build();
+    mailer.send(email).await.map_err(|err| {
+        let kind = match smtp.login {
+            None => ErrorKind::EmailLocalhost,
+            Some(_) => ErrorKind::Email,
+        };
+        Error::new(kind, format!("Failed to send email: {err}"))
+    })?;
+
+    Ok(())
+}
+
+/// Renders the HTML body of an email. Each notification has the keys of the webhook payload, as
+/// well as `time`, the formatted timestamp, and `highlight_html`, the rendered highlight, if any.
+fn render_html(title: &str, notifications: &[Notification], smtp: &SmtpConfig) -> Result<String> {
+    let notifications = notifications
+        .iter()
+        .map(|notification| {
+            let mut value = template::notification_value(notification)?;
+            value["highlight_html"] = notification.highlight.as_ref().map(Highlight::html).into();
+            Ok(value)
+        })
+        .collect::<Result<Vec<serde_json::Value>, serde_json::Error>>()?;
+    let mut context = Context::new();
+    context.insert("title", title);
+    context.insert("notifications", &notifications);
+    template::render(&smtp.html, EMAIL_TEMPLATE, &context)
+}
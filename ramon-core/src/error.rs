@@ -0,0 +1,229 @@
+//! Errors that operators may need to act on, each with a stable code and a hint.
+
+use std::{fmt, io};
+
+/// An operator-facing error with a stable code and, where possible, a hint on how to fix it.
+#[derive(Debug)]
+pub struct Error {
+    /// What went wrong, which determines the code and hint.
+    pub kind: ErrorKind,
+    message: String,
+}
+
+/// A kind of error, each of which has a stable code listed in the README.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorKind {
+    /// E0001: The config file could not be opened.
+    ConfigOpen,
+    /// E0002: Permission denied while opening the config.
+    ConfigPermission,
+    /// E0003: The config file has a syntax error.
+    ConfigSyntax,
+    /// E0004: The config file is invalid.
+    ConfigInvalid,
+    /// E0005: Privileges could not be dropped.
+    DropPrivileges,
+    /// E0030: A log file could not be opened.
+    LogOpen,
+    /// E0031: A log file does not exist.
+    LogNotFound,
+    /// E0032: Permission denied while opening a log file.
+    LogPermission,
+    /// E0033: A log file could not be watched.
+    LogWatch,
+    /// E0040: journalctl could not be spawned.
+    JournalSpawn,
+    /// E0041: The syslog listener could not bind its address.
+    SyslogBind,
+    /// E0042: A `source` command could not be spawned.
+    SourceSpawn,
+    /// E0050: An `exec` command could not be spawned.
+    ExecSpawn,
+    /// E0060: The cache in /var/cache/ramon is not writable.
+    CacheWrite,
+    /// E0061: A state file was written by a newer ramon.
+    StateVersion,
+    /// E0062: The disk holding the cache is nearly full.
+    DiskPressure,
+    /// E0070: A template could not be parsed or rendered.
+    Template,
+    /// E0080: An email could not be sent.
+    Email,
+    /// E0081: An email could not be sent to localhost.
+    EmailLocalhost,
+    /// E0082: A webhook could not be posted.
+    Webhook,
+    /// E0083: Notification recipients could not be resolved.
+    Recipients,
+    /// E0084: A notification could not be written to `file`.
+    NotifyFile,
+    /// E0085: A notification `exec` command failed.
+    NotifyExec,
+    /// E0086: A desktop notification could not be shown.
+    Desktop,
+    /// E0087: A Matrix message could not be sent.
+    Matrix,
+    /// E0088: A Teams message could not be posted.
+    Teams,
+    /// E0089: A Gotify or Pushover message could not be sent.
+    Push,
+    /// E0090: A text message could not be sent with Twilio.
+    Sms,
+    /// E0091: An MQTT broker could not be reached.
+    Mqtt,
+    /// E0092: The ingest server could not bind its address.
+    IngestBind,
+    /// E0093: Alerts could not be posted to Alertmanager.
+    Alertmanager,
+    /// E0094: The control socket is unavailable.
+    ControlSocket,
+    /// E0095: The health server could not bind its address.
+    HealthBind,
+    /// E0096: The audit log could not be opened.
+    AuditOpen,
+    /// E0097: The event database could not be opened.
+    EventsOpen,
+    /// E0098: The API server could not bind its address.
+    ApiBind,
+    /// E0099: Another instance is listening on the control socket.
+    AlreadyRunning,
+}
+
+impl Error {
+    /// Creates an error of `kind`, which is displayed with its code and hint.
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /// Returns the message without the code or hint.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl ErrorKind {
+    /// Returns the stable code of the error (e.g. `E0001`).
+    pub fn code(self) -> &'static str {
+        match self {
+            ErrorKind::ConfigOpen => "E0001",
+            ErrorKind::ConfigPermission => "E0002",
+            ErrorKind::ConfigSyntax => "E0003",
+            ErrorKind::ConfigInvalid => "E0004",
+            ErrorKind::DropPrivileges => "E0005",
+            ErrorKind::LogOpen => "E0030",
+            ErrorKind::LogNotFound => "E0031",
+            ErrorKind::LogPermission => "E0032",
+            ErrorKind::LogWatch => "E0033",
+            ErrorKind::JournalSpawn => "E0040",
+            ErrorKind::SyslogBind => "E0041",
+            ErrorKind::SourceSpawn => "E0042",
+            ErrorKind::ExecSpawn => "E0050",
+            ErrorKind::CacheWrite => "E0060",
+            ErrorKind::StateVersion => "E0061",
+            ErrorKind::DiskPressure => "E0062",
+            ErrorKind::Template => "E0070",
+            ErrorKind::Email => "E0080",
+            ErrorKind::EmailLocalhost => "E0081",
+            ErrorKind::Webhook => "E0082",
+            ErrorKind::Recipients => "E0083",
+            ErrorKind::NotifyFile => "E0084",
+            ErrorKind::NotifyExec => "E0085",
+            ErrorKind::Desktop => "E0086",
+            ErrorKind::Matrix => "E0087",
+            ErrorKind::Teams => "E0088",
+            ErrorKind::Push => "E0089",
+            ErrorKind::Sms => "E0090",
+            ErrorKind::Mqtt => "E0091",
+            ErrorKind::IngestBind => "E0092",
+            ErrorKind::Alertmanager => "E0093",
+            ErrorKind::ControlSocket => "E0094",
+            ErrorKind::HealthBind => "E0095",
+            ErrorKind::AuditOpen => "E0096",
+            ErrorKind::EventsOpen => "E0097",
+            ErrorKind::ApiBind => "E0098",
+            ErrorKind::AlreadyRunning => "E0099",
+        }
+    }
+
+    /// Returns how the operator might fix the error, if there's a likely fix.
+    pub fn hint(self) -> Option<&'static str> {
+        match self {
+            ErrorKind::ConfigOpen => Some("run `ramon init` to create the config"),
+            ErrorKind::ConfigPermission => Some("run ramon as root"),
+            ErrorKind::ConfigSyntax => None,
+            ErrorKind::ConfigInvalid => {
+                Some("refer to https://github.com/reujab/ramon#specification-wip")
+            }
+            ErrorKind::DropPrivileges => Some("run ramon as root to switch to another user"),
+            ErrorKind::LogOpen => None,
+            ErrorKind::LogNotFound => Some("check the `log` path for typos"),
+            ErrorKind::LogPermission => Some("run ramon as root or as a user in group adm"),
+            ErrorKind::LogWatch => {
+                Some("raise fs.inotify.max_user_watches or fs.inotify.max_user_instances")
+            }
+            ErrorKind::JournalSpawn => Some("ensure journalctl is installed and in PATH"),
+            ErrorKind::SyslogBind => Some("use a free port; ports below 1024 require root"),
+            ErrorKind::SourceSpawn => {
+                Some("ensure the `shell` of the monitor exists and is executable")
+            }
+            ErrorKind::ExecSpawn => Some("ensure the command exists and is executable"),
+            ErrorKind::CacheWrite => match cfg!(windows) {
+                true => Some(r"ensure %ProgramData%\ramon\cache is writable by ramon"),
+                false => Some("ensure /var/cache/ramon is writable by ramon"),
+            },
+            ErrorKind::StateVersion => {
+                Some("upgrade ramon, or delete the file to discard its state")
+            }
+            ErrorKind::DiskPressure => Some("free up space on the filesystem holding the cache"),
+            ErrorKind::Template => None,
+            ErrorKind::Email => None,
+            ErrorKind::EmailLocalhost => Some("consider setting smtp_host, username, and password"),
+            ErrorKind::Webhook => None,
+            ErrorKind::Recipients => Some("set `fallback` to a static recipient"),
+            ErrorKind::NotifyFile => Some("ensure the directory exists and is writable by ramon"),
+            ErrorKind::NotifyExec => Some("ensure the command exists and exits with status 0"),
+            ErrorKind::Desktop => Some("run ramon as a user with a desktop session"),
+            ErrorKind::Matrix => Some("ensure the access token's user has joined the room"),
+            ErrorKind::Teams => Some("ensure the incoming webhook still exists"),
+            ErrorKind::Push => Some("check the server URL, user key, and application token"),
+            ErrorKind::Sms => Some("check the account SID, auth token, and phone numbers"),
+            ErrorKind::Mqtt => {
+                Some("check that the broker is reachable and the credentials are correct")
+            }
+            ErrorKind::IngestBind => Some("use a free port; ports below 1024 require root"),
+            ErrorKind::Alertmanager => {
+                Some("ensure the URL points to Alertmanager, not Prometheus")
+            }
+            ErrorKind::ControlSocket => Some("ensure ramon is running, and run this as its user"),
+            ErrorKind::HealthBind => Some("use a free port; ports below 1024 require root"),
+            ErrorKind::AuditOpen => Some("ensure the directory exists and is writable by ramon"),
+            ErrorKind::EventsOpen => Some("ensure the directory exists and is writable by ramon"),
+            ErrorKind::ApiBind => Some("use a free port; ports below 1024 require root"),
+            ErrorKind::AlreadyRunning => Some("stop it first, since both would use the same cache"),
+        }
+    }
+
+    /// Classifies an I/O error that occurred while opening a log file.
+    pub fn log_open(err: &io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::NotFound => ErrorKind::LogNotFound,
+            io::ErrorKind::PermissionDenied => ErrorKind::LogPermission,
+            _ => ErrorKind::LogOpen,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind.code(), self.message)?;
+        if let Some(hint) = self.kind.hint() {
+            write!(f, " (hint: {hint})")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,212 @@
+//! An SQLite database of matches and notification deliveries, which `ramon events` queries, for a
+//! history of what happened without a separate logging stack. The database is only built with the
+//! `sqlite` feature.
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::Value;
+
+/// The `events` database.
+pub struct EventsConfig {
+    /// The path of the SQLite database.
+    pub path: PathBuf,
+    /// Events older than this are deleted.
+    pub max_age: Option<Duration>,
+}
+
+/// A recorded event.
+#[derive(Serialize)]
+pub struct Event {
+    /// When it was recorded, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The monitor it belongs to.
+    pub monitor: String,
+    /// Its name, such as `match` or `delivery`.
+    pub event: String,
+    /// What happened, such as the variables of a match.
+    pub details: Value,
+}
+
+#[cfg(feature = "sqlite")]
+mod db {
+    use std::{path::Path, sync::OnceLock, thread, time::Duration};
+
+    use anyhow::Result;
+    use log::{debug, warn};
+    use rusqlite::{params, Connection, OpenFlags};
+    use serde_json::Value;
+    use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+    use super::{Event, EventsConfig};
+    use crate::{
+        error::{Error, ErrorKind},
+        state, status,
+    };
+
+    /// How often events older than `max_age` are deleted, in seconds.
+    const PRUNE_INTERVAL: u64 = 60 * 60;
+
+    /// An event to insert: its timestamp, monitor, name, and details.
+    type Row = (u64, String, String, String);
+
+    /// Sends events to the thread that inserts them, so that monitors don't wait on SQLite.
+    static DB: OnceLock<UnboundedSender<Row>> = OnceLock::new();
+
+    pub fn init(config: &EventsConfig) -> Result<()> {
+        let path = &config.path;
+        let db = Connection::open(path)
+            .and_then(|db| {
+                db.execute_batch(
+                    "CREATE TABLE IF NOT EXISTS events (
+                        timestamp INTEGER NOT NULL,
+                        monitor TEXT NOT NULL,
+                        event TEXT NOT NULL,
+                        details TEXT NOT NULL
+                    );
+                    CREATE INDEX IF NOT EXISTS events_monitor ON events (monitor, timestamp);",
+                )?;
+                Ok(db)
+            })
+            .map_err(|err| {
+                Error::new(
+                    ErrorKind::EventsOpen,
+                    format!("Failed to open event database {path:?}: {err}"),
+                )
+            })?;
+        let (row_tx, row_rx) = unbounded_channel();
+        if DB.set(row_tx).is_ok() {
+            let max_age = config.max_age;
+            thread::Builder::new()
+                .name("events".into())
+                .spawn(move || insert_rows(db, row_rx, max_age))?;
+        }
+        Ok(())
+    }
+
+    /// Inserts each event in the order they were recorded, deleting those older than `max_age`
+    /// when opened and then at most once per `PRUNE_INTERVAL`.
+    fn insert_rows(db: Connection, mut row_rx: UnboundedReceiver<Row>, max_age: Option<Duration>) {
+        let mut pruned = None;
+        loop {
+            if let Some(max_age) = max_age {
+                let now = status::now();
+                if pruned.is_none_or(|pruned| now >= pruned + PRUNE_INTERVAL) {
+                    pruned = Some(now);
+                    let res = db.execute(
+                        "DELETE FROM events WHERE timestamp < ?1",
+                        params![now.saturating_sub(max_age.as_secs()) as i64],
+                    );
+                    if let Err(err) = res {
+                        warn!("Failed to delete old events: {err}");
+                    }
+                }
+            }
+            let Some((timestamp, monitor, event, details)) = row_rx.blocking_recv() else {
+                return;
+            };
+            let res = db.execute(
+                "INSERT INTO events (timestamp, monitor, event, details) VALUES (?1, ?2, ?3, ?4)",
+                params![timestamp as i64, monitor, event, details],
+            );
+            if let Err(err) = res {
+                warn!("Failed to write to the event database: {err}");
+            }
+        }
+    }
+
+    pub fn enabled() -> bool {
+        DB.get().is_some()
+    }
+
+    pub fn record(event: &str, monitor: &str, details: &Value) {
+        let Some(db) = DB.get() else {
+            return;
+        };
+        if state::under_disk_pressure() {
+            debug!("Skipping `{event}` event of `{monitor}`.");
+            return;
+        }
+        let _ = db.send((
+            status::now(),
+            monitor.to_owned(),
+            event.to_owned(),
+            details.to_string(),
+        ));
+    }
+
+    pub fn query(path: &Path, monitor: Option<&str>, since: u64) -> Result<Vec<Event>> {
+        let db =
+            Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(|err| {
+                Error::new(
+                    ErrorKind::EventsOpen,
+                    format!("Failed to open event database {path:?}: {err}"),
+                )
+            })?;
+        let mut statement = db.prepare(
+            "SELECT timestamp, monitor, event, details FROM events
+            WHERE timestamp >= ?1 AND (?2 IS NULL OR monitor = ?2)
+            ORDER BY timestamp, rowid",
+        )?;
+        let rows = statement.query_map(params![since as i64, monitor], |row| {
+            Ok(Event {
+                timestamp: row.get::<_, i64>(0)? as u64,
+                monitor: row.get(1)?,
+                event: row.get(2)?,
+                details: serde_json::from_str(&row.get::<_, String>(3)?).unwrap_or_default(),
+            })
+        })?;
+        Ok(rows.collect::<Result<Vec<Event>, _>>()?)
+    }
+}
+
+/// Opens the database, creating it if it doesn't exist.
+#[cfg(feature = "sqlite")]
+pub fn init(config: &EventsConfig) -> Result<()> {
+    db::init(config)
+}
+
+/// Fails, since the database requires the `sqlite` feature.
+#[cfg(not(feature = "sqlite"))]
+pub fn init(_config: &EventsConfig) -> Result<()> {
+    anyhow::bail!("events: ramon was built without the `sqlite` feature")
+}
+
+/// Returns whether there's a database, so that events are only built if they're recorded.
+#[cfg(feature = "sqlite")]
+pub fn enabled() -> bool {
+    db::enabled()
+}
+
+/// Returns false, since the database requires the `sqlite` feature.
+#[cfg(not(feature = "sqlite"))]
+pub fn enabled() -> bool {
+    false
+}
+
+/// Records an event of `monitor`, such as `match` or `delivery`.
+#[cfg(feature = "sqlite")]
+pub fn record(event: &str, monitor: &str, details: &Value) {
+    db::record(event, monitor, details);
+}
+
+/// Does nothing, since the database requires the `sqlite` feature.
+#[cfg(not(feature = "sqlite"))]
+pub fn record(_event: &str, _monitor: &str, _details: &Value) {}
+
+/// Returns the events in the database at `path`, oldest first, of `monitor` (or every monitor)
+/// recorded at or after `since`, in seconds since the Unix epoch.
+#[cfg(feature = "sqlite")]
+pub fn query(path: &Path, monitor: Option<&str>, since: u64) -> Result<Vec<Event>> {
+    db::query(path, monitor, since)
+}
+
+/// Fails, since the database requires the `sqlite` feature.
+#[cfg(not(feature = "sqlite"))]
+pub fn query(_path: &Path, _monitor: Option<&str>, _since: u64) -> Result<Vec<Event>> {
+    anyhow::bail!("ramon was built without the `sqlite` feature")
+}
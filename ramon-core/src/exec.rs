@@ -0,0 +1,90 @@
+//! Runs a command for each notification.
+
+use std::{process::Stdio, time::Duration};
+
+use anyhow::Result;
+use tokio::{io::AsyncWriteExt, process::Command, time::timeout};
+
+use crate::{
+    config::{Exec, ExecConfig, Notification},
+    error::{Error, ErrorKind},
+    highlight::{self, Highlight},
+    notifier::{Delivery, Notifier},
+    source::BoxFuture,
+};
+
+/// Notification commands that run for longer than this are killed.
+const EXEC_TIMEOUT: Duration = Duration::from_secs(60);
+
+impl Notifier for ExecConfig {
+    fn channel(&self) -> &'static str {
+        "exec"
+    }
+
+    fn send<'a>(
+        &'a self,
+        notification: &'a Notification,
+        _delivery: &'a Delivery,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(run(notification, self))
+    }
+
+    /// The command runs once per aggregated notification.
+    fn splits_digests(&self) -> bool {
+        true
+    }
+}
+
+/// Runs a command with the notification in environment variables and as JSON on stdin.
+async fn run(notification: &Notification, exec: &ExecConfig) -> Result<()> {
+    let mut command = match &exec.exec {
+        Exec::Shell(script) => exec.shell.command(script),
+        Exec::Spawn(args) => {
+            let mut command = Command::new(&args[0]);
+            command.args(&args[1..]);
+            command
+        }
+    };
+    let body = highlight::append(
+        &notification.body,
+        notification.highlight.as_ref(),
+        Highlight::plain,
+    );
+    command
+        .env("RAMON_TYPE", &notification.r#type)
+        .env("RAMON_MONITOR", &notification.monitor)
+        .env("RAMON_SEVERITY", notification.severity.as_str())
+        .env("RAMON_TITLE", &notification.title)
+        .env("RAMON_BODY", body)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let payload = serde_json::to_vec(notification)?;
+    let res = timeout(EXEC_TIMEOUT, async {
+        let mut child = command.spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // The command may exit without reading its input.
+            let _ = stdin.write_all(&payload).await;
+        }
+        child.wait_with_output().await
+    })
+    .await;
+    let message = match res {
+        Err(_) => format!("Timed out after {EXEC_TIMEOUT:?}"),
+        Ok(Err(err)) => format!("Failed to spawn: {err}"),
+        Ok(Ok(output)) if output.status.success() => return Ok(()),
+        Ok(Ok(output)) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            match stderr.trim_end() {
+                "" => format!("Exited with {}", output.status),
+                stderr => format!("Exited with {}: {stderr}", output.status),
+            }
+        }
+    };
+    Err(Error::new(
+        ErrorKind::NotifyExec,
+        format!("Notification exec: {message}"),
+    )
+    .into())
+}
@@ -0,0 +1,134 @@
+//! Limits on how many commands run at once, for `max_concurrent_execs` and `max_processes`.
+
+use std::{
+    future::Future,
+    str::FromStr,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use anyhow::{bail, Error, Result};
+use tokio::{
+    process::Command,
+    sync::{OwnedSemaphorePermit, Semaphore},
+};
+
+/// Limits the commands run by all monitors together. Unset means unlimited.
+static PROCESSES: OnceLock<Arc<Semaphore>> = OnceLock::new();
+
+/// Sets how many commands may run at once across all monitors.
+pub fn init(max_processes: usize) {
+    let _ = PROCESSES.set(Arc::new(Semaphore::new(max_processes)));
+}
+
+/// Waits until fewer than `max_processes` commands are running. The returned permit must be held
+/// while the command runs.
+pub async fn process_permit() -> Option<OwnedSemaphorePermit> {
+    let processes = PROCESSES.get()?.clone();
+    processes.acquire_owned().await.ok()
+}
+
+/// What to do with a command when the monitor already runs `max_concurrent_execs` of them.
+#[derive(Clone, Copy, Default)]
+pub enum Overflow {
+    /// Run it once another one exits.
+    #[default]
+    Queue,
+    /// Don't run it.
+    Drop,
+    /// Run only the latest of the commands that arrive while the limit is reached.
+    Coalesce,
+}
+
+impl FromStr for Overflow {
+    type Err = Error;
+
+    fn from_str(overflow: &str) -> Result<Self> {
+        Ok(match overflow {
+            "queue" => Self::Queue,
+            "drop" => Self::Drop,
+            "coalesce" => Self::Coalesce,
+            _ => bail!("must be `queue`, `drop`, or `coalesce`"),
+        })
+    }
+}
+
+/// What happened to a submitted command.
+pub enum Submitted {
+    /// The command runs now, or once a slot is free.
+    Run,
+    /// The command replaced one that was waiting, or was dropped.
+    Skipped,
+}
+
+/// Limits how many runs of a monitor's `exec` may run at once.
+pub struct ExecLimit {
+    slots: Arc<Semaphore>,
+    overflow: Overflow,
+    /// The command that runs next, when coalescing.
+    pending: Arc<Mutex<Option<Command>>>,
+}
+
+impl ExecLimit {
+    /// Allows `max_concurrent` runs at once, handling the others by `overflow`.
+    pub fn new(max_concurrent: usize, overflow: Overflow) -> Self {
+        Self {
+            slots: Arc::new(Semaphore::new(max_concurrent)),
+            overflow,
+            pending: Arc::default(),
+        }
+    }
+
+    /// Passes the command to `run` once a slot of the monitor and a process are free, according to
+    /// the overflow policy. Both are released when the future returned by `run` completes.
+    pub fn submit<F, Fut>(&self, command: Command, run: F) -> Submitted
+    where
+        F: FnOnce(Command) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        if let Ok(slot) = self.slots.clone().try_acquire_owned() {
+            tokio::spawn(run_in_slot(slot, command, run));
+            return Submitted::Run;
+        }
+        match self.overflow {
+            Overflow::Queue => {
+                let slots = self.slots.clone();
+                tokio::spawn(async move {
+                    let Ok(slot) = slots.acquire_owned().await else {
+                        return;
+                    };
+                    run_in_slot(slot, command, run).await;
+                });
+                Submitted::Run
+            }
+            Overflow::Drop => Submitted::Skipped,
+            Overflow::Coalesce => {
+                if self.pending.lock().unwrap().replace(command).is_some() {
+                    // The task that's already waiting runs the new command instead.
+                    return Submitted::Skipped;
+                }
+                let slots = self.slots.clone();
+                let pending = self.pending.clone();
+                tokio::spawn(async move {
+                    let Ok(slot) = slots.acquire_owned().await else {
+                        return;
+                    };
+                    let Some(command) = pending.lock().unwrap().take() else {
+                        return;
+                    };
+                    run_in_slot(slot, command, run).await;
+                });
+                Submitted::Run
+            }
+        }
+    }
+}
+
+async fn run_in_slot<F, Fut>(slot: OwnedSemaphorePermit, command: Command, run: F)
+where
+    F: FnOnce(Command) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let process = process_permit().await;
+    run(command).await;
+    drop((slot, process));
+}
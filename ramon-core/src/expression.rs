@@ -0,0 +1,574 @@
+//! The expressions of `if`, `threshold`, and `composite`.
+
+use std::{cmp::Ordering, collections::HashMap, fmt, str::FromStr};
+
+use anyhow::{anyhow, bail, Error, Result};
+use toml::Value;
+
+/// A parsed expression such as `latency_ms > 500 && var.env == "prod"`.
+///
+/// Identifiers refer to local variables (e.g. named capture groups), and identifiers prefixed
+/// with `var.` refer to global variables. Strings that look like numbers are compared
+/// numerically. Numbers may have a duration unit (`ms`, `s`, `m`, `h`, or `d`), in which case
+/// they are converted to seconds. `+` adds numbers and concatenates anything else.
+pub struct Expression {
+    source: String,
+    root: Node,
+}
+
+enum Node {
+    Literal(Literal),
+    Local(String),
+    Global(String),
+    Not(Box<Node>),
+    Binary(Box<Node>, Operator, Box<Node>),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Operator {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+/// The value of an expression.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Literal {
+    /// A number, which integers are converted to.
+    Number(f64),
+    /// A string.
+    String(String),
+    /// A boolean.
+    Boolean(bool),
+}
+
+#[derive(Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    String(String),
+    Identifier(String),
+    Operator(Operator),
+    Not,
+    OpenParen,
+    CloseParen,
+}
+
+impl Expression {
+    /// Evaluates the expression and returns whether the result is truthy.
+    pub fn is_true(
+        &self,
+        local: &HashMap<String, Value>,
+        global: &HashMap<String, Value>,
+    ) -> Result<bool> {
+        Ok(self.evaluate(local, global)?.is_truthy())
+    }
+
+    /// Evaluates the expression, with local variables by name and global ones through `var`.
+    pub fn evaluate(
+        &self,
+        local: &HashMap<String, Value>,
+        global: &HashMap<String, Value>,
+    ) -> Result<Literal> {
+        self.root
+            .evaluate(local, global)
+            .map_err(|err| anyhow!("Failed to evaluate {:?}: {err}", self.source))
+    }
+}
+
+impl FromStr for Expression {
+    type Err = Error;
+
+    fn from_str(source: &str) -> Result<Self> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, index: 0 };
+        let root = parser.parse_or()?;
+        if let Some(token) = parser.peek() {
+            bail!("Unexpected {token}");
+        }
+        Ok(Self {
+            source: source.to_owned(),
+            root,
+        })
+    }
+}
+
+impl Node {
+    fn evaluate(
+        &self,
+        local: &HashMap<String, Value>,
+        global: &HashMap<String, Value>,
+    ) -> Result<Literal> {
+        match self {
+            Node::Literal(literal) => Ok(literal.clone()),
+            Node::Local(name) => match lookup(local, name) {
+                None => bail!("Variable `{name}` is not set."),
+                Some(value) => Ok(Literal::from(value)),
+            },
+            Node::Global(name) => match lookup(global, name) {
+                None => bail!("Variable `var.{name}` is not set."),
+                Some(value) => Ok(Literal::from(value)),
+            },
+            Node::Not(node) => Ok(Literal::Boolean(!node.evaluate(local, global)?.is_truthy())),
+            Node::Binary(lhs, Operator::And, rhs) => Ok(Literal::Boolean(
+                lhs.evaluate(local, global)?.is_truthy()
+                    && rhs.evaluate(local, global)?.is_truthy(),
+            )),
+            Node::Binary(lhs, Operator::Or, rhs) => Ok(Literal::Boolean(
+                lhs.evaluate(local, global)?.is_truthy()
+                    || rhs.evaluate(local, global)?.is_truthy(),
+            )),
+            Node::Binary(lhs, operator, rhs) => {
+                let lhs = lhs.evaluate(local, global)?;
+                let rhs = rhs.evaluate(local, global)?;
+                if let Operator::Add = operator {
+                    return Ok(match (lhs.as_number(), rhs.as_number()) {
+                        (Some(lhs), Some(rhs)) => Literal::Number(lhs + rhs),
+                        _ => Literal::String(format!("{lhs}{rhs}")),
+                    });
+                }
+                if let Operator::Sub | Operator::Mul | Operator::Div = operator {
+                    let (Some(lhs), Some(rhs)) = (lhs.as_number(), rhs.as_number()) else {
+                        bail!("Cannot do arithmetic on {lhs} and {rhs}.");
+                    };
+                    return Ok(Literal::Number(match operator {
+                        Operator::Sub => lhs - rhs,
+                        Operator::Mul => lhs * rhs,
+                        _ if rhs == 0.0 => bail!("Cannot divide {lhs} by zero."),
+                        _ => lhs / rhs,
+                    }));
+                }
+                let result = match operator {
+                    Operator::Eq => lhs.loose_eq(&rhs),
+                    Operator::Ne => !lhs.loose_eq(&rhs),
+                    _ => {
+                        let ordering = match (lhs.as_number(), rhs.as_number()) {
+                            (Some(lhs), Some(rhs)) => lhs.partial_cmp(&rhs),
+                            _ => None,
+                        };
+                        let Some(ordering) = ordering else {
+                            bail!("Cannot compare {lhs} and {rhs} as numbers.");
+                        };
+                        match operator {
+                            Operator::Lt => ordering == Ordering::Less,
+                            Operator::Le => ordering != Ordering::Greater,
+                            Operator::Gt => ordering == Ordering::Greater,
+                            Operator::Ge => ordering != Ordering::Less,
+                            _ => unreachable!(),
+                        }
+                    }
+                };
+                Ok(Literal::Boolean(result))
+            }
+        }
+    }
+}
+
+/// Looks up a variable, or a field of a table if the name has dots (e.g. `disk.firing`).
+fn lookup<'a>(variables: &'a HashMap<String, Value>, name: &str) -> Option<&'a Value> {
+    if let Some(value) = variables.get(name) {
+        return Some(value);
+    }
+    let mut path = name.split('.');
+    let mut value = variables.get(path.next()?)?;
+    for key in path {
+        value = value.as_table()?.get(key)?;
+    }
+    Some(value)
+}
+
+impl Literal {
+    /// Returns the value as a number, parsing strings.
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Literal::Number(number) => Some(*number),
+            Literal::String(string) => string.trim().parse().ok(),
+            Literal::Boolean(_) => None,
+        }
+    }
+
+    /// Returns whether the value is true, a non-zero number, or a non-empty string.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Literal::Number(number) => *number != 0.0,
+            Literal::String(string) => !string.is_empty(),
+            Literal::Boolean(boolean) => *boolean,
+        }
+    }
+
+    fn loose_eq(&self, other: &Literal) -> bool {
+        match (self.as_number(), other.as_number()) {
+            (Some(lhs), Some(rhs)) => lhs == rhs,
+            _ => self.to_string() == other.to_string(),
+        }
+    }
+}
+
+impl From<&Value> for Literal {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Integer(integer) => Literal::Number(*integer as f64),
+            Value::Float(float) => Literal::Number(*float),
+            Value::Boolean(boolean) => Literal::Boolean(*boolean),
+            Value::String(string) => Literal::String(string.clone()),
+            value => Literal::String(value.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Literal::Number(number) => write!(f, "{number}"),
+            Literal::String(string) => write!(f, "{string}"),
+            Literal::Boolean(boolean) => write!(f, "{boolean}"),
+        }
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(number) => write!(f, "number `{number}`"),
+            Token::String(string) => write!(f, "string {string:?}"),
+            Token::Identifier(identifier) => write!(f, "identifier `{identifier}`"),
+            Token::Operator(operator) => write!(
+                f,
+                "operator `{}`",
+                match operator {
+                    Operator::Or => "||",
+                    Operator::And => "&&",
+                    Operator::Eq => "==",
+                    Operator::Ne => "!=",
+                    Operator::Lt => "<",
+                    Operator::Le => "<=",
+                    Operator::Gt => ">",
+                    Operator::Ge => ">=",
+                    Operator::Add => "+",
+                    Operator::Sub => "-",
+                    Operator::Mul => "*",
+                    Operator::Div => "/",
+                }
+            ),
+            Token::Not => write!(f, "`!`"),
+            Token::OpenParen => write!(f, "`(`"),
+            Token::CloseParen => write!(f, "`)`"),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        if c.is_ascii_digit() {
+            let mut number = String::new();
+            while let Some(&c) = chars.peek() {
+                if !c.is_ascii_digit() && c != '.' {
+                    break;
+                }
+                number.push(c);
+                chars.next();
+            }
+            let mut unit = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_alphabetic()) {
+                unit.push(c);
+            }
+            let number = number
+                .parse::<f64>()
+                .map_err(|err| anyhow!("Invalid number `{number}`: {err}"))?;
+            // Durations are converted to seconds.
+            let seconds = match unit.as_str() {
+                "" | "s" => 1.0,
+                "ms" => 0.001,
+                "m" => 60.0,
+                "h" => 60.0 * 60.0,
+                "d" => 24.0 * 60.0 * 60.0,
+                _ => bail!("Invalid unit `{unit}` in `{number}{unit}`"),
+            };
+            tokens.push(Token::Number(number * seconds));
+            continue;
+        }
+
+        if c.is_alphabetic() || c == '_' {
+            let mut identifier = String::new();
+            while let Some(&c) = chars.peek() {
+                if !c.is_alphanumeric() && c != '_' && c != '.' {
+                    break;
+                }
+                identifier.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Identifier(identifier));
+            continue;
+        }
+
+        chars.next();
+        let token = match c {
+            '"' | '\'' => {
+                let mut string = String::new();
+                loop {
+                    match chars.next() {
+                        None => bail!("Unterminated string"),
+                        Some('\\') => match chars.next() {
+                            None => bail!("Unterminated string"),
+                            Some(c) => string.push(c),
+                        },
+                        Some(quote) if quote == c => break,
+                        Some(c) => string.push(c),
+                    }
+                }
+                Token::String(string)
+            }
+            '(' => Token::OpenParen,
+            ')' => Token::CloseParen,
+            '!' if chars.next_if_eq(&'=').is_some() => Token::Operator(Operator::Ne),
+            '!' => Token::Not,
+            '=' if chars.next_if_eq(&'=').is_some() => Token::Operator(Operator::Eq),
+            '<' if chars.next_if_eq(&'=').is_some() => Token::Operator(Operator::Le),
+            '<' => Token::Operator(Operator::Lt),
+            '>' if chars.next_if_eq(&'=').is_some() => Token::Operator(Operator::Ge),
+            '>' => Token::Operator(Operator::Gt),
+            '&' if chars.next_if_eq(&'&').is_some() => Token::Operator(Operator::And),
+            '|' if chars.next_if_eq(&'|').is_some() => Token::Operator(Operator::Or),
+            '+' => Token::Operator(Operator::Add),
+            '-' => Token::Operator(Operator::Sub),
+            '*' => Token::Operator(Operator::Mul),
+            '/' => Token::Operator(Operator::Div),
+            c => bail!("Unexpected character `{c}`"),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.index).cloned();
+        self.index += 1;
+        token
+    }
+
+    fn next_if_operator(&mut self, operators: &[Operator]) -> Option<Operator> {
+        match self.peek() {
+            Some(Token::Operator(operator)) if operators.contains(operator) => {
+                let operator = *operator;
+                self.index += 1;
+                Some(operator)
+            }
+            _ => None,
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Node> {
+        let mut node = self.parse_and()?;
+        while let Some(operator) = self.next_if_operator(&[Operator::Or]) {
+            node = Node::Binary(Box::new(node), operator, Box::new(self.parse_and()?));
+        }
+        Ok(node)
+    }
+
+    fn parse_and(&mut self) -> Result<Node> {
+        let mut node = self.parse_comparison()?;
+        while let Some(operator) = self.next_if_operator(&[Operator::And]) {
+            node = Node::Binary(Box::new(node), operator, Box::new(self.parse_comparison()?));
+        }
+        Ok(node)
+    }
+
+    fn parse_comparison(&mut self) -> Result<Node> {
+        let node = self.parse_sum()?;
+        let comparisons = [
+            Operator::Eq,
+            Operator::Ne,
+            Operator::Lt,
+            Operator::Le,
+            Operator::Gt,
+            Operator::Ge,
+        ];
+        match self.next_if_operator(&comparisons) {
+            None => Ok(node),
+            Some(operator) => Ok(Node::Binary(
+                Box::new(node),
+                operator,
+                Box::new(self.parse_sum()?),
+            )),
+        }
+    }
+
+    fn parse_sum(&mut self) -> Result<Node> {
+        let mut node = self.parse_product()?;
+        while let Some(operator) = self.next_if_operator(&[Operator::Add, Operator::Sub]) {
+            node = Node::Binary(Box::new(node), operator, Box::new(self.parse_product()?));
+        }
+        Ok(node)
+    }
+
+    fn parse_product(&mut self) -> Result<Node> {
+        let mut node = self.parse_unary()?;
+        while let Some(operator) = self.next_if_operator(&[Operator::Mul, Operator::Div]) {
+            node = Node::Binary(Box::new(node), operator, Box::new(self.parse_unary()?));
+        }
+        Ok(node)
+    }
+
+    fn parse_unary(&mut self) -> Result<Node> {
+        match self.next() {
+            None => bail!("Unexpected end of expression"),
+            Some(Token::Not) => Ok(Node::Not(Box::new(self.parse_unary()?))),
+            Some(Token::Operator(Operator::Sub)) => Ok(Node::Binary(
+                Box::new(Node::Literal(Literal::Number(0.0))),
+                Operator::Sub,
+                Box::new(self.parse_unary()?),
+            )),
+            Some(Token::Number(number)) => Ok(Node::Literal(Literal::Number(number))),
+            Some(Token::String(string)) => Ok(Node::Literal(Literal::String(string))),
+            Some(Token::Identifier(identifier)) => Ok(match identifier.as_str() {
+                "true" => Node::Literal(Literal::Boolean(true)),
+                "false" => Node::Literal(Literal::Boolean(false)),
+                _ => match identifier.strip_prefix("var.") {
+                    Some(name) => Node::Global(name.to_owned()),
+                    None => Node::Local(identifier),
+                },
+            }),
+            Some(Token::OpenParen) => {
+                let node = self.parse_or()?;
+                match self.next() {
+                    Some(Token::CloseParen) => Ok(node),
+                    Some(token) => bail!("Expected `)` but found {token}"),
+                    None => bail!("Expected `)`"),
+                }
+            }
+            Some(token) => bail!("Unexpected {token}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(
+        source: &str,
+        local: &[(&str, Value)],
+        global: &[(&str, Value)],
+    ) -> Result<Literal> {
+        let variables = |variables: &[(&str, Value)]| {
+            variables
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect::<HashMap<String, Value>>()
+        };
+        source
+            .parse::<Expression>()?
+            .evaluate(&variables(local), &variables(global))
+    }
+
+    fn is_true(source: &str) -> bool {
+        evaluate(source, &[], &[]).unwrap().is_truthy()
+    }
+
+    #[test]
+    fn precedence() {
+        // `&&` binds tighter than `||`, and `!` tighter than both.
+        assert!(is_true("true || false && false"));
+        assert!(is_true("false && true || true"));
+        assert!(!is_true("(true || false) && false"));
+        assert!(is_true("!false && true"));
+        assert!(is_true("!true || true"));
+        assert!(!is_true("!(true || true)"));
+        assert!(is_true("1 + 2 * 3 == 7 && 10 - 4 / 2 == 8"));
+    }
+
+    #[test]
+    fn global_variables() {
+        let local = [("env", Value::String("dev".into()))];
+        let disk = Value::Table(toml::toml! { firing = true });
+        let global = [("env", Value::String("prod".into())), ("disk", disk)];
+        let value = evaluate("var.env == \"prod\" && env == \"dev\"", &local, &global).unwrap();
+        assert_eq!(value, Literal::Boolean(true));
+        let value = evaluate("var.disk.firing", &[], &global).unwrap();
+        assert_eq!(value, Literal::Boolean(true));
+    }
+
+    #[test]
+    fn comparison() {
+        // Strings that look like numbers are compared as numbers.
+        assert!(is_true("\"10\" > \"9\""));
+        assert!(is_true("\"1.0\" == 1"));
+        assert!(is_true("\" 5 \" == 5"));
+        let local = [("status", Value::String("503".into()))];
+        let value = evaluate("status >= 500", &local, &[]).unwrap();
+        assert_eq!(value, Literal::Boolean(true));
+        // Anything else is compared as strings.
+        assert!(is_true("\"abc\" == 'abc'"));
+        assert!(!is_true("\"abc\" == 1"));
+        assert!(is_true("\"a\" + 1 == \"a1\""));
+        let err = evaluate("\"abc\" < 1", &[], &[]).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Cannot compare abc and 1 as numbers."));
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(evaluate("500ms", &[], &[]).unwrap(), Literal::Number(0.5));
+        assert_eq!(evaluate("2m", &[], &[]).unwrap(), Literal::Number(120.0));
+        assert!(is_true("90s == 1.5m"));
+        assert!(is_true("1d == 24h"));
+        let err = "5x".parse::<Expression>().err().unwrap();
+        assert_eq!(err.to_string(), "Invalid unit `x` in `5x`");
+    }
+
+    #[test]
+    fn parse_errors() {
+        for (source, expected) in [
+            ("1 +", "Unexpected end of expression"),
+            ("(1", "Expected `)`"),
+            ("(1 2", "Expected `)` but found number `2`"),
+            ("1 2", "Unexpected number `2`"),
+            ("\"abc", "Unterminated string"),
+            ("a = b", "Unexpected character `=`"),
+            ("&& a", "Unexpected operator `&&`"),
+        ] {
+            let err = source.parse::<Expression>().err().unwrap();
+            assert_eq!(err.to_string(), expected, "{source}");
+        }
+    }
+
+    #[test]
+    fn unknown_variables() {
+        let err = evaluate("missing > 1", &[], &[]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Failed to evaluate \"missing > 1\": Variable `missing` is not set."
+        );
+        let local = [("env", Value::String("prod".into()))];
+        let err = evaluate("var.env == \"prod\"", &local, &[]).unwrap_err();
+        assert!(err.to_string().ends_with("Variable `var.env` is not set."));
+        // The right side of `&&` isn't evaluated if the left side is false.
+        assert!(!is_true("false && missing"));
+    }
+}
@@ -0,0 +1,70 @@
+//! Appends notifications to a file, as JSON lines or rendered from a template.
+
+use anyhow::Result;
+use tera::Context;
+use tokio::{fs::OpenOptions, io::AsyncWriteExt};
+
+use crate::{
+    config::{FileConfig, Notification},
+    error::{Error, ErrorKind},
+    notifier::{Delivery, Notifier},
+    source::BoxFuture,
+    template::{self, FILE_TEMPLATE},
+};
+
+impl Notifier for FileConfig {
+    fn channel(&self) -> &'static str {
+        "file"
+    }
+
+    fn send<'a>(
+        &'a self,
+        notification: &'a Notification,
+        _delivery: &'a Delivery,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(append(std::slice::from_ref(notification), self))
+    }
+
+    /// Each aggregated notification is appended on its own line.
+    fn send_digest<'a>(
+        &'a self,
+        _digest: &'a Notification,
+        queue: &'a [Notification],
+        _delivery: &'a Delivery,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(append(queue, self))
+    }
+}
+
+/// Appends one line per notification to the file.
+async fn append(notifications: &[Notification], file: &FileConfig) -> Result<()> {
+    let mut lines = String::new();
+    for notification in notifications {
+        let line = match &file.template {
+            None => serde_json::to_string(notification)?,
+            Some(template) => {
+                let context = Context::from_value(template::notification_value(notification)?)?;
+                template::render(template, FILE_TEMPLATE, &context)?
+            }
+        };
+        lines += &line;
+        lines += "\n";
+    }
+    let res = async {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&file.path)
+            .await?
+            .write_all(lines.as_bytes())
+            .await
+    }
+    .await;
+    res.map_err(|err| {
+        Error::new(
+            ErrorKind::NotifyFile,
+            format!("Failed to write to {:?}: {err}", file.path),
+        )
+        .into()
+    })
+}
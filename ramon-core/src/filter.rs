@@ -0,0 +1,114 @@
+//! Allowlists and blocklists of the values of local variables, for `filter`.
+
+use std::{collections::HashMap, net::IpAddr};
+
+use anyhow::{anyhow, bail, Result};
+use ipnet::IpNet;
+use regex::Regex;
+use toml::Value;
+
+use crate::config::value_to_string;
+
+enum Pattern {
+    Exact(String),
+    /// Matches the IP addresses within a network. A single address is a network of one.
+    Network(IpNet),
+    Regex(Regex),
+}
+
+impl Pattern {
+    fn matches(&self, value: &str) -> bool {
+        match self {
+            Pattern::Exact(exact) => value == exact,
+            Pattern::Network(network) => value
+                .parse::<IpAddr>()
+                .is_ok_and(|ip| network.contains(&ip)),
+            Pattern::Regex(regex) => regex.is_match(value),
+        }
+    }
+}
+
+/// A condition of `filter` on the value of a variable.
+pub struct Filter {
+    /// The name of the variable.
+    pub variable: String,
+    /// If set, the value must match one of these patterns (`in` and `match`).
+    allow: Vec<Pattern>,
+    /// The value must match none of these patterns (`not_in` and `not_match`).
+    deny: Vec<Pattern>,
+}
+
+impl Filter {
+    /// Returns whether the value of the variable passes the filter. A variable that isn't set only
+    /// passes blocklists.
+    pub fn passes(&self, variables: &HashMap<String, Value>) -> bool {
+        let Some(value) = variables.get(&self.variable) else {
+            return self.allow.is_empty();
+        };
+        let value = value_to_string(value.clone());
+        (self.allow.is_empty() || self.allow.iter().any(|pattern| pattern.matches(&value)))
+            && !self.deny.iter().any(|pattern| pattern.matches(&value))
+    }
+}
+
+/// Parses the `filter` table of a monitor, whose keys are local variables and whose values are
+/// tables with the keys `in`, `not_in`, `match`, and `not_match`.
+pub fn parse(value: Value) -> Result<Vec<Filter>> {
+    let Value::Table(table) = value else {
+        bail!("Key `filter` must be a table.");
+    };
+    let mut filters = Vec::with_capacity(table.len());
+    for (variable, value) in table {
+        let Value::Table(mut table) = value else {
+            bail!("Key `filter.{variable}` must be a table.");
+        };
+        let mut allow = Vec::new();
+        let mut deny = Vec::new();
+        for (key, regex) in [
+            ("in", false),
+            ("not_in", false),
+            ("match", true),
+            ("not_match", true),
+        ] {
+            let Some(value) = table.remove(key) else {
+                continue;
+            };
+            let values = match value {
+                Value::String(_) => vec![value],
+                Value::Array(values) => values,
+                _ => {
+                    bail!("Key `filter.{variable}.{key}` must be a string or an array of strings.")
+                }
+            };
+            for value in values {
+                let Value::String(value) = value else {
+                    bail!("Key `filter.{variable}.{key}` must be a string or an array of strings.");
+                };
+                let pattern = if regex {
+                    Pattern::Regex(Regex::new(&value).map_err(|err| {
+                        anyhow!("Failed to parse `filter.{variable}.{key}`: {err}")
+                    })?)
+                } else if let Ok(network) = value.parse() {
+                    Pattern::Network(network)
+                } else if let Ok(ip) = value.parse::<IpAddr>() {
+                    Pattern::Network(ip.into())
+                } else {
+                    Pattern::Exact(value)
+                };
+                match key.starts_with("not_") {
+                    false => allow.push(pattern),
+                    true => deny.push(pattern),
+                }
+            }
+        }
+        if let Some(unknown) = table.keys().next() {
+            bail!("Unknown key `filter.{variable}.{unknown}`.");
+        }
+        filters.push(Filter {
+            variable,
+            allow,
+            deny,
+        });
+    }
+    Ok(filters)
+}
@@ -0,0 +1,175 @@
+//! An HTTP server that answers `GET /healthz` with whether every monitor is running and attached
+//! to its sources, and every aggregator is alive, for container health checks.
+
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        LazyLock, Mutex,
+    },
+};
+
+use anyhow::Result;
+use http_body_util::Full;
+use hyper::{
+    body::{Bytes, Incoming},
+    header::CONTENT_TYPE,
+    server::conn::http1,
+    service::service_fn,
+    Method, Request, Response, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use log::{debug, info};
+use serde::Serialize;
+use tokio::net::TcpListener;
+
+use crate::error::{Error, ErrorKind};
+
+static HEALTH: LazyLock<Mutex<Health>> = LazyLock::new(Default::default);
+/// Whether every monitor has started.
+static READY: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Default, Serialize)]
+struct Health {
+    healthy: bool,
+    monitors: BTreeMap<String, MonitorHealth>,
+    notify: BTreeMap<String, NotifyHealth>,
+}
+
+#[derive(Clone, Default, Serialize)]
+struct MonitorHealth {
+    healthy: bool,
+    running: bool,
+    /// Whether each source that runs in the background (e.g. `log`) is still attached.
+    sources: BTreeMap<&'static str, bool>,
+}
+
+#[derive(Clone, Default, Serialize)]
+struct NotifyHealth {
+    healthy: bool,
+}
+
+/// Marks a monitor as running, a source as attached, or an aggregator as alive, until it's
+/// dropped, which happens even if the task holding it panics.
+pub struct Guard(Box<dyn Fn(&mut Health) + Send>);
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        (self.0)(&mut HEALTH.lock().unwrap());
+    }
+}
+
+/// Marks the monitor `name` as running.
+pub fn run(name: &str) -> Guard {
+    let name = name.to_owned();
+    let mut health = HEALTH.lock().unwrap();
+    health.monitors.entry(name.clone()).or_default().running = true;
+    Guard(Box::new(move |health| {
+        health.monitors.entry(name.clone()).or_default().running = false;
+    }))
+}
+
+/// Marks the source `source` of the monitor `name` as attached.
+pub fn attach(name: &str, source: &'static str) -> Guard {
+    let name = name.to_owned();
+    let mut health = HEALTH.lock().unwrap();
+    let monitor = health.monitors.entry(name.clone()).or_default();
+    monitor.sources.insert(source, true);
+    Guard(Box::new(move |health| {
+        let monitor = health.monitors.entry(name.clone()).or_default();
+        monitor.sources.insert(source, false);
+    }))
+}
+
+/// Marks the aggregator of the notification config `name` as alive.
+pub fn aggregate(name: &str) -> Guard {
+    let name = name.to_owned();
+    HEALTH
+        .lock()
+        .unwrap()
+        .notify
+        .insert(name.clone(), NotifyHealth { healthy: true });
+    Guard(Box::new(move |health| {
+        health
+            .notify
+            .insert(name.clone(), NotifyHealth { healthy: false });
+    }))
+}
+
+/// Marks every monitor as started. Until then, ramon isn't healthy.
+pub fn ready() {
+    READY.store(true, Ordering::Relaxed);
+}
+
+fn snapshot() -> Health {
+    let mut health = HEALTH.lock().unwrap().clone();
+    for monitor in health.monitors.values_mut() {
+        monitor.healthy = monitor.running && monitor.sources.values().all(|attached| *attached);
+    }
+    health.healthy = READY.load(Ordering::Relaxed)
+        && health.monitors.values().all(|monitor| monitor.healthy)
+        && health.notify.values().all(|notify| notify.healthy);
+    health
+}
+
+/// The server that answers `GET /healthz`.
+pub struct HealthServer {
+    listener: TcpListener,
+}
+
+impl HealthServer {
+    /// Binds `bind`.
+    pub async fn new(bind: SocketAddr) -> Result<Self> {
+        let listener = TcpListener::bind(bind).await.map_err(|err| {
+            Error::new(
+                ErrorKind::HealthBind,
+                format!("Failed to bind health server to {bind}: {err}"),
+            )
+        })?;
+        info!("Listening for health checks on {bind}");
+        Ok(Self { listener })
+    }
+
+    /// Serves requests until the listener fails.
+    pub async fn start(self) -> Result<()> {
+        loop {
+            let (stream, peer) = self.listener.accept().await?;
+            tokio::spawn(async move {
+                if let Err(err) = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service_fn(handle))
+                    .await
+                {
+                    debug!("Health connection from {peer}: {err}");
+                }
+            });
+        }
+    }
+}
+
+async fn handle(req: Request<Incoming>) -> Result<Response<Full<Bytes>>> {
+    if req.uri().path() != "/healthz" {
+        return Ok(respond(StatusCode::NOT_FOUND, "Not found\n".into()));
+    }
+    if req.method() != Method::GET {
+        return Ok(respond(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "Method not allowed\n".into(),
+        ));
+    }
+    let health = snapshot();
+    let status = match health.healthy {
+        true => StatusCode::OK,
+        false => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    let mut res = respond(status, format!("{}\n", serde_json::to_string(&health)?));
+    res.headers_mut()
+        .insert(CONTENT_TYPE, "application/json".parse()?);
+    Ok(res)
+}
+
+fn respond(status: StatusCode, body: String) -> Response<Full<Bytes>> {
+    let mut res = Response::new(Full::new(Bytes::from(body)));
+    *res.status_mut() = status;
+    res
+}
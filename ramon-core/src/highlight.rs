@@ -0,0 +1,213 @@
+//! The lines surrounding a match, and rendering them for each notification channel.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use toml::Value;
+
+use crate::config::value_to_string;
+
+/// The lines surrounding a match, with the matched portion marked, so that recipients can see why
+/// a rule fired. Each channel renders it in its own format.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Highlight {
+    /// The lines before the match, oldest first.
+    pub before: Vec<String>,
+    /// The line that matched.
+    pub line: String,
+    /// The lines after the match.
+    pub after: Vec<String>,
+    /// The byte range of the match within `line`.
+    pub span: Option<(usize, usize)>,
+    /// The name and value of each named capture group that matched.
+    pub captures: Vec<(String, String)>,
+}
+
+impl Highlight {
+    /// Builds a highlight from the variables set by the monitor: the matched `line`, the lines of
+    /// `context_before` and `context_after`, and the bytes of the match within the line,
+    /// `match_span`. The variables should not be redacted yet; `hide` replaces the values that may
+    /// not be revealed, so that the highlight reveals nothing more than the notification.
+    pub fn from_variables<'a>(
+        variables: &HashMap<String, Value>,
+        capture_names: impl Iterator<Item = &'a str>,
+        hide: impl Fn(&str) -> String,
+    ) -> Option<Self> {
+        let lines = |name: &str| match variables.get(name) {
+            Some(Value::Array(lines)) => Some(
+                lines
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(&hide)
+                    .collect::<Vec<String>>(),
+            ),
+            _ => None,
+        };
+        let before = lines("context_before")?;
+        let after = lines("context_after")?;
+        let Some(Value::String(raw)) = variables.get("line") else {
+            return None;
+        };
+        let line = hide(raw);
+
+        let offsets = match variables.get("match_span") {
+            Some(Value::Array(offsets)) => offsets
+                .iter()
+                .map(|offset| usize::try_from(offset.as_integer()?).ok())
+                .collect::<Option<Vec<usize>>>(),
+            _ => None,
+        };
+        // The parts are hidden separately so that the span still covers the match. If a hidden
+        // value crosses the edge of the match, the parts don't add up and the span is left out.
+        let span = match offsets.as_deref() {
+            Some(&[start, end]) if start < end => {
+                match (raw.get(..start), raw.get(start..end), raw.get(end..)) {
+                    (Some(prefix), Some(matched), Some(suffix)) => {
+                        let (prefix, matched, suffix) = (hide(prefix), hide(matched), hide(suffix));
+                        (format!("{prefix}{matched}{suffix}") == line)
+                            .then(|| (prefix.len(), prefix.len() + matched.len()))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        let captures = capture_names
+            .filter_map(|name| {
+                let value = variables.get(name)?.clone();
+                Some((name.to_owned(), hide(&value_to_string(value))))
+            })
+            .collect();
+
+        Some(Self {
+            before,
+            line,
+            after,
+            span,
+            captures,
+        })
+    }
+
+    /// Marks the line with `>` and the match with carets, like a compiler error.
+    pub fn plain(&self) -> String {
+        let mut text = self.plain_lines().join("\n");
+        text += &self.capture_table(|name, value| format!("{name}: {value}"));
+        text
+    }
+
+    /// Marks the match in bold red for terminals.
+    pub fn ansi(&self) -> String {
+        let mut lines = self.context_lines();
+        if let Some((start, end)) = self.span {
+            lines[self.before.len()] = format!(
+                "> {}\x1b[1;31m{}\x1b[0m{}",
+                &self.line[..start],
+                &self.line[start..end],
+                &self.line[end..]
+            );
+        }
+        let mut text = lines.join("\n");
+        text += &self.capture_table(|name, value| format!("\x1b[1m{name}\x1b[0m: {value}"));
+        text
+    }
+
+    /// Renders the lines in a code block for chat services that support Markdown.
+    pub fn markdown(&self) -> String {
+        let code = self.plain_lines().join("\n");
+        let fence = if code.contains("```") { "````" } else { "```" };
+        let mut text = format!("{fence}\n{code}\n{fence}");
+        text += &self
+            .capture_table(|name, value| format!("- **{name}**: `{}`", value.replace('`', "'")));
+        text
+    }
+
+    /// Marks the match in bold for HTML email.
+    pub fn html(&self) -> String {
+        let mut html = String::from("<pre>");
+        for line in &self.before {
+            html += &format!("  {}\n", escape_html(line));
+        }
+        match self.span {
+            None => html += &format!("<b>&gt; {}</b>\n", escape_html(&self.line)),
+            Some((start, end)) => {
+                html += &format!(
+                    "&gt; {}<b><mark>{}</mark></b>{}\n",
+                    escape_html(&self.line[..start]),
+                    escape_html(&self.line[start..end]),
+                    escape_html(&self.line[end..])
+                )
+            }
+        }
+        for line in &self.after {
+            html += &format!("  {}\n", escape_html(line));
+        }
+        html += "</pre>";
+
+        if !self.captures.is_empty() {
+            html += "<table>";
+            for (name, value) in &self.captures {
+                html += &format!(
+                    "<tr><th align=\"left\">{}</th><td><code>{}</code></td></tr>",
+                    escape_html(name),
+                    escape_html(value)
+                );
+            }
+            html += "</table>";
+        }
+        html
+    }
+
+    fn context_lines(&self) -> Vec<String> {
+        let mut lines = Vec::with_capacity(self.before.len() + 1 + self.after.len());
+        lines.extend(self.before.iter().map(|line| format!("  {line}")));
+        lines.push(format!("> {}", self.line));
+        lines.extend(self.after.iter().map(|line| format!("  {line}")));
+        lines
+    }
+
+    fn plain_lines(&self) -> Vec<String> {
+        let mut lines = self.context_lines();
+        if let Some((start, end)) = self.span {
+            let offset = self.line[..start].chars().count();
+            let width = self.line[start..end].chars().count();
+            lines.insert(
+                self.before.len() + 1,
+                format!("  {}{}", " ".repeat(offset), "^".repeat(width)),
+            );
+        }
+        lines
+    }
+
+    fn capture_table(&self, format_row: impl Fn(&str, &str) -> String) -> String {
+        if self.captures.is_empty() {
+            return String::new();
+        }
+        let rows = self
+            .captures
+            .iter()
+            .map(|(name, value)| format_row(name, value))
+            .collect::<Vec<String>>();
+        format!("\n\n{}", rows.join("\n"))
+    }
+}
+
+/// Appends the highlight, rendered by `format`, to a notification body.
+pub fn append(
+    body: &str,
+    highlight: Option<&Highlight>,
+    format: fn(&Highlight) -> String,
+) -> String {
+    match highlight {
+        None => body.to_owned(),
+        Some(highlight) if body.is_empty() => format(highlight),
+        Some(highlight) => format!("{body}\n\n{}", format(highlight)),
+    }
+}
+
+/// Escapes `text` for HTML bodies and attributes.
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,172 @@
+//! An HTTP server that lets other systems push lines into monitors with `POST /ingest/<monitor>`.
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{LazyLock, Mutex},
+};
+
+use anyhow::Result;
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::{
+    body::{Bytes, Incoming},
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    server::conn::http1,
+    service::service_fn,
+    HeaderMap, Method, Request, Response, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use log::{debug, info, warn};
+use sha2::{Digest, Sha256};
+use tokio::{net::TcpListener, sync::mpsc::Sender};
+use toml::Value;
+
+use crate::{
+    error::{Error, ErrorKind},
+    monitor::{json_variables, Event},
+};
+
+/// Larger request bodies are rejected.
+const MAX_BODY_LEN: usize = 1024 * 1024;
+
+/// The monitors whose source is `"ingest"`, by name.
+static ROUTES: LazyLock<Mutex<HashMap<String, Sender<Event>>>> = LazyLock::new(Default::default);
+
+/// `ingest`, the server that receives lines over HTTP.
+pub struct IngestConfig {
+    /// The address the server listens on.
+    pub bind: SocketAddr,
+    /// The bearer token that every request must have.
+    pub token: String,
+}
+
+/// Lets the server send lines to a monitor.
+pub fn register(name: String, event_tx: Sender<Event>) {
+    ROUTES.lock().unwrap().insert(name, event_tx);
+}
+
+/// The server that routes posted lines to the monitors whose source is `"ingest"`.
+pub struct IngestServer {
+    listener: TcpListener,
+    token: String,
+}
+
+impl IngestServer {
+    /// Binds the address of `config`.
+    pub async fn new(config: IngestConfig) -> Result<Self> {
+        let listener = TcpListener::bind(config.bind).await.map_err(|err| {
+            Error::new(
+                ErrorKind::IngestBind,
+                format!("Failed to bind ingest server to {}: {err}", config.bind),
+            )
+        })?;
+        info!("Listening for ingested lines on {}", config.bind);
+        Ok(Self {
+            listener,
+            token: config.token,
+        })
+    }
+
+    /// Serves requests until the listener fails.
+    pub async fn start(self) -> Result<()> {
+        let token_hash = Sha256::digest(&self.token);
+        loop {
+            let (stream, peer) = self.listener.accept().await?;
+            let service = service_fn(move |req| handle(req, peer, token_hash));
+            tokio::spawn(async move {
+                if let Err(err) = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    debug!("Ingest connection from {peer}: {err}");
+                }
+            });
+        }
+    }
+}
+
+async fn handle(
+    req: Request<Incoming>,
+    peer: SocketAddr,
+    token_hash: impl AsRef<[u8]>,
+) -> Result<Response<Full<Bytes>>> {
+    let Some(name) = req.uri().path().strip_prefix("/ingest/") else {
+        return Ok(respond(StatusCode::NOT_FOUND, "Not found"));
+    };
+    let name = name.to_owned();
+    if req.method() != Method::POST {
+        return Ok(respond(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "Method not allowed",
+        ));
+    }
+    if !authorized(req.headers(), token_hash.as_ref()) {
+        warn!("Rejected ingest request from {peer} with a missing or invalid token");
+        return Ok(respond(StatusCode::UNAUTHORIZED, "Invalid token"));
+    }
+    let Some(event_tx) = ROUTES.lock().unwrap().get(&name).cloned() else {
+        return Ok(respond(StatusCode::NOT_FOUND, "Unknown monitor"));
+    };
+
+    let is_json = req
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    let body = match Limited::new(req.into_body(), MAX_BODY_LEN).collect().await {
+        Ok(body) => body.to_bytes(),
+        Err(_) => return Ok(respond(StatusCode::PAYLOAD_TOO_LARGE, "Body is too large")),
+    };
+    let body = String::from_utf8_lossy(&body);
+
+    let mut lines = Vec::new();
+    if is_json {
+        // An array is split into one line per element.
+        match serde_json::from_str(&body) {
+            Ok(serde_json::Value::Array(values)) => {
+                lines.extend(values.iter().map(serde_json::Value::to_string))
+            }
+            Ok(value) => lines.push(value.to_string()),
+            Err(err) => return Ok(respond(StatusCode::BAD_REQUEST, &format!("{err}"))),
+        }
+    } else {
+        lines.extend(
+            body.lines()
+                .filter(|line| !line.is_empty())
+                .map(str::to_owned),
+        );
+    }
+
+    debug!("[{name}] Ingesting {} lines from {peer}", lines.len());
+    for line in lines {
+        let mut variables = json_variables(&line);
+        variables.insert("peer".into(), Value::String(peer.ip().to_string()));
+        if event_tx
+            .send(Event::NewMessage(line, variables))
+            .await
+            .is_err()
+        {
+            return Ok(respond(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Monitor has exited",
+            ));
+        }
+    }
+    Ok(respond(StatusCode::ACCEPTED, "Accepted"))
+}
+
+/// Returns whether a request has the bearer token whose hash is `token_hash`. Tokens are compared
+/// by hash so the comparison doesn't leak how much of a guess is right.
+pub fn authorized(headers: &HeaderMap, token_hash: &[u8]) -> bool {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|token| Sha256::digest(token).as_slice() == token_hash)
+}
+
+fn respond(status: StatusCode, message: &str) -> Response<Full<Bytes>> {
+    let mut res = Response::new(Full::new(Bytes::from(format!("{message}\n"))));
+    *res.status_mut() = status;
+    res
+}
@@ -0,0 +1,61 @@
+//! What the `ramon` binary and the tests use of ramon-core besides its API. None of it is stable.
+
+pub mod aggregator {
+    pub use crate::aggregator::format_utc;
+}
+
+pub mod api {
+    pub use crate::api::ApiServer;
+}
+
+pub mod audit {
+    pub use crate::audit::init;
+}
+
+pub mod events {
+    pub use crate::events::{init, query, Event, EventsConfig};
+}
+
+pub mod exec_limit {
+    pub use crate::exec_limit::init;
+}
+
+pub mod health {
+    pub use crate::health::{ready, run, HealthServer};
+}
+
+pub mod ingest {
+    pub use crate::ingest::IngestServer;
+}
+
+pub mod meta {
+    pub use crate::meta::{init, report_fatal, MONITOR};
+}
+
+pub mod monitor {
+    pub use crate::monitor::Trace;
+}
+
+pub mod overrides {
+    pub use crate::overrides::load;
+}
+
+pub mod platform {
+    pub use crate::platform::{config_path, drop_privileges, set_config_path};
+}
+
+pub mod silence {
+    pub use crate::silence::{silence, unsilence};
+}
+
+pub mod state {
+    pub use crate::state::{check_disk_pressure, detach, DISK_CHECK_INTERVAL};
+}
+
+pub mod status {
+    pub use crate::status::{now, snapshot, MonitorStatus, Status};
+}
+
+pub mod systemd {
+    pub use crate::systemd::{notify, watchdog_interval};
+}
@@ -0,0 +1,75 @@
+//! Follows the journal of a systemd unit with `journalctl`, for `service`.
+
+use std::process::Stdio;
+
+use anyhow::{anyhow, bail, Result};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    process::{Child, Command},
+    sync::mpsc::Sender,
+};
+
+use crate::{
+    error::{Error, ErrorKind},
+    monitor::Event,
+    source::{BoxFuture, Source},
+};
+
+/// The source of `service`, which follows the journal of a systemd unit with `journalctl`.
+pub struct Journal {
+    service: String,
+    /// `journalctl`, once the source is opened.
+    child: Option<Child>,
+}
+
+impl Journal {
+    /// Follows the messages of the systemd unit `service`.
+    pub fn new(service: String) -> Self {
+        Self {
+            service,
+            child: None,
+        }
+    }
+}
+
+impl Source for Journal {
+    fn kind(&self) -> &'static str {
+        "service"
+    }
+
+    fn open(&mut self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async {
+            let child = Command::new("journalctl")
+                .args(["-n0", "-fu", &self.service])
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .kill_on_drop(true)
+                .spawn()
+                .map_err(|err| {
+                    Error::new(
+                        ErrorKind::JournalSpawn,
+                        format!("Failed to spawn journalctl: {err}"),
+                    )
+                })?;
+            self.child = Some(child);
+            Ok(())
+        })
+    }
+
+    fn run(self: Box<Self>, event_tx: Sender<Event>) -> BoxFuture<'static, Result<()>> {
+        Box::pin(async move {
+            let mut child = self.child.ok_or(anyhow!("journalctl wasn't spawned."))?;
+            let stdout = child
+                .stdout
+                .take()
+                .ok_or(anyhow!("Failed to capture stdout."))?;
+            let mut lines = BufReader::new(stdout).lines();
+            while let Some(line) = lines.next_line().await? {
+                if event_tx.send(Event::NewLogLine(line)).await.is_err() {
+                    return Ok(());
+                }
+            }
+            bail!("journalctl exited early.");
+        })
+    }
+}
@@ -0,0 +1,113 @@
+//! The monitors, sources, and notification channels of ramon, for programs that embed log
+//! watching and alerting rather than running the `ramon` binary.
+//!
+//! A program usually parses a config with [`config::parse`] or [`config::load`], spawns the
+//! [`Aggregator`] of each notification config with [`Tenant::spawn_aggregators`], then builds a
+//! [`Monitor`] from each [`MonitorConfig`](config::MonitorConfig) and spawns [`Monitor::start`].
+//! Monitors can be given sources of their own with [`Monitor::add_source`], and notification
+//! configs can create channels of their own with [`notifier::register`].
+//!
+//! The status of monitors, silences, registered channels, and the like are global, and persisted
+//! state is stored in one cache directory by the names of monitors and notification configs, so a
+//! process can only run one instance: the tenants of one config.
+//!
+//! ```no_run
+//! use std::{mem, sync::Arc};
+//!
+//! use ramon_core::{
+//!     clock::{SharedClock, SystemClock},
+//!     config, Monitor,
+//! };
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let clock: SharedClock = Arc::new(SystemClock);
+//! let config = config::parse(r#"
+//!     [notify.default]
+//!     webhook = "https://hooks.example.com/ramon"
+//!
+//!     [monitor.auth]
+//!     log = "/var/log/auth.log"
+//!     match_log = "Failed password"
+//! "#, &clock)?;
+//! for mut tenant in config.tenants {
+//!     tenant.spawn_aggregators();
+//!     for monitor in mem::take(&mut tenant.monitors) {
+//!         let notify_tx = tenant.aggregator_for(&monitor)?;
+//!         let mut monitor =
+//!             Monitor::new(monitor, notify_tx, tenant.variables.clone(), clock.clone()).await?;
+//!         tokio::spawn(async move { monitor.start().await });
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+#![warn(missing_docs)]
+
+pub(crate) mod active;
+pub(crate) mod aggregator;
+pub(crate) mod alertmanager;
+pub(crate) mod anomaly;
+pub(crate) mod api;
+pub(crate) mod audit;
+pub(crate) mod ban;
+pub(crate) mod bus;
+pub mod clock;
+pub(crate) mod command_check;
+pub(crate) mod command_source;
+pub mod config;
+pub(crate) mod desktop;
+pub(crate) mod dns;
+pub(crate) mod docker;
+pub(crate) mod email;
+pub mod error;
+pub(crate) mod events;
+pub(crate) mod exec;
+pub(crate) mod exec_limit;
+pub(crate) mod expression;
+pub(crate) mod file;
+pub(crate) mod filter;
+pub(crate) mod health;
+pub(crate) mod highlight;
+pub(crate) mod ingest;
+#[doc(hidden)]
+pub mod internal;
+pub(crate) mod journal;
+pub(crate) mod line_context;
+pub(crate) mod log_watcher;
+pub(crate) mod matrix;
+pub(crate) mod meta;
+pub(crate) mod monitor;
+pub(crate) mod mqtt;
+pub mod notifier;
+pub(crate) mod overrides;
+pub(crate) mod pairing;
+pub(crate) mod pipeline;
+pub(crate) mod platform;
+pub(crate) mod privacy;
+pub(crate) mod push;
+pub(crate) mod rate_limit;
+pub(crate) mod recipients;
+pub(crate) mod scenario;
+pub(crate) mod script;
+pub(crate) mod silence;
+pub(crate) mod sms;
+pub mod source;
+pub(crate) mod state;
+pub(crate) mod status;
+pub(crate) mod syslog;
+pub(crate) mod systemd;
+pub(crate) mod teams;
+pub(crate) mod template;
+pub(crate) mod uptime;
+pub(crate) mod variables;
+pub(crate) mod webhook;
+pub(crate) mod window;
+
+pub use aggregator::Aggregator;
+pub use config::{Config, Notification, NotificationConfig, Tenant};
+pub use error::{Error, ErrorKind};
+pub use monitor::Monitor;
+pub use notifier::Notifier;
+pub use source::Source;
+pub use variables::{Globals, Variables};
@@ -0,0 +1,144 @@
+//! `context`, which attaches the lines before and after a match to its notification.
+
+use std::{collections::HashMap, collections::VecDeque, mem::take, time::Duration};
+
+use tokio::time::Instant;
+use toml::Value;
+
+use crate::clock::SharedClock;
+
+/// Keeps recent log lines so that matches can be reported with the lines surrounding them.
+pub struct LineContext {
+    before: usize,
+    after: usize,
+    timeout: Duration,
+    recent: VecDeque<String>,
+    pending: Vec<PendingMatch>,
+    clock: SharedClock,
+}
+
+/// A match that is waiting for its after-context.
+struct PendingMatch {
+    variables: HashMap<String, Value>,
+    before: Vec<String>,
+    line: String,
+    after: Vec<String>,
+    deadline: Instant,
+}
+
+impl LineContext {
+    /// Keeps `before` lines, and waits up to `timeout` for `after` lines.
+    pub fn new(before: usize, after: usize, timeout: Duration, clock: SharedClock) -> Self {
+        Self {
+            before,
+            after,
+            timeout,
+            recent: VecDeque::with_capacity(before + 1),
+            pending: Vec::new(),
+            clock,
+        }
+    }
+
+    /// Records a new line. Returns the variables of pending matches whose after-context is now
+    /// complete.
+    pub fn push(&mut self, line: &str) -> Vec<HashMap<String, Value>> {
+        for pending in &mut self.pending {
+            pending.after.push(line.to_owned());
+        }
+        let after = self.after;
+        let ready = self.take_pending(|pending| pending.after.len() >= after);
+
+        self.recent.push_back(line.to_owned());
+        // The newest line is the current line, which is not part of its own before-context.
+        while self.recent.len() > self.before + 1 {
+            self.recent.pop_front();
+        }
+
+        ready
+    }
+
+    /// Attaches context to a match on the most recent line. If after-context is required, the
+    /// match is deferred and `None` is returned.
+    pub fn attach(
+        &mut self,
+        mut variables: HashMap<String, Value>,
+    ) -> Option<HashMap<String, Value>> {
+        let mut before = self.recent.iter().cloned().collect::<Vec<String>>();
+        let line = before.pop().unwrap_or_default();
+        if self.after == 0 {
+            insert_context(&mut variables, before, &line, Vec::new());
+            return Some(variables);
+        }
+
+        self.pending.push(PendingMatch {
+            variables,
+            before,
+            line,
+            after: Vec::with_capacity(self.after),
+            deadline: self.clock.now() + self.timeout,
+        });
+        None
+    }
+
+    /// Returns when the oldest pending match should be emitted, even if its after-context is
+    /// incomplete.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.pending.iter().map(|pending| pending.deadline).min()
+    }
+
+    /// Returns the variables of pending matches whose deadline has passed.
+    pub fn expire(&mut self) -> Vec<HashMap<String, Value>> {
+        let now = self.clock.now();
+        self.take_pending(|pending| pending.deadline <= now)
+    }
+
+    /// Returns the variables of every pending match, such as when the input has ended.
+    pub fn flush(&mut self) -> Vec<HashMap<String, Value>> {
+        self.take_pending(|_| true)
+    }
+
+    fn take_pending(
+        &mut self,
+        is_ready: impl Fn(&PendingMatch) -> bool,
+    ) -> Vec<HashMap<String, Value>> {
+        let (ready, pending) = take(&mut self.pending)
+            .into_iter()
+            .partition::<Vec<PendingMatch>, _>(is_ready);
+        self.pending = pending;
+        ready
+            .into_iter()
+            .map(|mut pending| {
+                insert_context(
+                    &mut pending.variables,
+                    pending.before,
+                    &pending.line,
+                    pending.after,
+                );
+                pending.variables
+            })
+            .collect()
+    }
+}
+
+/// Sets `context`, and the lines before and after the match as `context_before` and
+/// `context_after`.
+fn insert_context(
+    variables: &mut HashMap<String, Value>,
+    before: Vec<String>,
+    line: &str,
+    after: Vec<String>,
+) {
+    let context = format_context(&before, line, &after);
+    variables.insert("context".into(), context.into());
+    variables.insert("context_before".into(), before.into());
+    variables.insert("context_after".into(), after.into());
+}
+
+/// Formats context lines like `grep -C`, marking the matched line with `>`.
+fn format_context(before: &[String], line: &str, after: &[String]) -> String {
+    let mut context = Vec::with_capacity(before.len() + 1 + after.len());
+    context.extend(before.iter().map(|line| format!("  {line}")));
+    context.push(format!("> {line}"));
+    context.extend(after.iter().map(|line| format!("  {line}")));
+    context.join("\n")
+}
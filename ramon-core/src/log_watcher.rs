@@ -0,0 +1,478 @@
+//! Follows log files, including rotated files and globs, and sends each new line to a monitor.
+
+use crate::{
+    config::{LogConfig, WatchMode},
+    error::{Error, ErrorKind},
+    monitor::Event,
+    platform::{self, FileId},
+    source::{BoxFuture, Source},
+    status,
+};
+use anyhow::{bail, Result};
+use encoding_rs::Encoding;
+use log::{debug, info, warn};
+use notify::{
+    event::{CreateKind, ModifyKind, RemoveKind, RenameMode},
+    EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use std::{
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncReadExt, AsyncSeekExt},
+    sync::mpsc::{self, Receiver, Sender},
+    time::sleep,
+};
+use tracing::instrument;
+
+/// Filesystems whose changes are usually not reported by inotify, such as those mounted over the
+/// network. FUSE filesystems (e.g. sshfs) are included as well.
+const NETWORK_FILESYSTEMS: [&str; 6] = ["nfs", "nfs4", "cifs", "smb3", "smbfs", "9p"];
+
+/// Lines longer than this are skipped.
+const MAX_LINE_LEN: usize = 1024 * 1024;
+const READ_BUFFER_LEN: usize = 64 * 1024;
+
+/// The source of `log`, which tails a file, following it across rotations.
+pub struct LogWatcher {
+    name: String,
+    /// Watches the parent directory rather than the file, so that a file that is deleted and
+    /// recreated (e.g. by logrotate's `create` mode) is reopened as soon as it reappears. `None`
+    /// if the file is polled instead.
+    _watcher: Option<RecommendedWatcher>,
+    path: PathBuf,
+    file: File,
+    /// The identity of the open file, used to detect when the path is replaced.
+    file_id: Option<FileId>,
+    cursor: u64,
+    /// Whether the rest of the line at the cursor is being skipped because it's too long.
+    skipping_line: bool,
+    encoding: &'static Encoding,
+    watcher_rx: Receiver<Result<notify::Event, notify::Error>>,
+}
+
+impl LogWatcher {
+    /// Opens the log files of `config`, reporting errors as the monitor `name`.
+    pub async fn new(name: String, config: LogConfig) -> Result<Self> {
+        let LogConfig {
+            path,
+            watch_mode,
+            from_start,
+            encoding,
+        } = config;
+        let mut file = OpenOptions::new()
+            .read(true)
+            .open(&path)
+            .await
+            .map_err(|err| {
+                Error::new(
+                    ErrorKind::log_open(&err),
+                    format!("Failed to open {path:?}: {err}"),
+                )
+            })?;
+        let cursor = match from_start {
+            true => 0,
+            false => file.seek(SeekFrom::End(0)).await?,
+        };
+        let file_id = platform::file_id(&file.metadata().await?);
+        status::update(&name, |status| status.cursor = Some(cursor));
+
+        let (watcher_tx, watcher_rx) = mpsc::channel(1);
+        let watcher = match watch_mode {
+            WatchMode::Native => Some(Self::watch_dir(&name, &path, watcher_tx)?),
+            WatchMode::Poll(interval) => {
+                tokio::spawn(poll(path.clone(), interval, watcher_tx));
+                None
+            }
+        };
+
+        Ok(Self {
+            name,
+            _watcher: watcher,
+            path,
+            file,
+            file_id,
+            cursor,
+            skipping_line: false,
+            encoding,
+            watcher_rx,
+        })
+    }
+
+    fn watch_dir(
+        name: &str,
+        path: &Path,
+        watcher_tx: Sender<Result<notify::Event, notify::Error>>,
+    ) -> Result<RecommendedWatcher> {
+        let mut watcher = notify::recommended_watcher(move |res| {
+            watcher_tx.blocking_send(res).unwrap();
+        })
+        .map_err(|err| {
+            Error::new(
+                ErrorKind::LogWatch,
+                format!("Failed to create watcher: {err}"),
+            )
+        })?;
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        if let Some(fs_type) = platform::filesystem_type(dir) {
+            if NETWORK_FILESYSTEMS
+                .iter()
+                .any(|network_fs| fs_type == *network_fs || fs_type.starts_with("fuse"))
+            {
+                warn!(
+                    "[{name}] {path:?} is on a {fs_type} filesystem, which may not report changes. Consider setting `watch_mode = \"poll\"`."
+                );
+            }
+        }
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|err| {
+                Error::new(
+                    ErrorKind::LogWatch,
+                    format!("Failed to watch {dir:?}: {err}"),
+                )
+            })?;
+        Ok(watcher)
+    }
+
+    async fn start(mut self, event_tx: Sender<Event>) -> Result<()> {
+        // Read the existing contents if `from_start` is set.
+        let size = self.file.metadata().await?.len();
+        if size > self.cursor {
+            self.process_chunk(size, false, &event_tx).await?;
+        }
+
+        while let Some(res) = self.watcher_rx.recv().await {
+            // The monitor has stopped.
+            if event_tx.is_closed() {
+                return Ok(());
+            }
+            self.process_log_event(res?, &event_tx).await?;
+        }
+        bail!("No more events.");
+    }
+
+    async fn process_log_event(
+        &mut self,
+        event: notify::Event,
+        event_tx: &Sender<Event>,
+    ) -> Result<()> {
+        // Ignore the other files in the directory.
+        if !event
+            .paths
+            .iter()
+            .any(|path| path.file_name() == self.path.file_name())
+        {
+            return Ok(());
+        }
+        debug!("[{}] Event: {event:?}", self.name);
+
+        // Handle log rotation. The old file stays open until its replacement appears.
+        let created = match event.kind {
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) | EventKind::Remove(_) => {
+                info!(
+                    "[{}] File {:?} was moved or deleted. Waiting for it to be recreated.",
+                    self.name, self.path,
+                );
+                false
+            }
+            EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => true,
+            _ => false,
+        };
+        if self.was_replaced(created).await {
+            return self.reinit_file_descriptors(event_tx).await;
+        }
+
+        let new_size = self.file.metadata().await?.len();
+        if new_size < self.cursor {
+            // The file was truncated in place (e.g. by logrotate's `copytruncate` mode), so
+            // anything in it now was written after the truncation.
+            warn!("[{}] File {:?} was truncated", self.name, self.path);
+            self.cursor = 0;
+            self.skipping_line = false;
+            if new_size == 0 {
+                return Ok(());
+            }
+        } else if new_size == self.cursor {
+            return Ok(());
+        }
+        self.process_chunk(new_size, false, event_tx).await
+    }
+
+    /// Returns whether the path now refers to a different file than the open one. Where file
+    /// identities aren't supported, any creation at the path is assumed to be a new file.
+    async fn was_replaced(&mut self, created: bool) -> bool {
+        let Some(file_id) = self.file_id else {
+            return created;
+        };
+        match tokio::fs::metadata(&self.path).await {
+            Ok(metadata) => platform::file_id(&metadata) != Some(file_id),
+            // The file was moved or deleted, and has not been recreated yet.
+            Err(_) => false,
+        }
+    }
+
+    async fn reinit_file_descriptors(&mut self, event_tx: &Sender<Event>) -> Result<()> {
+        // Lines written to the old file just before it was rotated are read first. Nothing else
+        // will be written to it, so its last line is complete even without a newline.
+        let old_size = self.file.metadata().await?.len();
+        if old_size > self.cursor {
+            self.process_chunk(old_size, true, event_tx).await?;
+        }
+
+        info!(
+            "[{}] File {:?} was replaced. Reestablishing file descriptors.",
+            self.name, self.path,
+        );
+
+        self.file = OpenOptions::new()
+            .read(true)
+            .open(&self.path)
+            .await
+            .map_err(|err| {
+                Error::new(
+                    ErrorKind::log_open(&err),
+                    format!("Failed to reopen {:?}: {err}", self.path),
+                )
+            })?;
+        self.file_id = platform::file_id(&self.file.metadata().await?);
+        self.cursor = 0;
+        self.skipping_line = false;
+        info!("[{}] File descriptors were reestablished.", self.name);
+
+        // Read anything written before the watcher reported the creation.
+        let new_size = self.file.metadata().await?.len();
+        match new_size {
+            0 => Ok(()),
+            new_size => self.process_chunk(new_size, false, event_tx).await,
+        }
+    }
+
+    /// Reads the lines between the cursor and `new_size` in fixed-size pieces, so that memory use
+    /// is bounded however much the file grew. Unless `at_eof` is true, an incomplete last line is
+    /// left for the next call.
+    #[instrument(name = "read", skip_all, fields(monitor = %self.name, bytes = new_size - self.cursor))]
+    async fn process_chunk(
+        &mut self,
+        new_size: u64,
+        at_eof: bool,
+        event_tx: &Sender<Event>,
+    ) -> Result<()> {
+        info!(
+            "[{}] Log file grew by {} bytes.",
+            self.name,
+            new_size - self.cursor
+        );
+
+        // SeekFrom::End is not used here because it introduces a race condition if the file grew
+        // immediately after the size was checked.
+        self.file.seek(SeekFrom::Start(self.cursor)).await?;
+        let mut buffer = vec![0; READ_BUFFER_LEN];
+        let mut line = Vec::new();
+        let mut position = self.cursor;
+        while position < new_size {
+            let len = (new_size - position).min(READ_BUFFER_LEN as u64) as usize;
+            let read = self.file.read(&mut buffer[..len]).await?;
+            if read == 0 {
+                // The file was truncated while it was being read.
+                break;
+            }
+            position += read as u64;
+
+            let mut rest = &buffer[..read];
+            while let Some(newline) = rest.iter().position(|&byte| byte == b'\n') {
+                match self.skipping_line {
+                    true => self.skipping_line = false,
+                    false => {
+                        line.extend_from_slice(&rest[..newline]);
+                        self.send_line(&line, event_tx).await?;
+                    }
+                }
+                line.clear();
+                rest = &rest[newline + 1..];
+                self.cursor = position - rest.len() as u64;
+            }
+            if self.skipping_line {
+                self.cursor = position;
+            } else {
+                line.extend_from_slice(rest);
+                if line.len() > MAX_LINE_LEN {
+                    warn!(
+                        "[{}] Skipping a line longer than {MAX_LINE_LEN} bytes.",
+                        self.name
+                    );
+                    line.clear();
+                    self.skipping_line = true;
+                    self.cursor = position;
+                }
+            }
+        }
+
+        if at_eof {
+            if !line.is_empty() {
+                self.send_line(&line, event_tx).await?;
+            }
+            self.skipping_line = false;
+            self.cursor = position;
+        }
+        status::update(&self.name, |status| status.cursor = Some(self.cursor));
+        Ok(())
+    }
+
+    async fn send_line(&self, line: &[u8], event_tx: &Sender<Event>) -> Result<()> {
+        let (line, had_errors) = self.encoding.decode_without_bom_handling(line);
+        if had_errors {
+            debug!(
+                "[{}] Replaced invalid {} in line: {line}",
+                self.name,
+                self.encoding.name()
+            );
+        }
+        let line = line.strip_suffix('\r').unwrap_or(&line);
+        event_tx.send(Event::NewLogLine(line.to_owned())).await?;
+        Ok(())
+    }
+}
+
+impl Source for LogWatcher {
+    fn kind(&self) -> &'static str {
+        "log"
+    }
+
+    fn run(self: Box<Self>, event_tx: Sender<Event>) -> BoxFuture<'static, Result<()>> {
+        Box::pin(self.start(event_tx))
+    }
+}
+
+/// Sends an event whenever the size, modification time, or identity of the file changes. Unlike
+/// notify's `PollWatcher`, this notices writes within the same second.
+async fn poll(
+    path: PathBuf,
+    interval: Duration,
+    watcher_tx: Sender<Result<notify::Event, notify::Error>>,
+) {
+    type Snapshot = (u64, Option<SystemTime>, Option<FileId>);
+    let snapshot = |metadata: std::fs::Metadata| -> Snapshot {
+        (
+            metadata.len(),
+            metadata.modified().ok(),
+            platform::file_id(&metadata),
+        )
+    };
+
+    let mut last = tokio::fs::metadata(&path).await.ok().map(snapshot);
+    loop {
+        sleep(interval).await;
+        let current = tokio::fs::metadata(&path).await.ok().map(snapshot);
+        if current == last {
+            continue;
+        }
+        let kind = match (&last, &current) {
+            (_, None) => EventKind::Remove(RemoveKind::Any),
+            (None, Some(_)) => EventKind::Create(CreateKind::Any),
+            (Some(_), Some(_)) => EventKind::Modify(ModifyKind::Any),
+        };
+        let event = notify::Event::new(kind).add_path(path.clone());
+        if watcher_tx.send(Ok(event)).await.is_err() {
+            return;
+        }
+        last = current;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process};
+
+    use encoding_rs::UTF_8;
+    use notify::event::ModifyKind;
+
+    use super::*;
+
+    /// Returns a watcher of a new file in its own directory, which has `contents`. Events are sent
+    /// with `process` rather than noticed.
+    async fn watch(test: &str, contents: &str) -> LogWatcher {
+        let dir = std::env::temp_dir().join(format!("ramon-test-{}-{test}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test.log");
+        fs::write(&path, contents).unwrap();
+        let config = LogConfig {
+            path,
+            watch_mode: WatchMode::Poll(Duration::from_secs(60 * 60)),
+            from_start: false,
+            encoding: UTF_8,
+        };
+        LogWatcher::new(test.into(), config).await.unwrap()
+    }
+
+    /// Sends `kind` for the watched file, and returns the lines that were read.
+    async fn process(watcher: &mut LogWatcher, kind: EventKind) -> Vec<String> {
+        let (event_tx, mut event_rx) = mpsc::channel(16);
+        let event = notify::Event::new(kind).add_path(watcher.path.clone());
+        watcher.process_log_event(event, &event_tx).await.unwrap();
+        drop(event_tx);
+        let mut lines = Vec::new();
+        while let Some(event) = event_rx.recv().await {
+            if let Event::NewLogLine(line) = event {
+                lines.push(line);
+            }
+        }
+        lines
+    }
+
+    fn append(path: &Path, contents: &str) {
+        let mut file = fs::OpenOptions::new().append(true).open(path).unwrap();
+        std::io::Write::write_all(&mut file, contents.as_bytes()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn crlf() {
+        let mut watcher = watch("crlf", "").await;
+        append(&watcher.path, "one\r\ntwo\r\r\nthree\n");
+        let lines = process(&mut watcher, EventKind::Modify(ModifyKind::Any)).await;
+        // Only the line ending is stripped.
+        assert_eq!(lines, ["one", "two\r", "three"]);
+    }
+
+    #[tokio::test]
+    async fn rotation() {
+        let mut watcher = watch("rotation", "old\n").await;
+        let rotated = watcher.path.with_extension("log.1");
+        fs::rename(&watcher.path, &rotated).unwrap();
+        append(&rotated, "before rotation\n");
+
+        // The moved file is still read until its replacement appears.
+        let kind = EventKind::Modify(ModifyKind::Name(RenameMode::From));
+        let lines = process(&mut watcher, kind).await;
+        assert_eq!(lines, ["before rotation"]);
+        append(&rotated, "unterminated");
+        let lines = process(&mut watcher, EventKind::Remove(RemoveKind::File)).await;
+        assert!(lines.is_empty());
+
+        fs::write(&watcher.path, "new\n").unwrap();
+        let lines = process(&mut watcher, EventKind::Create(CreateKind::File)).await;
+        assert_eq!(lines, ["unterminated", "new"]);
+    }
+
+    #[tokio::test]
+    async fn recreated_by_rename() {
+        let mut watcher = watch("rename", "old\n").await;
+        fs::remove_file(&watcher.path).unwrap();
+        let lines = process(&mut watcher, EventKind::Remove(RemoveKind::File)).await;
+        assert!(lines.is_empty());
+
+        let tmp = watcher.path.with_extension("tmp");
+        fs::write(&tmp, "new\n").unwrap();
+        fs::rename(&tmp, &watcher.path).unwrap();
+        let kind = EventKind::Modify(ModifyKind::Name(RenameMode::To));
+        let lines = process(&mut watcher, kind).await;
+        assert_eq!(lines, ["new"]);
+    }
+}
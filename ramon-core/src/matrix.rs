@@ -0,0 +1,93 @@
+//! Sends notifications to a Matrix room through the client-server API.
+
+use anyhow::{anyhow, Result};
+use reqwest::Url;
+use serde_json::json;
+
+use crate::{
+    config::Notification,
+    error::{Error, ErrorKind},
+    highlight::{self, escape_html, Highlight},
+    notifier::{Delivery, Notifier},
+    source::BoxFuture,
+};
+
+/// The `matrix` channel.
+pub struct MatrixConfig {
+    /// The base URL of the homeserver (e.g. `https://matrix.org`).
+    pub homeserver: Url,
+    /// The access token of the user to send as.
+    pub access_token: String,
+    /// The ID of the room (e.g. `!abcdef:matrix.org`), rather than an alias.
+    pub room_id: String,
+}
+
+impl Notifier for MatrixConfig {
+    fn channel(&self) -> &'static str {
+        "matrix"
+    }
+
+    /// The ID of the delivery is the transaction ID, which lets the homeserver ignore retries of
+    /// the same message.
+    fn send<'a>(
+        &'a self,
+        notification: &'a Notification,
+        delivery: &'a Delivery,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(send(notification, self, delivery.id))
+    }
+}
+
+/// Sends a message with a Markdown body for clients that don't render HTML.
+async fn send(notification: &Notification, config: &MatrixConfig, txn_id: &str) -> Result<()> {
+    let mut url = config.homeserver.clone();
+    url.path_segments_mut()
+        .map_err(|_| anyhow!("Invalid homeserver {}", config.homeserver))?
+        .pop_if_empty()
+        .extend([
+            "_matrix",
+            "client",
+            "v3",
+            "rooms",
+            &config.room_id,
+            "send",
+            "m.room.message",
+            txn_id,
+        ]);
+
+    let mut body = format!("**{}**", notification.title);
+    let mut formatted_body = format!("<strong>{}</strong>", escape_html(&notification.title));
+    if !notification.body.is_empty() {
+        body += &format!("\n\n{}", notification.body);
+        formatted_body += &format!(
+            "<br>{}",
+            escape_html(&notification.body).replace('\n', "<br>")
+        );
+    }
+    let body = highlight::append(&body, notification.highlight.as_ref(), Highlight::markdown);
+    if let Some(highlight) = &notification.highlight {
+        formatted_body += &highlight.html();
+    }
+    let payload = json!({
+        "msgtype": "m.text",
+        "body": body.trim_end(),
+        "format": "org.matrix.custom.html",
+        "formatted_body": formatted_body,
+    });
+
+    let res = reqwest::Client::new()
+        .put(url)
+        .bearer_auth(&config.access_token)
+        .json(&payload)
+        .send()
+        .await
+        .and_then(|res| res.error_for_status());
+    match res {
+        Ok(_) => Ok(()),
+        Err(err) => Err(Error::new(
+            ErrorKind::Matrix,
+            format!("Failed to send Matrix message: {err}"),
+        )
+        .into()),
+    }
+}
@@ -0,0 +1,97 @@
+//! Reports ramon's own failures (e.g. a monitor that stopped, or a notification that couldn't be
+//! delivered) through the notification config set by `self_notify`.
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::{LazyLock, Mutex, OnceLock},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use log::{debug, warn};
+use tokio::{
+    pin,
+    sync::{mpsc::Sender, Notify},
+};
+
+use crate::config::{Notification, Severity};
+
+/// The monitor name of internal notifications.
+pub const MONITOR: &str = "ramon";
+
+/// The same failure is reported at most once per hour.
+const COOLDOWN: Duration = Duration::from_secs(60 * 60);
+/// How long to wait for a fatal failure to be delivered before exiting.
+const FATAL_TIMEOUT: Duration = Duration::from_secs(30);
+
+static TARGET: OnceLock<(String, Sender<Notification>)> = OnceLock::new();
+static LAST_REPORTED: LazyLock<Mutex<HashMap<String, Instant>>> = LazyLock::new(Default::default);
+static DELIVERED: Notify = Notify::const_new();
+
+/// Sends internal notifications to the notification config `name`.
+pub fn init(name: String, notify_tx: Sender<Notification>) {
+    let _ = TARGET.set((name, notify_tx));
+}
+
+/// Returns whether `name` is the config that receives internal notifications. Its own failures
+/// aren't reported, since the report couldn't be delivered either.
+pub fn is_target(name: &str) -> bool {
+    TARGET.get().is_some_and(|(target, _)| target == name)
+}
+
+/// Reports a failure of `source` (a monitor or notification config), unless self-monitoring is
+/// disabled or the same failure was reported within the last hour. Returns whether it was sent.
+pub fn report(source: &str, severity: Severity, title: String, err: &dyn Display) -> bool {
+    let Some((target, notify_tx)) = TARGET.get() else {
+        return false;
+    };
+    let key = format!("{source}\0{title}");
+    {
+        let mut last_reported = LAST_REPORTED.lock().unwrap();
+        let now = Instant::now();
+        if let Some(last) = last_reported.get(&key) {
+            if now.duration_since(*last) < COOLDOWN {
+                debug!("[{source}] Not reporting {title:?} again yet");
+                return false;
+            }
+        }
+        last_reported.insert(key, now);
+    }
+
+    let notification = Notification {
+        r#type: target.clone(),
+        monitor: MONITOR.into(),
+        severity,
+        title,
+        body: err.to_string(),
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default(),
+        variables: HashMap::from([("source".into(), source.into())]),
+        highlight: None,
+        resolved: false,
+    };
+    let notify_tx = notify_tx.clone();
+    tokio::spawn(async move {
+        if notify_tx.send(notification).await.is_err() {
+            warn!("Failed to report a failure: the `self_notify` config has stopped");
+        }
+    });
+    true
+}
+
+/// Reports a failure that is about to stop ramon, and waits for it to be sent.
+pub async fn report_fatal(source: &str, title: String, err: &(dyn Display + Sync)) {
+    let delivered = DELIVERED.notified();
+    pin!(delivered);
+    delivered.as_mut().enable();
+    if report(source, Severity::Critical, title, err) {
+        let _ = tokio::time::timeout(FATAL_TIMEOUT, delivered).await;
+    }
+}
+
+/// Called by the aggregator once it has tried to send an internal notification.
+pub fn delivered() {
+    DELIVERED.notify_waiters();
+}
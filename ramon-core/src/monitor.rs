@@ -0,0 +1,1468 @@
+//! Monitors, which evaluate the conditions of their config against each event from their sources
+//! and run their actions when an event matches.
+
+use crate::{
+    active::{self, TimeWindow},
+    anomaly::Anomaly,
+    audit,
+    ban::Ban,
+    bus,
+    clock::{SharedClock, Ticker},
+    command_check::CommandCheck,
+    command_source::CommandSource,
+    config::{
+        value_to_string, Composite, Exec, ExecOptions, MatchLog, MonitorConfig, Notification,
+        Severity, Source,
+    },
+    dns,
+    docker::{self, DockerAction},
+    error::{Error, ErrorKind},
+    events,
+    exec_limit::{ExecLimit, Submitted},
+    expression::{Expression, Literal},
+    filter::Filter,
+    highlight::Highlight,
+    ingest,
+    journal::Journal,
+    line_context::LineContext,
+    log_watcher::LogWatcher,
+    meta,
+    mqtt::MqttSubscriber,
+    overrides,
+    pairing::{Boundary, Pairing},
+    pipeline::{self, Action, Pipeline},
+    platform::Shell,
+    privacy::{self, PrivacyLevel, Sink},
+    scenario::Scenario,
+    script::{self, Script},
+    silence, source,
+    state::{self, Format},
+    status,
+    syslog::SyslogListener,
+    systemd::{self, UnitAction},
+    template,
+    uptime::Uptime,
+    variables::{self, Assignment, Globals},
+    window::Window,
+};
+use anyhow::{bail, Result};
+use log::{debug, info, warn};
+use regex::Regex;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    io::{self, IsTerminal},
+    iter::once,
+    num::NonZeroUsize,
+    process::{Output, Stdio},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tera::{Context, Tera};
+use tokio::{
+    io::AsyncWriteExt,
+    process::{Child, Command},
+    select,
+    sync::mpsc::{self, Receiver, Sender, UnboundedSender},
+    time::Instant,
+};
+use toml::Value;
+use tracing::{field, instrument, Instrument, Span};
+
+/// A running monitor, which evaluates the events of its sources and runs its actions.
+pub struct Monitor {
+    /// The name of the monitor, prefixed with its tenant.
+    pub name: String,
+    aggregator_tx: Sender<Notification>,
+
+    /// Lets sources be added after the monitor is created.
+    event_tx: Sender<Event>,
+    event_rx: Receiver<Event>,
+    /// When actions were last run, by key. Only tracked with `cooldown`.
+    last_action_times: HashMap<String, Instant>,
+
+    active: Vec<TimeWindow>,
+    cooldown: Option<Duration>,
+    /// Whether there's a `key` template.
+    keyed: bool,
+    sample: Option<u64>,
+    /// The number of matches so far, counted for `sample`.
+    sample_count: u64,
+    match_log: Option<MatchLog>,
+    composite: Option<Composite>,
+    anomaly: Option<Anomaly>,
+    window: Option<Window>,
+    reverse_dns: Vec<String>,
+    pairing: Option<Pairing>,
+    ignore_regexes: Vec<Regex>,
+    filters: Vec<Filter>,
+    filter_script: Option<Script>,
+    scenario: Option<Scenario>,
+    command_check: Option<CommandCheck>,
+    /// Whether the output of `command_check` is run through `match_log`, as with `exec_check`.
+    check_output: bool,
+    unique: Option<Unique>,
+    condition: Option<Expression>,
+    threshold: Option<Threshold>,
+    uptime: Option<Uptime>,
+    context: Option<LineContext>,
+
+    script: Option<Script>,
+    exec: Option<Exec>,
+    exec_options: ExecOptions,
+    exec_limit: ExecLimit,
+    /// How many times `exec` is retried after failing.
+    exec_retries: u32,
+    pipeline: Option<Arc<Pipeline>>,
+    /// The number of consecutive runs of `exec` that failed.
+    exec_failures: Arc<AtomicU32>,
+    notify: Option<Notification>,
+    resolve: Option<Expression>,
+    ban: Option<Ban>,
+    systemd: Option<UnitAction>,
+    docker: Option<DockerAction>,
+    dry_run: bool,
+    /// Set by `ramon replay`, which prints what the monitor does instead of running `exec` and
+    /// `ban`.
+    trace_tx: Option<UnboundedSender<Trace>>,
+
+    templates: Tera,
+    globals: Globals,
+    /// A copy of `globals`, taken when an event starts being evaluated and after `set` and `push`.
+    variables: HashMap<String, Value>,
+    assignments: Vec<Assignment>,
+    emit: Option<String>,
+    privacy: HashMap<String, PrivacyLevel>,
+    clock: SharedClock,
+    shell: Shell,
+}
+
+/// `exec` failing this many times in a row is reported by self-monitoring.
+const EXEC_FAILURE_LIMIT: u32 = 3;
+/// How long to wait before the first retry of `exec`, doubling after each retry.
+const EXEC_RETRY_DELAY: Duration = Duration::from_secs(1);
+/// How many characters of the stderr of a failed `exec` are included in its notification.
+const STDERR_LIMIT: usize = 2000;
+
+/// One value of the `unique` variable per line.
+const UNIQUE_FORMAT: Format = Format {
+    kind: "unique",
+    migrations: &[state::unchanged],
+    essential: true,
+};
+
+/// Something for a monitor to evaluate.
+pub enum Event {
+    /// `every` fired.
+    Tick,
+    /// A line of a log file or command.
+    NewLogLine(String),
+    /// A line along with variables set by its source (e.g. the hostname of a syslog message).
+    NewMessage(String, HashMap<String, Value>),
+    /// An event from within ramon, with its variables: another monitor emitted an event, or the
+    /// global variable of `on_expire` expired.
+    Internal(HashMap<String, Value>),
+}
+
+impl Event {
+    fn line(&self) -> Option<&str> {
+        match self {
+            Event::Tick | Event::Internal(_) => None,
+            Event::NewLogLine(line) | Event::NewMessage(line, _) => Some(line),
+        }
+    }
+}
+
+/// A step of handling an event, reported to `ramon replay`.
+pub enum Trace {
+    /// The line matched the pattern of `match_log` with this index.
+    Matched(usize),
+    /// `threshold` counted the event, and whether it was reached.
+    Threshold(bool),
+    /// The event was ignored because of `cooldown`.
+    CoolingDown,
+    /// The event was ignored because it's outside the times set by `active`.
+    Inactive,
+    /// The match was skipped by `sample`.
+    SampledOut,
+    /// The match was excluded by this key, `filter` or `filter.<variable>`.
+    Filtered(String),
+    /// Every condition was true, so the actions were run with these variables.
+    Fired(HashMap<String, Value>),
+    /// The command `exec` would have run.
+    Exec(String),
+    /// An action that would have run, other than `exec` and notifications, such as a step of
+    /// `actions` that sends a request.
+    Step(String),
+}
+
+/// If the message is a JSON object, returns its fields, except those that are null.
+pub fn json_variables(message: &str) -> HashMap<String, Value> {
+    match serde_json::from_str(message) {
+        Ok(serde_json::Value::Object(fields)) => fields
+            .into_iter()
+            .filter_map(|(key, value)| Some((key, Value::try_from(value).ok()?)))
+            .collect(),
+        _ => HashMap::new(),
+    }
+}
+
+struct Unique {
+    variable_name: String,
+    recorded_values: HashSet<String>,
+}
+
+struct Threshold {
+    threshold: NonZeroUsize,
+    duration: Duration,
+    /// The times of the last `threshold` events, by key.
+    histories: HashMap<String, VecDeque<Instant>>,
+}
+
+impl Monitor {
+    /// Opens the sources of `config` and returns the monitor, which evaluates events once it's
+    /// started. Notifications are sent to `aggregator_tx`, and `globals` are the variables of the
+    /// monitor's tenant.
+    pub async fn new(
+        config: MonitorConfig,
+        aggregator_tx: Sender<Notification>,
+        globals: Globals,
+        clock: SharedClock,
+    ) -> Result<Self> {
+        let variables = globals.lock().unwrap().values.clone();
+        let name = config.name;
+        status::update(&name, |_| {});
+
+        let mut templates = template::new_engine();
+        if let Some(exec) = &config.exec {
+            add_exec_templates(&mut templates, "exec", exec)?;
+        }
+        add_exec_option_templates(&mut templates, &config.exec_options)?;
+        if let Some(key) = &config.key {
+            template::add(&mut templates, "key", key)?;
+        }
+        if let Some(notification) = &config.notify {
+            template::add(&mut templates, "title", &notification.title)?;
+            template::add(&mut templates, "body", &notification.body)?;
+        }
+
+        if let Some(action) = &config.systemd {
+            template::add(&mut templates, "systemd", &action.unit)?;
+        }
+        if let Some(action) = &config.docker {
+            docker::add_templates(&mut templates, "docker", action)?;
+        }
+        let manages_units = config.systemd.is_some()
+            || config
+                .actions
+                .iter()
+                .any(|step| matches!(step.action, Action::Systemd(_)));
+        if manages_units {
+            systemd::check_permission(&name);
+        }
+        let pipeline = match config.actions.is_empty() {
+            true => None,
+            false => Some(Arc::new(Pipeline::new(
+                config.actions,
+                &config.exec_options,
+            )?)),
+        };
+
+        let (event_tx, event_rx) = mpsc::channel(1);
+
+        let period = config.every.as_ref().map(Ticker::period);
+        if let Some(ticker) = config.every {
+            source::spawn(&name, Box::new(ticker), event_tx.clone()).await?;
+        }
+        if let Some(log) = config.log {
+            let log_watcher = LogWatcher::new(name.clone(), log).await?;
+            source::spawn(&name, Box::new(log_watcher), event_tx.clone()).await?;
+        }
+        if let Some(service) = config.service {
+            source::spawn(&name, Box::new(Journal::new(service)), event_tx.clone()).await?;
+        }
+
+        if let Some(variable) = config.on_expire {
+            globals
+                .lock()
+                .unwrap()
+                .on_expire(variable, event_tx.clone());
+        }
+
+        let mut command_check = None;
+        let check_output = config.exec_check.is_some();
+        if let (Some(command), Some(period)) = (config.exec_check, period) {
+            command_check =
+                Some(CommandCheck::load(&name, command, config.shell.clone(), period).await?);
+        }
+        match (config.source, period) {
+            (None, _) => {}
+            // With `every`, the command is run on each tick rather than kept running.
+            (Some(Source::Command(command)), Some(period)) => {
+                command_check =
+                    Some(CommandCheck::load(&name, command, config.shell.clone(), period).await?);
+            }
+            (Some(Source::Syslog(addr)), _) => {
+                let listener = SyslogListener::new(name.clone(), addr).await?;
+                source::spawn(&name, Box::new(listener), event_tx.clone()).await?;
+            }
+            (Some(Source::Mqtt(broker, topics)), _) => {
+                let subscriber = MqttSubscriber::new(name.clone(), broker, topics);
+                source::spawn(&name, Box::new(subscriber), event_tx.clone()).await?;
+            }
+            (Some(Source::Ingest), _) => ingest::register(name.clone(), event_tx.clone()),
+            (Some(Source::Event(event)), _) => bus::subscribe(event, event_tx.clone()),
+            (Some(Source::Command(command)), None) => {
+                let command_source =
+                    CommandSource::new(name.clone(), command, config.shell.clone());
+                source::spawn(&name, Box::new(command_source), event_tx.clone()).await?;
+            }
+        }
+
+        let unique = match config.unique {
+            None => None,
+            Some(variable_name) => {
+                let recorded_values = match UNIQUE_FORMAT.read(&name).await? {
+                    None => HashSet::new(),
+                    Some(contents) => contents.lines().map(str::to_owned).collect(),
+                };
+                Some(Unique {
+                    variable_name,
+                    recorded_values,
+                })
+            }
+        };
+
+        let threshold = config.threshold.map(|(threshold, duration)| Threshold {
+            threshold,
+            duration,
+            histories: HashMap::new(),
+        });
+
+        let scenario = config.scenario.map(Scenario::new).transpose()?;
+        let ban = config
+            .ban
+            .map(|ban| Ban::new(ban, clock.clone(), config.shell.clone()))
+            .transpose()?;
+
+        let uptime = match config.uptime {
+            false => None,
+            true => Some(Uptime::load(&name).await?),
+        };
+
+        overrides::register(&name, event_tx.clone(), config.cooldown);
+        if let (Some(notification), false) = (&config.notify, config.dry_run) {
+            silence::register(
+                &name,
+                notification.r#type.clone(),
+                aggregator_tx.clone(),
+                clock.clone(),
+            );
+        }
+
+        Ok(Self {
+            name,
+            aggregator_tx,
+
+            event_tx,
+            event_rx,
+            last_action_times: HashMap::new(),
+
+            active: config.active,
+            cooldown: config.cooldown,
+            keyed: config.key.is_some(),
+            sample: config.sample,
+            sample_count: 0,
+            match_log: config.match_log,
+            composite: config.composite,
+            anomaly: config.anomaly.map(Anomaly::new),
+            window: config.window.map(Window::new),
+            reverse_dns: config.reverse_dns,
+            pairing: config.pair.map(|pair| Pairing::new(pair, clock.clone())),
+            ignore_regexes: config.ignore_log,
+            filters: config.filters,
+            filter_script: config.filter_script,
+            scenario,
+            command_check,
+            check_output,
+            unique,
+            condition: config.condition,
+            threshold,
+            uptime,
+            context: config.context.map(|(before, after, timeout)| {
+                LineContext::new(before, after, timeout, clock.clone())
+            }),
+
+            script: config.script,
+            exec: config.exec,
+            exec_options: config.exec_options,
+            exec_retries: config.exec_retries,
+            exec_limit: ExecLimit::new(config.max_concurrent_execs, config.exec_overflow),
+            pipeline,
+            exec_failures: Arc::default(),
+            notify: config.notify,
+            resolve: config.resolve,
+            ban,
+            systemd: config.systemd,
+            docker: config.docker,
+            dry_run: config.dry_run,
+            trace_tx: None,
+
+            templates,
+            globals,
+            variables,
+            assignments: config.assignments,
+            emit: config.emit,
+            privacy: config.privacy,
+            clock,
+            shell: config.shell,
+        })
+    }
+
+    /// Evaluates events until every source has ended, which is an error.
+    pub async fn start(&mut self) -> Result<()> {
+        info!("Starting monitor `{}`", self.name);
+
+        loop {
+            let deadline = self.context.as_ref().and_then(LineContext::next_deadline);
+            let next_unban = self.ban.as_ref().and_then(Ban::next_unban);
+            let next_timeout = self.pairing.as_ref().and_then(Pairing::next_deadline);
+            select! {
+                event = self.event_rx.recv() => match event {
+                    None => break,
+                    Some(event) => self.evaluate(event).await?,
+                },
+                // Emit matches whose after-context did not arrive in time.
+                _ = self.clock.sleep_until(deadline.unwrap_or_else(|| self.clock.now())), if deadline.is_some() => {
+                    let expired = self.context.as_mut().map(LineContext::expire).unwrap_or_default();
+                    for temp_variables in expired {
+                        self.run_actions(temp_variables).await?;
+                    }
+                }
+                // Fire for operations whose end wasn't logged in time.
+                _ = self.clock.sleep_until(next_timeout.unwrap_or_else(|| self.clock.now())), if next_timeout.is_some() => {
+                    self.expire_pairs().await?;
+                }
+                _ = self.clock.sleep_until(next_unban.unwrap_or_else(|| self.clock.now())), if next_unban.is_some() => {
+                    if let Some(ban) = &mut self.ban {
+                        ban.unban_expired(&self.name).await;
+                    }
+                }
+            }
+        }
+
+        bail!("No more events?");
+    }
+
+    /// Opens a source other than those set by the config, and runs it in the background.
+    pub async fn add_source(&self, source: Box<dyn source::Source>) -> Result<()> {
+        source::spawn(&self.name, source, self.event_tx.clone()).await
+    }
+
+    /// Reports each step of handling events to `trace_tx` instead of running `exec` and `ban`.
+    pub fn trace_to(&mut self, trace_tx: UnboundedSender<Trace>) {
+        self.trace_tx = Some(trace_tx);
+    }
+
+    /// Handles a line without the monitor running, after emitting matches whose after-context
+    /// timed out. Used by `ramon replay`, where the clock is driven by the timestamps of lines.
+    pub async fn replay(&mut self, line: String) -> Result<()> {
+        let expired = self
+            .context
+            .as_mut()
+            .map(LineContext::expire)
+            .unwrap_or_default();
+        for temp_variables in expired {
+            self.run_actions(temp_variables).await?;
+        }
+        self.expire_pairs().await?;
+        self.evaluate(Event::NewLogLine(line)).await
+    }
+
+    /// Evaluates the conditions for each operation that timed out, with the variables of its start.
+    async fn expire_pairs(&mut self) -> Result<()> {
+        let expired = self
+            .pairing
+            .as_mut()
+            .map(Pairing::expire)
+            .unwrap_or_default();
+        for temp_variables in expired {
+            self.evaluate(Event::Internal(temp_variables)).await?;
+        }
+        Ok(())
+    }
+
+    /// Emits matches still waiting for their after-context, since no more lines will arrive.
+    pub async fn finish_replay(&mut self) -> Result<()> {
+        let pending = self
+            .context
+            .as_mut()
+            .map(LineContext::flush)
+            .unwrap_or_default();
+        for temp_variables in pending {
+            self.run_actions(temp_variables).await?;
+        }
+        Ok(())
+    }
+
+    fn trace(&self, trace: Trace) {
+        if let Some(trace_tx) = &self.trace_tx {
+            let _ = trace_tx.send(trace);
+        }
+    }
+
+    /// Evaluate all conditions to determine if actions should be run.
+    #[instrument(name = "event", skip_all, fields(monitor = %self.name))]
+    async fn evaluate(&mut self, event: Event) -> Result<()> {
+        if overrides::paused(&self.name) {
+            return Ok(());
+        }
+        let is_line = event.line().is_some();
+        status::update(&self.name, |status| {
+            status.last_event = Some(status::now());
+            if is_line {
+                status.lines += 1;
+            }
+        });
+        self.variables = self.globals.lock().unwrap().values.clone();
+        if let (Some(context), Some(line)) = (&mut self.context, event.line()) {
+            for temp_variables in context.push(line) {
+                self.run_actions(temp_variables).await?;
+            }
+        }
+
+        // With `key`, the cooldown depends on the variables, so it's checked after the conditions.
+        if !self.keyed && self.cooling_down("") {
+            info!("[{}] Still cooling down.", self.name);
+            self.trace(Trace::CoolingDown);
+            return Ok(());
+        }
+
+        let is_tick = matches!(event, Event::Tick);
+        let temp_variables = self.check(event).await?;
+        let matched = temp_variables.is_some();
+        let resolves = temp_variables
+            .as_ref()
+            .is_some_and(|temp_variables| self.resolves(temp_variables));
+        // Lines that don't match leave `firing` as it was, so it's only cleared by a check of
+        // `every` that fails or by an event that `resolve`s. Events skipped by the cooldown don't
+        // change it either, so it's held at least until the cooldown ends.
+        status::update(&self.name, |status| {
+            if is_tick || matched {
+                status.firing = matched && !resolves;
+            }
+            if matched {
+                status.last_match = Some(status::now());
+            }
+        });
+
+        if let Some(uptime) = &mut self.uptime {
+            if is_tick && uptime.record(temp_variables.is_none()) {
+                if let Err(err) = uptime.store(&self.name).await {
+                    let err = Error::new(
+                        ErrorKind::CacheWrite,
+                        format!("Failed to store uptime: {err}"),
+                    );
+                    warn!("[{}] {err}", self.name);
+                }
+            }
+        }
+
+        match temp_variables {
+            None => Ok(()),
+            Some(mut temp_variables) => {
+                if self.keyed {
+                    let key = self.key(&temp_variables);
+                    if self.cooling_down(&key) {
+                        info!("[{}] Still cooling down for {key:?}.", self.name);
+                        self.trace(Trace::CoolingDown);
+                        return Ok(());
+                    }
+                }
+                status::update(&self.name, |status| status.matches += 1);
+                if audit::enabled() || events::enabled() {
+                    let redacted =
+                        privacy::redact(&temp_variables, &self.privacy, Sink::Notification);
+                    let details = serde_json::json!({ "variables": redacted });
+                    events::record("match", &self.name, &details);
+                    audit::record("match", &self.name, details);
+                }
+                if let Some(sample) = self.sample {
+                    // The first match is acted on, then every `sample`th after it.
+                    let skip = !self.sample_count.is_multiple_of(sample);
+                    self.sample_count += 1;
+                    if skip {
+                        self.trace(Trace::SampledOut);
+                        return Ok(());
+                    }
+                }
+                temp_variables.insert("stats".into(), status::stats(&self.name));
+                for variable in &self.reverse_dns {
+                    let Some(ip) = temp_variables
+                        .get(variable)
+                        .and_then(Value::as_str)
+                        .and_then(|ip| ip.parse().ok())
+                    else {
+                        continue;
+                    };
+                    if let Some(hostname) = dns::reverse(ip).await {
+                        temp_variables.insert(format!("{variable}_ptr"), hostname.into());
+                    }
+                }
+                if let Some(uptime) = &self.uptime {
+                    temp_variables.extend(uptime.variables());
+                }
+                if let (Some(context), true) = (&mut self.context, is_line) {
+                    match context.attach(temp_variables) {
+                        Some(with_context) => temp_variables = with_context,
+                        // Actions are run once the after-context arrives.
+                        None => return Ok(()),
+                    }
+                }
+                self.run_actions(temp_variables).await
+            }
+        }
+    }
+
+    /// Evaluates every condition except `cooldown`, returning the local variables if all of them
+    /// are true.
+    async fn check(&mut self, event: Event) -> Result<Option<HashMap<String, Value>>> {
+        let (line, expired) = match event {
+            Event::NewLogLine(line) => (Some((line, HashMap::new())), None),
+            Event::NewMessage(line, variables) => (Some((line, variables)), None),
+            Event::Tick => (None, None),
+            Event::Internal(variables) => (None, Some(variables)),
+        };
+        if !self.active.is_empty() && !active::is_active(&self.active) {
+            self.trace(Trace::Inactive);
+            return Ok(None);
+        }
+        if let (Some((line, temp_variables)), Some(pairing)) = (&line, &self.pairing) {
+            if self.ignore_regexes.iter().any(|regex| regex.is_match(line)) {
+                return Ok(None);
+            }
+            let Some(boundary) = pairing.boundary(line) else {
+                return Ok(None);
+            };
+            // The monitor only fires for operations that time out.
+            match boundary {
+                Boundary::Start(mut variables) => {
+                    variables.extend(temp_variables.clone());
+                    let key = self.key(&variables);
+                    debug!("[{}] Operation {key:?} started.", self.name);
+                    if let Some(pairing) = &mut self.pairing {
+                        pairing.start(key, variables);
+                    }
+                }
+                Boundary::End(variables) => {
+                    let key = self.key(&variables);
+                    if let Some(pairing) = &mut self.pairing {
+                        if pairing.end(&key) {
+                            debug!("[{}] Operation {key:?} ended.", self.name);
+                        }
+                    }
+                }
+            }
+            return Ok(None);
+        }
+        let temp_variables = match (line, expired) {
+            (Some((line, mut temp_variables)), _) => match self.match_line(&line) {
+                None => return Ok(None),
+                Some(matched) => {
+                    temp_variables.extend(matched);
+                    if let Some(window) = &mut self.window {
+                        let value = temp_variables
+                            .get(window.variable())
+                            .and_then(|value| Literal::from(value).as_number());
+                        let Some(value) = value else {
+                            debug!(
+                                "[{}] `{}` isn't a number; skipping the line.",
+                                self.name,
+                                window.variable()
+                            );
+                            return Ok(None);
+                        };
+                        let stats = window.record(self.clock.now(), value);
+                        temp_variables.insert("window".into(), stats);
+                    }
+                    if let Some(anomaly) = &mut self.anomaly {
+                        match anomaly.record(self.clock.now()) {
+                            None => return Ok(None),
+                            Some(rate) => temp_variables.extend(rate),
+                        }
+                    }
+                    temp_variables
+                }
+            },
+            (None, Some(temp_variables)) => temp_variables,
+            (None, None) => match (&self.scenario, &mut self.command_check) {
+                (Some(scenario), _) => match scenario.run(&self.name, &self.variables).await {
+                    // Every step succeeded.
+                    None => return Ok(None),
+                    Some(temp_variables) => {
+                        debug!("[{}] Scenario failed: {}", self.name, temp_variables["err"]);
+                        temp_variables
+                    }
+                },
+                (None, Some(command_check)) => {
+                    let (success, mut temp_variables) = command_check.run(&self.name).await;
+                    if success && self.check_output && self.match_log.is_some() {
+                        // Successful runs of `exec_check` fire on the first line that matches.
+                        let output = temp_variables
+                            .get("output")
+                            .and_then(Value::as_str)
+                            .unwrap_or_default()
+                            .to_owned();
+                        let matched = output
+                            .lines()
+                            .find_map(|line| Some((line, self.match_line(line)?)));
+                        match matched {
+                            None => return Ok(None),
+                            Some((line, matched)) => {
+                                temp_variables.insert("line".into(), line.into());
+                                temp_variables.extend(matched);
+                            }
+                        }
+                    } else if success && self.condition.is_none() {
+                        // Without a condition, only failed runs fire.
+                        return Ok(None);
+                    }
+                    temp_variables
+                }
+                (None, None) => match &self.composite {
+                    None => HashMap::new(),
+                    Some(composite) => {
+                        let monitors = status::variables(composite.tenant.as_deref());
+                        match composite.expression.is_true(&monitors, &self.variables) {
+                            Ok(true) => monitors,
+                            Ok(false) => return Ok(None),
+                            Err(err) => {
+                                warn!("[{}] {err}", self.name);
+                                return Ok(None);
+                            }
+                        }
+                    }
+                },
+            },
+        };
+
+        if let Some(filter) = self
+            .filters
+            .iter()
+            .find(|filter| !filter.passes(&temp_variables))
+        {
+            self.trace(Trace::Filtered(format!("filter.{}", filter.variable)));
+            return Ok(None);
+        }
+        if let Some(script) = &self.filter_script {
+            let passes = match script.run(&temp_variables, &self.globals).await {
+                Ok(Value::Boolean(passes)) => passes,
+                Ok(result) => {
+                    warn!(
+                        "[{}] `filter` returned {result} instead of a boolean.",
+                        self.name
+                    );
+                    false
+                }
+                Err(err) => {
+                    warn!("[{}] `filter`: {err:#}", self.name);
+                    false
+                }
+            };
+            self.variables = self.globals.lock().unwrap().values.clone();
+            if !passes {
+                self.trace(Trace::Filtered("filter".to_owned()));
+                return Ok(None);
+            }
+        }
+
+        if let Some(unique) = &mut self.unique {
+            if let Some(var) = temp_variables
+                .get(&unique.variable_name)
+                .and_then(|v: &Value| v.as_str())
+            {
+                if unique.recorded_values.contains(var) {
+                    return Ok(None);
+                } else {
+                    unique.recorded_values.insert(var.to_owned());
+                    if let Err(err) = self.store_unique_values().await {
+                        let err = Error::new(
+                            ErrorKind::CacheWrite,
+                            format!("Failed to store unique values: {err}"),
+                        );
+                        warn!("[{}] {err}", self.name);
+                    }
+                }
+            }
+        }
+
+        // TODO: get
+
+        if let Some(condition) = &self.condition {
+            match condition.is_true(&temp_variables, &self.variables) {
+                Ok(true) => {}
+                Ok(false) => return Ok(None),
+                Err(err) => {
+                    warn!("[{}] {err}", self.name);
+                    return Ok(None);
+                }
+            }
+        }
+
+        let key = match &self.threshold {
+            None => String::new(),
+            Some(_) => self.key(&temp_variables),
+        };
+        if let Some(threshold) = &mut self.threshold {
+            let now = self.clock.now();
+            let duration = threshold.duration;
+            // Forget keys that haven't had an event within the duration.
+            threshold.histories.retain(|_, history| {
+                history
+                    .back()
+                    .is_some_and(|last| now.duration_since(*last) <= duration)
+            });
+            let history = threshold.histories.entry(key).or_default();
+            history.push_back(now);
+            if history.len() > threshold.threshold.get() {
+                history.pop_front();
+            }
+            if history.len() < threshold.threshold.get() {
+                self.trace(Trace::Threshold(false));
+                return Ok(None);
+            }
+
+            if history
+                .front()
+                .is_some_and(|first| now.duration_since(*first) > duration)
+            {
+                info!("Didn't hit it yet");
+                self.trace(Trace::Threshold(false));
+                return Ok(None);
+            }
+            self.trace(Trace::Threshold(true));
+        }
+
+        Ok(Some(temp_variables))
+    }
+
+    /// Returns the variables set by `match_log` if the line isn't ignored and matches (or there is
+    /// no `match_log`).
+    #[instrument(name = "match", skip_all, fields(monitor = %self.name, pattern_index = field::Empty))]
+    fn match_line(&self, line: &str) -> Option<HashMap<String, Value>> {
+        if self.ignore_regexes.iter().any(|regex| regex.is_match(line)) {
+            return None;
+        }
+
+        let mut temp_variables = HashMap::from([("line".to_owned(), line.into())]);
+        if let Some(match_log) = &self.match_log {
+            // Only the first matching pattern is used, so each line fires at most once.
+            let pattern_index = match_log.set.matches(line).into_iter().next()?;
+            Span::current().record("pattern_index", pattern_index);
+            let pattern = &match_log.patterns[pattern_index];
+            let regex = &pattern.regex;
+            let captures = regex.captures(line)?;
+            debug!("[{}] Match found.", self.name);
+            self.trace(Trace::Matched(pattern_index));
+            if let Some(matched) = captures.get(0) {
+                temp_variables.insert("match".into(), matched.as_str().into());
+                let span = vec![matched.start() as i64, matched.end() as i64];
+                temp_variables.insert("match_span".into(), span.into());
+            }
+            // Every group by position, including named ones. Groups that didn't participate are empty.
+            let groups = captures
+                .iter()
+                .skip(1)
+                .map(|group| group.map_or("", |group| group.as_str()).into())
+                .collect::<Vec<Value>>();
+            temp_variables.insert("groups".into(), groups.into());
+            temp_variables.insert("pattern_index".into(), (pattern_index as i64).into());
+            if let Some(pattern_name) = &pattern.name {
+                temp_variables.insert("pattern_name".into(), pattern_name.as_str().into());
+            }
+            for capture_name in regex.capture_names().flatten() {
+                if let Some(capture) = captures.name(capture_name) {
+                    temp_variables.insert(capture_name.to_owned(), capture.as_str().into());
+                } else {
+                    warn!(
+                        "[{}] Capture group `{capture_name}` was not found.",
+                        self.name
+                    );
+                }
+            }
+        }
+        Some(temp_variables)
+    }
+
+    /// Returns whether `resolve` marks the event as resolving an earlier problem.
+    fn resolves(&self, temp_variables: &HashMap<String, Value>) -> bool {
+        let Some(resolve) = &self.resolve else {
+            return false;
+        };
+        resolve
+            .is_true(temp_variables, &self.variables)
+            .unwrap_or_else(|err| {
+                warn!("[{}] {err}", self.name);
+                false
+            })
+    }
+
+    /// Returns the cooldown, which may have been overridden through the API.
+    fn cooldown(&self) -> Option<Duration> {
+        overrides::cooldown(&self.name).unwrap_or(self.cooldown)
+    }
+
+    fn cooling_down(&self, key: &str) -> bool {
+        let Some(cooldown) = self.cooldown() else {
+            return false;
+        };
+        self.last_action_times
+            .get(key)
+            .is_some_and(|time| self.clock.now().duration_since(*time) < cooldown)
+    }
+
+    /// Renders `key`, which separates the cooldown and threshold of each value (e.g. of each IP).
+    /// Without `key`, every event has the same key.
+    fn key(&self, temp_variables: &HashMap<String, Value>) -> String {
+        if !self.keyed {
+            return String::new();
+        }
+        let context = template::context(temp_variables, &self.variables);
+        template::render(&self.templates, "key", &context).unwrap_or_else(|err| {
+            warn!("[{}] Failed to render `key`: {err}", self.name);
+            String::new()
+        })
+    }
+
+    async fn store_unique_values(&mut self) -> Result<()> {
+        let variables = match &self.unique {
+            None => panic!(),
+            Some(values) => &values.recorded_values,
+        };
+        let contents = variables
+            .iter()
+            .map(|variable| format!("{variable}\n"))
+            .collect::<String>();
+        UNIQUE_FORMAT.write(&self.name, &contents).await
+    }
+
+    #[instrument(name = "actions", skip_all, fields(monitor = %self.name))]
+    async fn run_actions(&mut self, mut temp_variables: HashMap<String, Value>) -> Result<()> {
+        if let Some(cooldown) = self.cooldown() {
+            let now = self.clock.now();
+            self.last_action_times
+                .retain(|_, time| now.duration_since(*time) < cooldown);
+            self.last_action_times
+                .insert(self.key(&temp_variables), now);
+        }
+
+        self.trace(Trace::Fired(temp_variables.clone()));
+
+        if self.dry_run {
+            let temp_variables =
+                privacy::redact(&temp_variables, &self.privacy, Sink::Notification);
+            let variables = temp_variables
+                .into_iter()
+                .map(|(var, val)| (var, value_to_string(val)))
+                .collect::<BTreeMap<String, String>>();
+            info!(
+                "[{}] Dry run; not performing actions. Variables: {variables:?}",
+                self.name
+            );
+            return Ok(());
+        }
+
+        if let Some(script) = &self.script {
+            // Scripts also run during replays, since the other actions may use their variables.
+            self.trace(Trace::Step("script".to_owned()));
+            match script.run(&temp_variables, &self.globals).await {
+                Ok(result) => script::set_variables(result, &mut temp_variables),
+                Err(err) => warn!("[{}] `script`: {err:#}", self.name),
+            }
+            self.variables = self.globals.lock().unwrap().values.clone();
+        }
+
+        if !self.assignments.is_empty() {
+            if let Err(err) = variables::assign(
+                &self.assignments,
+                &temp_variables,
+                &self.globals,
+                &self.clock,
+            ) {
+                warn!("[{}] {err}", self.name);
+            }
+            self.variables = self.globals.lock().unwrap().values.clone();
+        }
+
+        if let Some(event) = &self.emit {
+            bus::emit(&self.name, event, temp_variables.clone());
+        }
+
+        if let Some(exec) = &self.exec {
+            let temp_variables = privacy::redact(&temp_variables, &self.privacy, Sink::Exec);
+            let context = template::context(&temp_variables, &self.variables);
+            let command = exec_command(
+                &self.templates,
+                "exec",
+                exec,
+                &self.shell,
+                &self.exec_options,
+                &context,
+                &temp_variables,
+            )?;
+            let stdin = match self.exec_options.stdin_json {
+                true => Some(stdin_json(&self.name, &temp_variables, &self.variables)?),
+                false => None,
+            };
+            match &self.trace_tx {
+                Some(trace_tx) => {
+                    let _ = trace_tx.send(Trace::Exec(command_line(&command)));
+                }
+                None => self.spawn_exec(command, stdin),
+            }
+        }
+
+        if let (Some(ban), None) = (&mut self.ban, &self.trace_tx) {
+            ban.record(&self.name, &temp_variables).await;
+        }
+
+        if let Some(action) = &self.systemd {
+            let redacted = privacy::redact(&temp_variables, &self.privacy, Sink::Exec);
+            let context = template::context(&redacted, &self.variables);
+            let unit = template::render(&self.templates, "systemd", &context)?;
+            let result = match &self.trace_tx {
+                Some(trace_tx) => {
+                    let _ =
+                        trace_tx.send(Trace::Step(format!("systemd: {} {unit}", action.operation)));
+                    "done".to_owned()
+                }
+                None => systemd::manage(action.operation, &unit)
+                    .await
+                    .unwrap_or_else(|err| format!("{err:#}")),
+            };
+            if result != "done" {
+                warn!(
+                    "[{}] Failed to {} {unit}: {result}",
+                    self.name, action.operation
+                );
+            }
+            temp_variables.insert("systemd_result".into(), result.into());
+        }
+
+        if let Some(action) = &self.docker {
+            let redacted = privacy::redact(&temp_variables, &self.privacy, Sink::Exec);
+            let context = template::context(&redacted, &self.variables);
+            let outcome = match &self.trace_tx {
+                Some(trace_tx) => {
+                    let description =
+                        docker::describe(&self.templates, "docker", action, &context)?;
+                    let _ = trace_tx.send(Trace::Step(description));
+                    docker::Outcome::default()
+                }
+                None => docker::perform(&self.templates, "docker", action, &context).await?,
+            };
+            if let Some(err) = &outcome.error {
+                warn!("[{}] docker: {err}", self.name);
+            }
+            temp_variables.extend(outcome.variables());
+        }
+
+        if let Some(notification) = &self.notify {
+            let resolved = self.resolves(&temp_variables);
+            let highlight = self.highlight(&temp_variables);
+            let temp_variables =
+                privacy::redact(&temp_variables, &self.privacy, Sink::Notification);
+            let context = template::context(&temp_variables, &self.variables);
+            let mut notif =
+                self.render_notification(notification, &context, temp_variables, highlight)?;
+            notif.resolved = resolved;
+            if silence::suppress(&self.name) {
+                debug!("[{}] Silenced; not notifying.", self.name);
+            } else {
+                self.aggregator_tx.send(notif).await?;
+            }
+        }
+
+        if let Some(pipeline) = &self.pipeline {
+            let env = pipeline::Env {
+                monitor: self.name.clone(),
+                shell: self.shell.clone(),
+                exec_options: self.exec_options.clone(),
+                privacy: self.privacy.clone(),
+                globals: self.globals.clone(),
+                clock: self.clock.clone(),
+                notify_tx: self.aggregator_tx.clone(),
+                trace_tx: self.trace_tx.clone(),
+            };
+            let variables = temp_variables;
+            match &self.trace_tx {
+                // Replays run the steps inline so that they're traced with the line.
+                Some(_) => pipeline.run(&env, variables).await,
+                None => {
+                    let pipeline = pipeline.clone();
+                    let run = async move { pipeline.run(&env, variables).await };
+                    tokio::spawn(run.in_current_span());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs `exec` in the background once `max_concurrent_execs` allows it, retrying it up to
+    /// `exec_retries` times, and tracking whether it keeps failing.
+    fn spawn_exec(&self, command: Command, stdin: Option<Vec<u8>>) {
+        let name = self.name.clone();
+        let exec_failures = self.exec_failures.clone();
+        let retries = self.exec_retries;
+        let timeout = self.exec_options.timeout;
+        let clock = self.clock.clone();
+        let notify_tx = self.aggregator_tx.clone();
+        let notification = self.exec_failure_notification();
+        // The command may run after the event is handled, but its span still belongs to it.
+        let span = Span::current();
+        let submitted = self.exec_limit.submit(command, move |mut command| {
+            async move {
+                if retries > 0 {
+                    // The errors of the last attempt are included in the notification.
+                    command.stderr(Stdio::piped());
+                }
+                let mut attempt = 0;
+                let (exit_status, stderr) = loop {
+                    let Err(failure) = run_exec(&name, &mut command, stdin.clone(), timeout).await
+                    else {
+                        exec_failures.store(0, Ordering::Relaxed);
+                        return;
+                    };
+                    if attempt == retries {
+                        break failure;
+                    }
+                    let delay = EXEC_RETRY_DELAY * 2u32.pow(attempt);
+                    debug!(
+                        "[{name}] exec failed ({}); retrying in {delay:?}",
+                        failure.0
+                    );
+                    clock.sleep_until(clock.now() + delay).await;
+                    attempt += 1;
+                };
+
+                let failures = exec_failures.fetch_add(1, Ordering::Relaxed) + 1;
+                if failures >= EXEC_FAILURE_LIMIT {
+                    let title = format!("`exec` of monitor `{name}` keeps failing");
+                    let err = format!(
+                    "It failed {failures} times in a row; the last run ended with {exit_status}."
+                );
+                    meta::report(&name, Severity::Warning, title, &err);
+                }
+                if retries == 0 || silence::suppress(&name) {
+                    return;
+                }
+                let attempts = retries + 1;
+                let mut body =
+                    format!("It failed {attempts} times; the last run ended with {exit_status}.");
+                if !stderr.is_empty() {
+                    body += &format!("\n\n```\n{stderr}\n```");
+                }
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|duration| duration.as_secs())
+                    .unwrap_or_default();
+                let notification = Notification {
+                    body,
+                    timestamp,
+                    variables: HashMap::from([
+                        ("exit_status".into(), exit_status.into()),
+                        ("stderr".into(), stderr.into()),
+                    ]),
+                    ..notification
+                };
+                if let Err(err) = notify_tx.send(notification).await {
+                    warn!("[{name}] {err}");
+                }
+            }
+            .instrument(span)
+        });
+        if let Submitted::Skipped = submitted {
+            debug!(
+                "[{}] Skipped exec; `max_concurrent_execs` is reached.",
+                self.name
+            );
+            status::update(&self.name, |status| status.execs_skipped += 1);
+        }
+    }
+
+    /// Returns the notification sent when every attempt of `exec` failed, without its body.
+    fn exec_failure_notification(&self) -> Notification {
+        Notification {
+            r#type: self
+                .notify
+                .as_ref()
+                .map_or("default".to_owned(), |notify| notify.r#type.clone()),
+            monitor: self.name.clone(),
+            severity: self
+                .notify
+                .as_ref()
+                .map_or(Severity::Warning, |notify| notify.severity),
+            title: format!("`exec` of monitor `{}` failed", self.name),
+            body: String::new(),
+            timestamp: 0,
+            variables: HashMap::new(),
+            highlight: None,
+            resolved: false,
+        }
+    }
+
+    /// Returns the lines surrounding the match, with the values that may not be sent in
+    /// notifications hidden.
+    fn highlight(&self, temp_variables: &HashMap<String, Value>) -> Option<Highlight> {
+        let capture_names = temp_variables
+            .get("pattern_index")
+            .and_then(Value::as_integer)
+            .and_then(|i| self.match_log.as_ref()?.patterns.get(i as usize))
+            .map(|pattern| {
+                pattern
+                    .regex
+                    .capture_names()
+                    .flatten()
+                    .collect::<Vec<&str>>()
+            })
+            .unwrap_or_default();
+        let hide = privacy::hider(temp_variables, &self.privacy, Sink::Notification);
+        Highlight::from_variables(temp_variables, capture_names.into_iter(), hide)
+    }
+
+    fn render_notification(
+        &self,
+        notification: &Notification,
+        context: &Context,
+        variables: HashMap<String, Value>,
+        highlight: Option<Highlight>,
+    ) -> Result<Notification> {
+        let title = template::render(&self.templates, "title", context)?;
+        let body = template::render(&self.templates, "body", context)?;
+        if let Some(highlight) = &highlight {
+            let text = match std::io::stderr().is_terminal() {
+                true => highlight.ansi(),
+                false => highlight.plain(),
+            };
+            debug!("[{}] Matched:\n{text}", self.name);
+        }
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        Ok(Notification {
+            r#type: notification.r#type.clone(),
+            monitor: notification.monitor.clone(),
+            severity: notification.severity,
+            title,
+            body,
+            timestamp,
+            variables,
+            highlight,
+            resolved: false,
+        })
+    }
+}
+
+/// Registers the templates of each argument of `exec` under `name.<i>`. Shell scripts aren't
+/// templates; they get variables through the environment.
+pub fn add_exec_templates(templates: &mut Tera, name: &str, exec: &Exec) -> Result<()> {
+    if let Exec::Spawn(args) = exec {
+        for (i, arg) in args.iter().enumerate() {
+            template::add(templates, &format!("{name}.{i}"), arg)?;
+        }
+    }
+    Ok(())
+}
+
+/// Registers the templates of `exec_cwd` and `exec_env`, which every command shares.
+pub fn add_exec_option_templates(templates: &mut Tera, options: &ExecOptions) -> Result<()> {
+    if let Some(cwd) = &options.cwd {
+        template::add(templates, "exec_cwd", cwd)?;
+    }
+    for (key, value) in &options.env {
+        template::add(templates, &format!("exec_env.{key}"), value)?;
+    }
+    Ok(())
+}
+
+/// Renders the templates registered by `add_exec_templates` and `add_exec_option_templates` into
+/// a command, with the local variables set as environment variables.
+pub fn exec_command(
+    templates: &Tera,
+    name: &str,
+    exec: &Exec,
+    shell: &Shell,
+    options: &ExecOptions,
+    context: &Context,
+    variables: &HashMap<String, Value>,
+) -> Result<Command> {
+    let mut command = match exec {
+        Exec::Shell(script) => shell.command(script),
+        Exec::Spawn(args) => {
+            let args = (0..args.len())
+                .map(|i| template::render(templates, &format!("{name}.{i}"), context))
+                .collect::<Result<Vec<String>>>()?;
+            let mut command = Command::new(&args[0]);
+            command.args(&args[1..]);
+            command
+        }
+    };
+    for (var, val) in variables {
+        command.env(var, value_to_string(val.clone()));
+    }
+    // Also under fixed names, for regexes without named groups.
+    for (var, name) in [("match", "MATCH"), ("line", "LINE")] {
+        if let Some(val) = variables.get(var) {
+            command.env(name, value_to_string(val.clone()));
+        }
+    }
+    if let Some(Value::Array(groups)) = variables.get("groups") {
+        for (i, group) in groups.iter().enumerate() {
+            command.env(format!("MATCH_{}", i + 1), value_to_string(group.clone()));
+        }
+    }
+    for (key, _) in &options.env {
+        command.env(
+            key,
+            template::render(templates, &format!("exec_env.{key}"), context)?,
+        );
+    }
+    if options.cwd.is_some() {
+        command.current_dir(template::render(templates, "exec_cwd", context)?);
+    }
+    if options.stdin_json {
+        command.stdin(Stdio::piped());
+    }
+    if options.timeout.is_some() {
+        // Dropping the child when it times out kills it.
+        command.kill_on_drop(true);
+    }
+    options.credentials.apply(&mut command);
+    Ok(command)
+}
+
+/// Returns the JSON document written to the stdin of commands with `exec_stdin = "json"`.
+pub fn stdin_json(
+    monitor: &str,
+    variables: &HashMap<String, Value>,
+    global: &HashMap<String, Value>,
+) -> Result<Vec<u8>> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let document = serde_json::json!({
+        "monitor": monitor,
+        "timestamp": timestamp,
+        "match": variables.get("match"),
+        "line": variables.get("line"),
+        "variables": variables,
+        "globals": global,
+    });
+    Ok(serde_json::to_vec(&document)?)
+}
+
+/// Writes `input` to the stdin of a child spawned with a piped stdin, then closes it.
+pub fn write_stdin(child: &mut Child, input: Vec<u8>) {
+    let Some(mut stdin) = child.stdin.take() else {
+        return;
+    };
+    // Commands that don't read their input close the pipe early, which isn't an error.
+    tokio::spawn(async move {
+        let _ = stdin.write_all(&input).await;
+    });
+}
+
+/// Waits for a child to exit and collects its output, killing it if it runs longer than `timeout`.
+pub async fn wait_with_timeout(child: Child, timeout: Option<Duration>) -> io::Result<Output> {
+    let Some(timeout) = timeout else {
+        return child.wait_with_output().await;
+    };
+    tokio::time::timeout(timeout, child.wait_with_output())
+        .await
+        .unwrap_or_else(|_| {
+            let message = format!("timed out after {timeout:?}");
+            Err(io::Error::new(io::ErrorKind::TimedOut, message))
+        })
+}
+
+/// Runs a command once and waits for it. If it fails, returns how it ended (e.g. `exit status: 1`)
+/// and the end of its stderr, if it was piped.
+#[instrument(name = "exec", skip_all, fields(monitor = name, status = field::Empty))]
+async fn run_exec(
+    name: &str,
+    command: &mut Command,
+    stdin: Option<Vec<u8>>,
+    timeout: Option<Duration>,
+) -> Result<(), (String, String)> {
+    status::update(name, |status| status.execs += 1);
+    let mut child = command.spawn().map_err(|err| {
+        status::update(name, |status| status.exec_failures += 1);
+        let err = Error::new(ErrorKind::ExecSpawn, format!("Failed to spawn exec: {err}"));
+        warn!("[{name}] {err}");
+        audit_exec(name, command, &err.to_string(), false);
+        (err.to_string(), String::new())
+    })?;
+    if let Some(stdin) = stdin {
+        write_stdin(&mut child, stdin);
+    }
+    let result = wait_with_timeout(child, timeout).await;
+    let ended = match &result {
+        Ok(output) => output.status.to_string(),
+        Err(err) => err.to_string(),
+    };
+    Span::current().record("status", &ended);
+    let success = result.as_ref().is_ok_and(|output| output.status.success());
+    audit_exec(name, command, &ended, success);
+    status::update(name, |status| {
+        status.last_exec = Some(ended);
+        if !success {
+            status.exec_failures += 1;
+        }
+    });
+    let output = result.map_err(|err| {
+        warn!("[{name}] exec failed: {err}");
+        (err.to_string(), String::new())
+    })?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stderr = stderr.trim_end();
+    if !stderr.is_empty() {
+        info!("[{name}] exec failed ({}): {stderr}", output.status);
+    }
+    // Only the end of the errors is kept, since that's usually where the cause is.
+    let start = stderr
+        .char_indices()
+        .rev()
+        .nth(STDERR_LIMIT - 1)
+        .map_or(0, |(i, _)| i);
+    Err((output.status.to_string(), stderr[start..].to_owned()))
+}
+
+/// Records a run of a command in the audit log, with how it ended (e.g. `exit status: 1`).
+pub fn audit_exec(name: &str, command: &Command, ended: &str, success: bool) {
+    if audit::enabled() {
+        let details = serde_json::json!({
+            "command": command_line(command),
+            "status": ended,
+            "success": success,
+        });
+        audit::record("exec", name, details);
+    }
+}
+
+/// Describes a command for `ramon replay`.
+pub fn command_line(command: &Command) -> String {
+    let command = command.as_std();
+    once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| format!("{arg:?}"))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
@@ -0,0 +1,109 @@
+//! The `Notifier` trait, which every channel that notifications are delivered through implements,
+//! and the registry of channels that `[[notify.<name>.channel]]` tables create by `type`.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        LazyLock, Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+use toml::{Table, Value};
+
+use crate::{
+    config::{self, Notification},
+    source::BoxFuture,
+};
+
+static DELIVERIES: AtomicU64 = AtomicU64::new(0);
+
+/// Creates a notifier from a `[[notify.<name>.channel]]` table, without its `type`. `path` is the
+/// path of the table, for errors.
+pub type Factory = fn(path: &str, table: Table) -> Result<Box<dyn Notifier>>;
+
+static REGISTRY: LazyLock<Mutex<HashMap<&'static str, Factory>>> =
+    LazyLock::new(|| Mutex::new(config::channel_factories().into_iter().collect()));
+
+/// A delivery of a notification, which may be attempted more than once.
+pub struct Delivery<'a> {
+    /// The name of the notification config.
+    pub name: &'a str,
+    /// The same for every attempt, which lets a receiver ignore retries of the same message.
+    pub id: &'a str,
+}
+
+/// A channel that notifications are delivered through, such as email or a webhook.
+pub trait Notifier: Send + Sync {
+    /// What the channel is called in the log, the audit log, and traces (e.g. `email`).
+    fn channel(&self) -> &'static str;
+
+    /// Returns whether `notification` is delivered through this channel at all.
+    fn accepts(&self, _notification: &Notification) -> bool {
+        true
+    }
+
+    /// Delivers a notification.
+    fn send<'a>(
+        &'a self,
+        notification: &'a Notification,
+        delivery: &'a Delivery,
+    ) -> BoxFuture<'a, Result<()>>;
+
+    /// Delivers aggregated notifications, where `digest` concatenates `queue`. By default, the
+    /// digest is sent like any other notification.
+    fn send_digest<'a>(
+        &'a self,
+        digest: &'a Notification,
+        _queue: &'a [Notification],
+        delivery: &'a Delivery,
+    ) -> BoxFuture<'a, Result<()>> {
+        self.send(digest, delivery)
+    }
+
+    /// Returns whether aggregated notifications are sent one at a time rather than as a digest.
+    fn splits_digests(&self) -> bool {
+        false
+    }
+
+    /// Returns whether a failed send is retried.
+    fn retries(&self) -> bool {
+        true
+    }
+}
+
+/// Lets `[[notify.<name>.channel]]` tables create notifiers whose `type` is `r#type`, replacing any
+/// channel of the same type.
+pub fn register(r#type: &'static str, factory: Factory) {
+    REGISTRY.lock().unwrap().insert(r#type, factory);
+}
+
+/// Creates the notifier that a `[[notify.<name>.channel]]` table at `path` describes.
+pub(crate) fn create(path: &str, mut table: Table) -> Result<Box<dyn Notifier>> {
+    let r#type = match table.remove("type") {
+        Some(Value::String(r#type)) => r#type,
+        Some(_) => return Err(anyhow!("{path}.type: must be a string")),
+        None => return Err(anyhow!("{path}.type: must be set")),
+    };
+    let factory = REGISTRY
+        .lock()
+        .unwrap()
+        .get(r#type.as_str())
+        .copied()
+        .ok_or_else(|| anyhow!("{path}.type: unknown channel {:?}", r#type))?;
+    factory(path, table)
+}
+
+/// Returns an ID for a new delivery.
+pub(crate) fn delivery_id() -> String {
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or_default();
+    format!(
+        "ramon{started}.{}",
+        DELIVERIES.fetch_add(1, Ordering::Relaxed)
+    )
+}
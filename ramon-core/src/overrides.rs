@@ -0,0 +1,180 @@
+//! Changes made to running monitors through the API (pausing them, overriding their cooldowns, and
+//! silencing them), which are saved to `api.overrides`, if it's set, and restored on startup.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    sync::{LazyLock, Mutex, OnceLock},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Sender;
+
+use crate::{monitor::Event, silence, status};
+
+static MONITORS: LazyLock<Mutex<HashMap<String, Overrides>>> = LazyLock::new(Default::default);
+/// Where overrides are saved.
+static PATH: OnceLock<PathBuf> = OnceLock::new();
+
+struct Overrides {
+    /// Receives lines injected through the API.
+    event_tx: Sender<Event>,
+    /// The cooldown set by the config.
+    configured_cooldown: Option<Duration>,
+    saved: Saved,
+}
+
+/// The overrides of a monitor, as saved to `api.overrides`.
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct Saved {
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    paused: bool,
+    /// Replaces the cooldown set by the config, in seconds. 0 disables it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cooldown: Option<u64>,
+    /// When the monitor's silence ends, in seconds since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    silenced_until: Option<u64>,
+}
+
+/// Lets the API control a monitor.
+pub fn register(name: &str, event_tx: Sender<Event>, cooldown: Option<Duration>) {
+    MONITORS.lock().unwrap().insert(
+        name.to_owned(),
+        Overrides {
+            event_tx,
+            configured_cooldown: cooldown,
+            saved: Saved::default(),
+        },
+    );
+}
+
+/// Returns whether `name` was paused, in which case it ignores every event.
+pub fn paused(name: &str) -> bool {
+    MONITORS
+        .lock()
+        .unwrap()
+        .get(name)
+        .is_some_and(|overrides| overrides.saved.paused)
+}
+
+/// Returns the cooldown of `name`, if it was overridden.
+pub fn cooldown(name: &str) -> Option<Option<Duration>> {
+    let monitors = MONITORS.lock().unwrap();
+    let cooldown = monitors.get(name)?.saved.cooldown?;
+    Some((cooldown > 0).then(|| Duration::from_secs(cooldown)))
+}
+
+/// Pauses or resumes the monitor `name`, saving the change.
+pub fn set_paused(name: &str, paused: bool) -> Result<()> {
+    update(name, |saved| saved.paused = paused)?;
+    status::update(name, |status| status.paused = paused);
+    info!("[{name}] {}", if paused { "Paused" } else { "Resumed" });
+    Ok(())
+}
+
+/// Overrides the cooldown of `name`, or restores the configured one if `cooldown` is `None`.
+pub fn set_cooldown(name: &str, cooldown: Option<Duration>) -> Result<()> {
+    update(name, |saved| {
+        saved.cooldown = cooldown.map(|cooldown| cooldown.as_secs())
+    })?;
+    match cooldown {
+        None => info!("[{name}] Restored the configured cooldown"),
+        Some(cooldown) => info!("[{name}] Set the cooldown to {cooldown:?}"),
+    }
+    Ok(())
+}
+
+/// Returns the sender that injects lines into `name`.
+pub fn sender(name: &str) -> Result<Sender<Event>> {
+    MONITORS
+        .lock()
+        .unwrap()
+        .get(name)
+        .map(|overrides| overrides.event_tx.clone())
+        .ok_or_else(|| anyhow!("Unknown monitor `{name}`"))
+}
+
+/// Returns the cooldown in effect for each monitor, for listing monitors.
+pub fn cooldowns() -> BTreeMap<String, Option<Duration>> {
+    MONITORS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(name, overrides)| {
+            let cooldown = match overrides.saved.cooldown {
+                None => overrides.configured_cooldown,
+                Some(cooldown) => (cooldown > 0).then(|| Duration::from_secs(cooldown)),
+            };
+            (name.clone(), cooldown)
+        })
+        .collect()
+}
+
+fn update(name: &str, f: impl FnOnce(&mut Saved)) -> Result<()> {
+    let mut monitors = MONITORS.lock().unwrap();
+    let overrides = monitors
+        .get_mut(name)
+        .ok_or_else(|| anyhow!("Unknown monitor `{name}`"))?;
+    f(&mut overrides.saved);
+    Ok(())
+}
+
+/// Restores the overrides saved to `path` for the registered monitors, and saves later overrides
+/// there. Overrides of monitors that no longer exist are dropped.
+pub fn load(path: PathBuf) -> Result<()> {
+    let saved: BTreeMap<String, Saved> = match std::fs::read_to_string(&path) {
+        Ok(contents) => serde_json::from_str(&contents)
+            .map_err(|err| anyhow!("Failed to parse overrides {path:?}: {err}"))?,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+        Err(err) => return Err(anyhow!("Failed to read overrides {path:?}: {err}")),
+    };
+    let now = status::now();
+    for (name, saved) in saved {
+        if update(&name, |overrides| *overrides = saved.clone()).is_err() {
+            warn!("Dropping the overrides of unknown monitor `{name}`");
+            continue;
+        }
+        if saved.paused {
+            status::update(&name, |status| status.paused = true);
+            info!("[{name}] Paused by overrides");
+        }
+        if let Some(until) = saved.silenced_until.filter(|until| *until > now) {
+            if let Err(err) = silence::silence(&name, Duration::from_secs(until - now)) {
+                warn!("[{name}] Failed to restore silence: {err}");
+            }
+        }
+    }
+    let _ = PATH.set(path);
+    Ok(())
+}
+
+/// Saves the overrides of every monitor, if `api.overrides` is set.
+pub fn save() {
+    let Some(path) = PATH.get() else {
+        return;
+    };
+    let silences = status::snapshot().monitors;
+    let saved = MONITORS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(name, overrides)| {
+            let mut saved = overrides.saved.clone();
+            saved.silenced_until = silences.get(name).and_then(|status| status.silenced_until);
+            (name.clone(), saved)
+        })
+        .filter(|(_, saved)| {
+            saved.paused || saved.cooldown.is_some() || saved.silenced_until.is_some()
+        })
+        .collect::<BTreeMap<String, Saved>>();
+    let res = serde_json::to_string_pretty(&saved)
+        .map_err(anyhow::Error::from)
+        .and_then(|contents| Ok(std::fs::write(path, contents + "\n")?));
+    if let Err(err) = res {
+        warn!("Failed to save overrides to {path:?}: {err}");
+    }
+}
@@ -0,0 +1,99 @@
+//! `match_start`, `match_end`, and `timeout`, which fire when an operation doesn't end in time.
+
+use std::{collections::HashMap, time::Duration};
+
+use regex::{Captures, Regex};
+use tokio::time::Instant;
+use toml::Value;
+
+use crate::clock::SharedClock;
+
+/// `match_start`, `match_end`, and `timeout`.
+pub struct PairConfig {
+    /// Matches the line that starts an operation.
+    pub start: Regex,
+    /// Matches the line that ends an operation.
+    pub end: Regex,
+    /// How long an operation may take before the monitor fires.
+    pub timeout: Duration,
+}
+
+/// Tracks operations whose start was logged, so the monitor can fire for those whose end isn't
+/// logged within the timeout.
+pub struct Pairing {
+    config: PairConfig,
+    /// The variables of the start line of each pending operation, by key.
+    pending: HashMap<String, (Instant, HashMap<String, Value>)>,
+    clock: SharedClock,
+}
+
+/// Whether a line started or ended an operation.
+pub enum Boundary {
+    /// The named capture groups of `match_start`, and the line.
+    Start(HashMap<String, Value>),
+    /// The named capture groups of `match_end`.
+    End(HashMap<String, Value>),
+}
+
+impl Pairing {
+    /// Tracks operations on `clock`.
+    pub fn new(config: PairConfig, clock: SharedClock) -> Self {
+        Self {
+            config,
+            pending: HashMap::new(),
+            clock,
+        }
+    }
+
+    /// Returns the named capture groups of `match_end` or `match_start` if either matches the
+    /// line, checking `match_end` first.
+    pub fn boundary(&self, line: &str) -> Option<Boundary> {
+        if let Some(captures) = self.config.end.captures(line) {
+            return Some(Boundary::End(variables(&self.config.end, &captures)));
+        }
+        let captures = self.config.start.captures(line)?;
+        let mut variables = variables(&self.config.start, &captures);
+        variables.insert("line".into(), line.into());
+        Some(Boundary::Start(variables))
+    }
+
+    /// Starts waiting for the end of the operation with this key, unless it's already pending.
+    pub fn start(&mut self, key: String, variables: HashMap<String, Value>) {
+        let deadline = self.clock.now() + self.config.timeout;
+        self.pending.entry(key).or_insert((deadline, variables));
+    }
+
+    /// Returns whether the operation with this key was pending.
+    pub fn end(&mut self, key: &str) -> bool {
+        self.pending.remove(key).is_some()
+    }
+
+    /// Returns when the next pending operation times out.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.pending.values().map(|(deadline, _)| *deadline).min()
+    }
+
+    /// Removes the operations that timed out, returning the variables of their start lines.
+    pub fn expire(&mut self) -> Vec<HashMap<String, Value>> {
+        let now = self.clock.now();
+        let expired = self
+            .pending
+            .iter()
+            .filter(|(_, (deadline, _))| *deadline <= now)
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<String>>();
+        expired
+            .into_iter()
+            .filter_map(|key| self.pending.remove(&key))
+            .map(|(_, variables)| variables)
+            .collect()
+    }
+}
+
+fn variables(regex: &Regex, captures: &Captures) -> HashMap<String, Value> {
+    regex
+        .capture_names()
+        .flatten()
+        .filter_map(|name| Some((name.to_owned(), captures.name(name)?.as_str().into())))
+        .collect()
+}
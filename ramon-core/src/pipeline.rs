@@ -0,0 +1,325 @@
+//! Ordered lists of actions, for `actions`.
+
+use std::{
+    collections::HashMap,
+    iter::once,
+    process::Stdio,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Result};
+use log::{debug, info, warn};
+use reqwest::Client;
+use tera::Tera;
+use tokio::sync::mpsc::{Sender, UnboundedSender};
+use toml::Value;
+use tracing::instrument;
+
+use crate::{
+    clock::SharedClock,
+    config::{Exec, ExecOptions, Notification},
+    docker::{self, DockerAction},
+    error::{Error, ErrorKind},
+    exec_limit,
+    monitor::{self, Trace},
+    platform::Shell,
+    privacy::{self, PrivacyLevel, Sink},
+    scenario::{self, StepConfig},
+    script::{self, Script},
+    silence, status,
+    systemd::{self, UnitAction},
+    template,
+    variables::{self, Assignment, Globals},
+};
+
+/// What a step of `actions` does.
+pub enum Action {
+    /// Runs a command.
+    Exec(Exec),
+    /// A notification whose title and body are templates.
+    Notify(Notification),
+    /// Changes global variables.
+    Set(Vec<Assignment>),
+    /// Sends an HTTP request.
+    Http(StepConfig),
+    /// A script whose returned map is set as variables.
+    Script(Script),
+    /// Starts, stops, or restarts a systemd unit.
+    Systemd(UnitAction),
+    /// Acts on Docker containers.
+    Docker(DockerAction),
+    /// Waits before the next step.
+    Sleep(Duration),
+}
+
+/// A step of `actions`.
+pub struct Step {
+    /// What the step does.
+    pub action: Action,
+    /// Whether the following steps run if this one fails.
+    pub continue_on_error: bool,
+}
+
+/// What the steps need from the monitor that runs them.
+pub struct Env {
+    /// The name of the monitor.
+    pub monitor: String,
+    /// The shell that runs commands given as strings.
+    pub shell: Shell,
+    /// How commands are run.
+    pub exec_options: ExecOptions,
+    /// How each variable is redacted, by its name.
+    pub privacy: HashMap<String, PrivacyLevel>,
+    /// The global variables of the monitor's tenant.
+    pub globals: Globals,
+    /// Times sleeps.
+    pub clock: SharedClock,
+    /// Sends notifications to the monitor's notification config.
+    pub notify_tx: Sender<Notification>,
+    /// Set by `ramon replay`, which prints the commands and requests instead of running them, and
+    /// skips sleeps.
+    pub trace_tx: Option<UnboundedSender<Trace>>,
+}
+
+/// The steps of `actions`, with their templates parsed.
+pub struct Pipeline {
+    steps: Vec<Step>,
+    templates: Tera,
+    client: Client,
+}
+
+impl Pipeline {
+    /// Parses the templates of each step, and those of `exec_options`.
+    pub fn new(steps: Vec<Step>, exec_options: &ExecOptions) -> Result<Self> {
+        let mut templates = template::new_engine();
+        monitor::add_exec_option_templates(&mut templates, exec_options)?;
+        for (i, step) in steps.iter().enumerate() {
+            match &step.action {
+                Action::Exec(exec) => {
+                    monitor::add_exec_templates(&mut templates, &format!("{i}.exec"), exec)?
+                }
+                Action::Notify(notification) => {
+                    template::add(&mut templates, &format!("{i}.title"), &notification.title)?;
+                    template::add(&mut templates, &format!("{i}.body"), &notification.body)?;
+                }
+                Action::Http(request) => {
+                    scenario::add_templates(&mut templates, &i.to_string(), request)?
+                }
+                Action::Systemd(action) => {
+                    template::add(&mut templates, &format!("{i}.systemd"), &action.unit)?
+                }
+                Action::Docker(action) => {
+                    docker::add_templates(&mut templates, &format!("{i}.docker"), action)?
+                }
+                Action::Set(_) | Action::Script(_) | Action::Sleep(_) => {}
+            }
+        }
+        Ok(Self {
+            steps,
+            templates,
+            client: Client::builder().build()?,
+        })
+    }
+
+    /// Runs each step in order, until one fails without `continue_on_error`. The variables set by
+    /// a step are seen by the following steps.
+    pub async fn run(&self, env: &Env, mut variables: HashMap<String, Value>) {
+        for (i, step) in self.steps.iter().enumerate() {
+            let Err(err) = self.run_step(env, i, &step.action, &mut variables).await else {
+                continue;
+            };
+            let monitor = &env.monitor;
+            if !step.continue_on_error {
+                warn!("[{monitor}] Step {} of `actions` failed: {err:#}", i + 1);
+                return;
+            }
+            info!(
+                "[{monitor}] Step {} of `actions` failed, continuing: {err:#}",
+                i + 1
+            );
+        }
+    }
+
+    #[instrument(name = "step", skip_all, fields(monitor = %env.monitor, step = i + 1))]
+    async fn run_step(
+        &self,
+        env: &Env,
+        i: usize,
+        action: &Action,
+        variables: &mut HashMap<String, Value>,
+    ) -> Result<()> {
+        let global = env.globals.lock().unwrap().values.clone();
+        match action {
+            Action::Exec(exec) => {
+                let redacted = privacy::redact(variables, &env.privacy, Sink::Exec);
+                let context = template::context(&redacted, &global);
+                let name = format!("{i}.exec");
+                let mut command = monitor::exec_command(
+                    &self.templates,
+                    &name,
+                    exec,
+                    &env.shell,
+                    &env.exec_options,
+                    &context,
+                    &redacted,
+                )?;
+                if let Some(trace_tx) = &env.trace_tx {
+                    let _ = trace_tx.send(Trace::Exec(monitor::command_line(&command)));
+                    variables.insert("status".into(), 0.into());
+                    variables.insert("output".into(), "".into());
+                    return Ok(());
+                }
+
+                let stdin = match env.exec_options.stdin_json {
+                    true => Some(monitor::stdin_json(&env.monitor, &redacted, &global)?),
+                    false => {
+                        command.stdin(Stdio::null());
+                        None
+                    }
+                };
+
+                let _process = exec_limit::process_permit().await;
+                status::update(&env.monitor, |status| status.execs += 1);
+                let mut child = command
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::inherit())
+                    .spawn()
+                    .map_err(|err| {
+                        status::update(&env.monitor, |status| status.exec_failures += 1);
+                        Error::new(ErrorKind::ExecSpawn, format!("Failed to spawn exec: {err}"))
+                    })
+                    .inspect_err(|err| {
+                        monitor::audit_exec(&env.monitor, &command, &err.to_string(), false)
+                    })?;
+                if let Some(stdin) = stdin {
+                    monitor::write_stdin(&mut child, stdin);
+                }
+                let output = monitor::wait_with_timeout(child, env.exec_options.timeout)
+                    .await
+                    .inspect_err(|err| {
+                        monitor::audit_exec(&env.monitor, &command, &err.to_string(), false)
+                    })?;
+                monitor::audit_exec(
+                    &env.monitor,
+                    &command,
+                    &output.status.to_string(),
+                    output.status.success(),
+                );
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                variables.insert(
+                    "status".into(),
+                    output.status.code().map_or(-1, i64::from).into(),
+                );
+                variables.insert("output".into(), stdout.trim_end().into());
+                if !output.status.success() {
+                    status::update(&env.monitor, |status| status.exec_failures += 1);
+                    bail!("exec failed ({})", output.status);
+                }
+            }
+            Action::Notify(notification) => {
+                let redacted = privacy::redact(variables, &env.privacy, Sink::Notification);
+                let context = template::context(&redacted, &global);
+                let title = template::render(&self.templates, &format!("{i}.title"), &context)?;
+                let body = template::render(&self.templates, &format!("{i}.body"), &context)?;
+                if silence::suppress(&env.monitor) {
+                    debug!("[{}] Silenced; not notifying.", env.monitor);
+                    return Ok(());
+                }
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|duration| duration.as_secs())
+                    .unwrap_or_default();
+                env.notify_tx
+                    .send(Notification {
+                        title,
+                        body,
+                        timestamp,
+                        variables: redacted,
+                        ..notification.clone()
+                    })
+                    .await?;
+            }
+            Action::Set(assignments) => {
+                variables::assign(assignments, variables, &env.globals, &env.clock)?;
+            }
+            Action::Http(request) => {
+                if let Some(trace_tx) = &env.trace_tx {
+                    let description = format!("http: {} {}", request.method, request.url);
+                    let _ = trace_tx.send(Trace::Step(description));
+                    return Ok(());
+                }
+                let mut response = privacy::redact(variables, &env.privacy, Sink::Notification);
+                let result = scenario::send(
+                    &self.client,
+                    &self.templates,
+                    &i.to_string(),
+                    request,
+                    &mut response,
+                    &global,
+                )
+                .await;
+                // Only the variables set by the response are kept, so hidden values stay hidden.
+                let names = once("status").chain(
+                    request
+                        .extract
+                        .iter()
+                        .flat_map(|regex| regex.capture_names().flatten()),
+                );
+                for name in names {
+                    if let Some(value) = response.remove(name) {
+                        variables.insert(name.to_owned(), value);
+                    }
+                }
+                result?;
+            }
+            Action::Script(script) => {
+                if let Some(trace_tx) = &env.trace_tx {
+                    let _ = trace_tx.send(Trace::Step("script".to_owned()));
+                }
+                let result = script.run(variables, &env.globals).await?;
+                script::set_variables(result, variables);
+            }
+            Action::Systemd(action) => {
+                let redacted = privacy::redact(variables, &env.privacy, Sink::Exec);
+                let context = template::context(&redacted, &global);
+                let unit = template::render(&self.templates, &format!("{i}.systemd"), &context)?;
+                if let Some(trace_tx) = &env.trace_tx {
+                    let description = format!("systemd: {} {unit}", action.operation);
+                    let _ = trace_tx.send(Trace::Step(description));
+                    variables.insert("systemd_result".into(), "done".into());
+                    return Ok(());
+                }
+                let result = systemd::manage(action.operation, &unit).await?;
+                variables.insert("systemd_result".into(), result.as_str().into());
+                if result != "done" {
+                    bail!("Failed to {} {unit}: {result}", action.operation);
+                }
+            }
+            Action::Docker(action) => {
+                let redacted = privacy::redact(variables, &env.privacy, Sink::Exec);
+                let context = template::context(&redacted, &global);
+                let prefix = format!("{i}.docker");
+                if let Some(trace_tx) = &env.trace_tx {
+                    let description = docker::describe(&self.templates, &prefix, action, &context)?;
+                    let _ = trace_tx.send(Trace::Step(description));
+                    variables.extend(docker::Outcome::default().variables());
+                    return Ok(());
+                }
+                let outcome = docker::perform(&self.templates, &prefix, action, &context).await?;
+                let error = outcome.error.clone();
+                variables.extend(outcome.variables());
+                if let Some(err) = error {
+                    bail!("{err}");
+                }
+            }
+            Action::Sleep(duration) => {
+                if let Some(trace_tx) = &env.trace_tx {
+                    let _ = trace_tx.send(Trace::Step(format!("sleep: {duration:?}")));
+                    return Ok(());
+                }
+                env.clock.sleep_until(env.clock.now() + *duration).await;
+            }
+        }
+        Ok(())
+    }
+}
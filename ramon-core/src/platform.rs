@@ -0,0 +1,352 @@
+//! Paths and commands that differ between Unix and Windows.
+
+use std::{
+    fs::Metadata,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use anyhow::{bail, Result};
+use tokio::process::Command;
+
+/// The config file set by `--config`.
+static CONFIG_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// Uses `path` instead of the default config file.
+pub fn set_config_path(path: PathBuf) {
+    let _ = CONFIG_PATH.set(path);
+}
+
+/// Returns the path of the config file.
+pub fn config_path() -> PathBuf {
+    CONFIG_PATH
+        .get()
+        .cloned()
+        .unwrap_or_else(default_config_path)
+}
+
+#[cfg(not(windows))]
+fn default_config_path() -> PathBuf {
+    "/etc/ramon.toml".into()
+}
+
+#[cfg(windows)]
+fn default_config_path() -> PathBuf {
+    program_data().join("ramon.toml")
+}
+
+/// The directory of config files that are merged into ramon.toml, which is next to it.
+pub fn config_dir() -> PathBuf {
+    config_path().with_file_name("ramon.d")
+}
+
+/// Where state (e.g. unique values and uptime history) is persisted.
+#[cfg(not(windows))]
+pub fn cache_dir() -> PathBuf {
+    "/var/cache/ramon".into()
+}
+
+#[cfg(windows)]
+pub fn cache_dir() -> PathBuf {
+    program_data().join("cache")
+}
+
+#[cfg(windows)]
+fn program_data() -> PathBuf {
+    let program_data = std::env::var_os("ProgramData").unwrap_or_else(|| r"C:\ProgramData".into());
+    PathBuf::from(program_data).join("ramon")
+}
+
+/// Identifies a file regardless of its path, such as its device and inode numbers.
+pub type FileId = (u64, u64);
+
+/// Returns the device and inode numbers of a file.
+#[cfg(not(windows))]
+pub fn file_id(metadata: &Metadata) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some((metadata.dev(), metadata.ino()))
+}
+
+/// The volume serial number and file index are not available on stable Rust.
+#[cfg(windows)]
+pub fn file_id(_metadata: &Metadata) -> Option<FileId> {
+    None
+}
+
+/// Returns the type of the filesystem containing `path` (e.g. `ext4` or `nfs4`), if known.
+#[cfg(target_os = "linux")]
+pub fn filesystem_type(path: &Path) -> Option<String> {
+    let path = path.canonicalize().ok()?;
+    let mounts = std::fs::read_to_string("/proc/mounts").ok()?;
+    // The mount with the longest matching mount point contains the path.
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(' ');
+            let mount_point = fields.nth(1)?.replace("\\040", " ");
+            let fs_type = fields.next()?;
+            path.starts_with(&mount_point)
+                .then(|| (mount_point.len(), fs_type.to_owned()))
+        })
+        .max_by_key(|(len, _)| *len)
+        .map(|(_, fs_type)| fs_type)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn filesystem_type(_path: &Path) -> Option<String> {
+    None
+}
+
+/// Returns the current day of the week (0 for Monday) and the number of minutes since midnight, in
+/// the local time zone.
+#[cfg(not(windows))]
+pub fn local_time() -> (usize, u32) {
+    // SAFETY: `localtime_r` only writes to `tm`, and a zeroed `tm` is valid.
+    let tm = unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm = std::mem::zeroed::<libc::tm>();
+        libc::localtime_r(&now, &mut tm);
+        tm
+    };
+    // `tm_wday` counts from Sunday.
+    (
+        (tm.tm_wday as usize + 6) % 7,
+        (tm.tm_hour * 60 + tm.tm_min) as u32,
+    )
+}
+
+/// The local time zone isn't available without the Windows API, so UTC is used instead.
+#[cfg(windows)]
+pub fn local_time() -> (usize, u32) {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let days = secs / 86400;
+    // January 1, 1970 was a Thursday.
+    (((days + 3) % 7) as usize, (secs % 86400 / 60) as u32)
+}
+
+/// The program and arguments used to run shell commands, such as `["bash", "-c"]`. The script is
+/// passed as the final argument.
+#[derive(Clone)]
+pub struct Shell(Vec<String>);
+
+impl Default for Shell {
+    fn default() -> Self {
+        #[cfg(not(windows))]
+        let args = ["sh", "-c"];
+        #[cfg(windows)]
+        let args = ["cmd", "/C"];
+
+        Self(args.map(str::to_owned).to_vec())
+    }
+}
+
+impl Shell {
+    /// `args` must not be empty.
+    pub fn new(args: Vec<String>) -> Self {
+        Self(args)
+    }
+
+    /// Returns the program of the shell, such as `bash`.
+    pub fn program(&self) -> &str {
+        &self.0[0]
+    }
+
+    /// Returns a command that runs `script` with this shell.
+    pub fn command(&self, script: &str) -> Command {
+        let mut command = Command::new(&self.0[0]);
+        command.args(&self.0[1..]);
+        command.arg(script);
+        command
+    }
+}
+
+/// The user and group that a command runs as, or that ramon switches to. Unset IDs are inherited.
+#[derive(Clone, Copy, Default)]
+pub struct Credentials {
+    /// The user ID.
+    pub uid: Option<u32>,
+    /// The group ID.
+    pub gid: Option<u32>,
+}
+
+impl Credentials {
+    /// Looks up a user and group by name or ID. Without a group, the user's primary group is used.
+    #[cfg(not(windows))]
+    pub fn lookup(user: Option<&str>, group: Option<&str>) -> Result<Self> {
+        let (uid, primary_gid) = match user {
+            None => (None, None),
+            Some(user) => {
+                let (uid, gid) = users::lookup_user(user)?;
+                (Some(uid), gid)
+            }
+        };
+        let gid = match group {
+            None => primary_gid,
+            Some(group) => Some(users::lookup_group(group)?),
+        };
+        Ok(Self { uid, gid })
+    }
+
+    #[cfg(windows)]
+    pub fn lookup(_user: Option<&str>, _group: Option<&str>) -> Result<Self> {
+        bail!("Running as another user or group is only supported on Unix.")
+    }
+
+    /// Makes the command run as the user and group.
+    pub fn apply(&self, command: &mut Command) {
+        #[cfg(not(windows))]
+        {
+            // Supplementary groups are dropped along with root.
+            if let Some(gid) = self.gid {
+                command.gid(gid);
+            }
+            if let Some(uid) = self.uid {
+                command.uid(uid);
+            }
+        }
+        #[cfg(windows)]
+        let _ = command;
+    }
+}
+
+/// Switches ramon itself to the user and group, dropping supplementary groups. It can't switch
+/// back afterwards.
+#[cfg(not(windows))]
+pub fn drop_privileges(credentials: Credentials) -> Result<()> {
+    use std::io;
+
+    // SAFETY: these calls have no memory-safety preconditions; the group list is a valid array of
+    // the given length. glibc applies them to every thread of the process.
+    unsafe {
+        if let Some(gid) = credentials.gid {
+            if libc::setgroups(1, &gid) != 0 {
+                bail!("Failed to set groups: {}", io::Error::last_os_error());
+            }
+            if libc::setgid(gid) != 0 {
+                bail!("Failed to set group {gid}: {}", io::Error::last_os_error());
+            }
+        }
+        if let Some(uid) = credentials.uid {
+            if libc::setuid(uid) != 0 {
+                bail!("Failed to set user {uid}: {}", io::Error::last_os_error());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+pub fn drop_privileges(_credentials: Credentials) -> Result<()> {
+    bail!("Key `drop_privileges` is only supported on Unix.")
+}
+
+#[cfg(not(windows))]
+mod users {
+    use std::{ffi::CString, io, mem, ptr};
+
+    use anyhow::{anyhow, bail, Result};
+
+    const BUFFER_LEN: usize = 16 * 1024;
+
+    /// Returns the UID of a user name or numeric ID, and its primary group if the user exists.
+    pub fn lookup_user(user: &str) -> Result<(u32, Option<u32>)> {
+        let name = CString::new(user).map_err(|_| anyhow!("Invalid user {user:?}."))?;
+        // SAFETY: a zeroed `passwd` is valid.
+        let mut passwd = unsafe { mem::zeroed::<libc::passwd>() };
+        let mut buffer = vec![0; BUFFER_LEN];
+        let mut result = ptr::null_mut();
+        // SAFETY: the buffer outlives the call, and `passwd` is only read if `result` is set.
+        let code = unsafe {
+            match user.parse::<u32>() {
+                Ok(uid) => libc::getpwuid_r(
+                    uid,
+                    &mut passwd,
+                    buffer.as_mut_ptr(),
+                    buffer.len(),
+                    &mut result,
+                ),
+                Err(_) => libc::getpwnam_r(
+                    name.as_ptr(),
+                    &mut passwd,
+                    buffer.as_mut_ptr(),
+                    buffer.len(),
+                    &mut result,
+                ),
+            }
+        };
+        if !result.is_null() {
+            return Ok((passwd.pw_uid, Some(passwd.pw_gid)));
+        }
+        match user.parse::<u32>() {
+            // A numeric ID doesn't need an entry in /etc/passwd.
+            Ok(uid) => Ok((uid, None)),
+            Err(_) if code != 0 => bail!(
+                "Failed to look up user {user:?}: {}",
+                io::Error::from_raw_os_error(code)
+            ),
+            Err(_) => bail!("Unknown user {user:?}."),
+        }
+    }
+
+    /// Returns the GID of a group name or numeric ID.
+    pub fn lookup_group(group: &str) -> Result<u32> {
+        if let Ok(gid) = group.parse() {
+            return Ok(gid);
+        }
+        let name = CString::new(group).map_err(|_| anyhow!("Invalid group {group:?}."))?;
+        // SAFETY: a zeroed `group` is valid.
+        let mut entry = unsafe { mem::zeroed::<libc::group>() };
+        let mut buffer = vec![0; BUFFER_LEN];
+        let mut result = ptr::null_mut();
+        // SAFETY: the buffer outlives the call, and `entry` is only read if `result` is set.
+        let code = unsafe {
+            libc::getgrnam_r(
+                name.as_ptr(),
+                &mut entry,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            )
+        };
+        if !result.is_null() {
+            return Ok(entry.gr_gid);
+        }
+        match code {
+            0 => bail!("Unknown group {group:?}."),
+            code => bail!(
+                "Failed to look up group {group:?}: {}",
+                io::Error::from_raw_os_error(code)
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_shell() {
+        let command = Shell::default().command("echo \"$ip\"");
+        let command = command.as_std();
+        #[cfg(not(windows))]
+        let expected = ("sh", ["-c", "echo \"$ip\""]);
+        #[cfg(windows)]
+        let expected = ("cmd", ["/C", "echo \"$ip\""]);
+        assert_eq!(command.get_program(), expected.0);
+        assert_eq!(command.get_args().collect::<Vec<_>>(), expected.1);
+    }
+
+    #[test]
+    fn custom_shell() {
+        let shell = Shell::new(vec!["bash".into(), "-e".into(), "-c".into()]);
+        let command = shell.command("true");
+        let command = command.as_std();
+        assert_eq!(command.get_program(), "bash");
+        assert_eq!(command.get_args().collect::<Vec<_>>(), ["-e", "-c", "true"]);
+    }
+}
@@ -0,0 +1,209 @@
+//! Privacy levels of variables, which decide where their values may be sent.
+
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    str::FromStr,
+    sync::LazyLock,
+};
+
+use anyhow::{bail, Error, Result};
+use hmac::{Hmac, Mac};
+use log::warn;
+use sha2::Sha256;
+use toml::Value;
+
+use crate::platform;
+
+/// The key of the hashes that replace redacted values, so that a value can't be found by hashing
+/// every candidate (e.g. every IPv4 address). It's generated once per install, so that hashes
+/// stay comparable across restarts.
+static KEY: LazyLock<Vec<u8>> = LazyLock::new(load_key);
+
+/// Where the value of a variable may be sent.
+#[derive(Clone, Copy, PartialEq)]
+pub enum PrivacyLevel {
+    /// The value may appear anywhere.
+    Public,
+    /// The value may be passed to `exec`, but not included in notifications.
+    Internal,
+    /// The value is only used by conditions.
+    Secret,
+}
+
+/// Where variables are sent after leaving the monitor.
+#[derive(Clone, Copy)]
+pub enum Sink {
+    /// The environment and templates of commands.
+    Exec,
+    /// Notifications, and records like the audit log.
+    Notification,
+}
+
+impl PrivacyLevel {
+    fn allows(self, sink: Sink) -> bool {
+        match (self, sink) {
+            (PrivacyLevel::Public, _) => true,
+            (PrivacyLevel::Internal, Sink::Exec) => true,
+            (PrivacyLevel::Internal, Sink::Notification) => false,
+            (PrivacyLevel::Secret, _) => false,
+        }
+    }
+}
+
+impl FromStr for PrivacyLevel {
+    type Err = Error;
+
+    fn from_str(level: &str) -> Result<Self> {
+        match level {
+            "public" => Ok(PrivacyLevel::Public),
+            "internal" => Ok(PrivacyLevel::Internal),
+            "secret" => Ok(PrivacyLevel::Secret),
+            _ => bail!("Invalid privacy level {level:?}; expected public, internal, or secret."),
+        }
+    }
+}
+
+/// Replaces variables that may not be sent to `sink` with a keyed hash of their value.
+/// Occurrences of those values within other variables (e.g. context lines) are replaced as well.
+pub fn redact(
+    variables: &HashMap<String, Value>,
+    levels: &HashMap<String, PrivacyLevel>,
+    sink: Sink,
+) -> HashMap<String, Value> {
+    let mut hidden = Vec::new();
+    let mut redacted = variables.clone();
+    for (name, raw) in hidden_values(variables, levels, sink) {
+        let hash = hash(&raw);
+        redacted.insert(name.to_owned(), Value::String(hash.clone()));
+        if !raw.is_empty() {
+            hidden.push((raw, hash));
+        }
+    }
+
+    if !hidden.is_empty() {
+        for (name, value) in &mut redacted {
+            if levels.contains_key(name) {
+                continue;
+            }
+            replace(value, &hidden);
+        }
+    }
+
+    redacted
+}
+
+/// Returns a function that replaces the values of `variables` that may not be sent to `sink`
+/// within text, as `redact` does within other variables.
+pub fn hider(
+    variables: &HashMap<String, Value>,
+    levels: &HashMap<String, PrivacyLevel>,
+    sink: Sink,
+) -> impl Fn(&str) -> String {
+    let hidden = hidden_values(variables, levels, sink)
+        .filter(|(_, raw)| !raw.is_empty())
+        .map(|(_, raw)| {
+            let hash = hash(&raw);
+            (raw, hash)
+        })
+        .collect::<Vec<(String, String)>>();
+    move |text| replace_str(text, &hidden)
+}
+
+/// Returns the name and value, as text, of each variable that may not be sent to `sink`.
+fn hidden_values<'a>(
+    variables: &'a HashMap<String, Value>,
+    levels: &'a HashMap<String, PrivacyLevel>,
+    sink: Sink,
+) -> impl Iterator<Item = (&'a str, String)> {
+    levels
+        .iter()
+        .filter(move |(_, level)| !level.allows(sink))
+        .filter_map(|(name, _)| {
+            let raw = match variables.get(name)? {
+                Value::String(string) => string.clone(),
+                value => value.to_string(),
+            };
+            Some((name.as_str(), raw))
+        })
+}
+
+/// Replaces the hidden values within strings, including those in arrays (e.g. `groups`) and
+/// tables.
+fn replace(value: &mut Value, hidden: &[(String, String)]) {
+    match value {
+        Value::String(string) => *string = replace_str(string, hidden),
+        Value::Array(values) => {
+            for value in values {
+                replace(value, hidden);
+            }
+        }
+        Value::Table(table) => {
+            for (_, value) in table.iter_mut() {
+                replace(value, hidden);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn replace_str(text: &str, hidden: &[(String, String)]) -> String {
+    let mut text = text.to_owned();
+    for (raw, hash) in hidden {
+        text = text.replace(raw, hash);
+    }
+    text
+}
+
+fn hash(value: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(&KEY).expect("HMAC takes keys of any length");
+    mac.update(value.as_bytes());
+    let hex = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .take(6)
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    format!("hmac:{hex}")
+}
+
+/// Reads the key from the cache directory, creating it if it doesn't exist. If it can't be read
+/// or stored, a key that lasts until ramon exits is used instead.
+fn load_key() -> Vec<u8> {
+    let path = platform::cache_dir().join("privacy.key");
+    match fs::read(&path) {
+        Ok(key) if !key.is_empty() => return key,
+        Ok(_) => {}
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => {
+            warn!("Failed to read {path:?}: {err}. Hashes of redacted values will change when ramon restarts.");
+            return random_key();
+        }
+    }
+    let key = random_key();
+    if let Err(err) = store_key(&path, &key) {
+        warn!("Failed to store {path:?}: {err}. Hashes of redacted values will change when ramon restarts.");
+    }
+    key
+}
+
+fn random_key() -> Vec<u8> {
+    let mut key = vec![0; 32];
+    getrandom::getrandom(&mut key).expect("Failed to generate a random key");
+    key
+}
+
+fn store_key(path: &Path, key: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    // Anyone with the key could recover redacted values by hashing candidates.
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(key)
+}
@@ -0,0 +1,156 @@
+//! Sends notifications to push services for phones (Gotify and Pushover).
+
+use anyhow::Result;
+use serde_json::json;
+
+use crate::{
+    config::{Notification, Severity},
+    error::{Error, ErrorKind},
+    highlight::{self, Highlight},
+    notifier::{Delivery, Notifier},
+    source::BoxFuture,
+};
+
+/// Pushover rejects titles and messages longer than these.
+const PUSHOVER_TITLE_LEN: usize = 250;
+const PUSHOVER_MESSAGE_LEN: usize = 1024;
+
+/// The `gotify` channel.
+pub struct GotifyConfig {
+    /// The base URL of the server.
+    pub url: String,
+    /// The token of the application to send as.
+    pub token: String,
+    /// If unset, the priority is chosen by severity.
+    pub priority: Option<i64>,
+}
+
+/// The `pushover` channel.
+pub struct PushoverConfig {
+    /// The key of the user or group to send to.
+    pub user: String,
+    /// The API token of the application to send as.
+    pub token: String,
+    /// From -2 to 2. If unset, the priority is chosen by severity.
+    pub priority: Option<i64>,
+    /// The name of the sound to play. If unset, the user's default sound is played.
+    pub sound: Option<String>,
+}
+
+impl Notifier for GotifyConfig {
+    fn channel(&self) -> &'static str {
+        "gotify"
+    }
+
+    fn send<'a>(
+        &'a self,
+        notification: &'a Notification,
+        _delivery: &'a Delivery,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(send_gotify(notification, self))
+    }
+}
+
+impl Notifier for PushoverConfig {
+    fn channel(&self) -> &'static str {
+        "pushover"
+    }
+
+    fn send<'a>(
+        &'a self,
+        notification: &'a Notification,
+        _delivery: &'a Delivery,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(send_pushover(notification, self))
+    }
+}
+
+async fn send_gotify(notification: &Notification, config: &GotifyConfig) -> Result<()> {
+    let priority = config.priority.unwrap_or(match notification.severity {
+        Severity::Info => 2,
+        Severity::Warning => 5,
+        Severity::Critical => 8,
+    });
+    let payload = json!({
+        "title": notification.title,
+        "message": message(notification),
+        "priority": priority,
+        "extras": { "client::display": { "contentType": "text/markdown" } },
+    });
+    let res = reqwest::Client::new()
+        .post(format!("{}/message", config.url.trim_end_matches('/')))
+        .header("X-Gotify-Key", &config.token)
+        .json(&payload)
+        .send()
+        .await
+        .and_then(|res| res.error_for_status());
+    match res {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            Err(Error::new(ErrorKind::Push, format!("Failed to send to Gotify: {err}")).into())
+        }
+    }
+}
+
+async fn send_pushover(notification: &Notification, config: &PushoverConfig) -> Result<()> {
+    let priority = config.priority.unwrap_or(match notification.severity {
+        Severity::Info => -1,
+        Severity::Warning => 0,
+        Severity::Critical => 1,
+    });
+    let mut payload = json!({
+        "token": config.token,
+        "user": config.user,
+        "title": truncate(&notification.title, PUSHOVER_TITLE_LEN),
+        "message": truncate(&message(notification), PUSHOVER_MESSAGE_LEN),
+        "priority": priority,
+        "timestamp": notification.timestamp,
+    });
+    // Emergency notifications repeat until they're acknowledged.
+    if priority == 2 {
+        payload["retry"] = 60.into();
+        payload["expire"] = 3600.into();
+    }
+    if let Some(sound) = &config.sound {
+        payload["sound"] = sound.as_str().into();
+    }
+    let res = reqwest::Client::new()
+        .post("https://api.pushover.net/1/messages.json")
+        .json(&payload)
+        .send()
+        .await
+        .and_then(|res| res.error_for_status());
+    match res {
+        Ok(_) => Ok(()),
+        Err(err) => Err(Error::new(
+            ErrorKind::Push,
+            format!("Failed to send to Pushover: {err}"),
+        )
+        .into()),
+    }
+}
+
+/// Both services require a message, so the title is repeated if there is no body.
+fn message(notification: &Notification) -> String {
+    let body = highlight::append(
+        &notification.body,
+        notification.highlight.as_ref(),
+        Highlight::markdown,
+    );
+    match body.is_empty() {
+        true => notification.title.clone(),
+        false => body,
+    }
+}
+
+/// Truncates `text` to `len` characters, ending with an ellipsis if anything was cut.
+pub fn truncate(text: &str, len: usize) -> String {
+    match text.char_indices().nth(len) {
+        None => text.to_owned(),
+        Some(_) => {
+            let mut truncated = text.chars().take(len - 1).collect::<String>();
+            truncated.push('…');
+            truncated
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! `max_per_hour`, which limits how many notifications each monitor may send.
+
+use std::{collections::HashMap, time::Duration};
+
+use tokio::time::Instant;
+
+use crate::clock::SharedClock;
+
+const HOUR: Duration = Duration::from_secs(60 * 60);
+
+/// Limits how many notifications each monitor may send with a token bucket per monitor. Buckets
+/// refill evenly over an hour, so short bursts are allowed but a runaway log loop isn't.
+pub struct RateLimit {
+    max_per_hour: u32,
+    buckets: HashMap<String, Bucket>,
+    clock: SharedClock,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    /// The number of notifications dropped since the last one that was allowed.
+    suppressed: usize,
+}
+
+impl RateLimit {
+    /// Allows up to `max_per_hour` notifications per hour from each monitor.
+    pub fn new(max_per_hour: u32, clock: SharedClock) -> Self {
+        Self {
+            max_per_hour,
+            buckets: HashMap::new(),
+            clock,
+        }
+    }
+
+    /// Returns how many notifications per hour are allowed from each monitor.
+    pub fn max_per_hour(&self) -> u32 {
+        self.max_per_hour
+    }
+
+    /// Returns `None` if a notification from `monitor` must be dropped. Otherwise, returns the
+    /// number of notifications from `monitor` that were dropped before this one.
+    pub fn allow(&mut self, monitor: &str) -> Option<usize> {
+        let now = self.clock.now();
+        let max_per_hour = self.max_per_hour;
+        let bucket = self
+            .buckets
+            .entry(monitor.to_owned())
+            .or_insert_with(|| Bucket {
+                tokens: max_per_hour as f64,
+                updated: now,
+                suppressed: 0,
+            });
+        bucket.refill(now, max_per_hour);
+        if bucket.tokens < 1.0 {
+            bucket.suppressed += 1;
+            return None;
+        }
+        bucket.tokens -= 1.0;
+        Some(std::mem::take(&mut bucket.suppressed))
+    }
+
+    /// Returns when the next monitor with dropped notifications will have a token again, if any.
+    pub fn next_refill(&self) -> Option<Instant> {
+        let rate = self.max_per_hour as f64 / HOUR.as_secs_f64();
+        self.buckets
+            .values()
+            .filter(|bucket| bucket.suppressed > 0)
+            .map(|bucket| bucket.updated + Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+            .min()
+            // Round up so that the token has refilled despite floating point error.
+            .map(|refill| refill + Duration::from_millis(1))
+    }
+
+    /// Takes a token from each monitor with dropped notifications that has one, so that a summary
+    /// can be sent in its place, and returns the number of notifications each one dropped.
+    pub fn take_suppressed(&mut self) -> Vec<(String, usize)> {
+        let now = self.clock.now();
+        let mut suppressed = Vec::new();
+        for (monitor, bucket) in &mut self.buckets {
+            if bucket.suppressed == 0 {
+                continue;
+            }
+            bucket.refill(now, self.max_per_hour);
+            if bucket.tokens >= 1.0 {
+                bucket.tokens -= 1.0;
+                suppressed.push((monitor.clone(), std::mem::take(&mut bucket.suppressed)));
+            }
+        }
+        suppressed
+    }
+
+    /// Returns the clock that refills the buckets.
+    pub fn clock(&self) -> &SharedClock {
+        &self.clock
+    }
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant, max_per_hour: u32) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * max_per_hour as f64 / HOUR.as_secs_f64())
+            .min(max_per_hour as f64);
+        self.updated = now;
+    }
+}
@@ -0,0 +1,125 @@
+//! The recipients of emails and text messages, and the URLs of webhooks, which may be resolved by
+//! running a command.
+
+use std::{
+    fmt::{Debug, Display},
+    process::Stdio,
+    str::FromStr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Result};
+use lettre::message::Mailbox;
+use log::{debug, warn};
+
+use crate::platform::Shell;
+
+/// The recipients of emails or text messages, or the URLs of webhooks.
+pub enum Recipients<T = Mailbox> {
+    /// Recipients written in the config.
+    Static(Vec<T>),
+    /// Recipients listed by a command when sending.
+    Command(RecipientCommand<T>),
+}
+
+/// Recipients resolved at send time from the output of an external command (e.g. an on-call
+/// rota). Each non-empty line of stdout is parsed as a recipient.
+pub struct RecipientCommand<T = Mailbox> {
+    /// The command, run by the shell, that lists the recipients.
+    pub command: String,
+    /// Used if the command fails before it has ever succeeded.
+    pub fallback: Option<T>,
+    /// How long the recipients are reused before the command runs again.
+    pub cache: Duration,
+    shell: Shell,
+    cached: Mutex<Option<(Instant, Vec<T>)>>,
+}
+
+impl<T: FromStr + Clone + Debug> Recipients<T>
+where
+    T::Err: Display,
+{
+    /// Returns the recipients, reporting failures as the notification config `name`.
+    pub async fn resolve(&self, name: &str) -> Result<Vec<T>> {
+        match self {
+            Recipients::Static(mailboxes) => Ok(mailboxes.clone()),
+            Recipients::Command(command) => command.resolve(name).await,
+        }
+    }
+}
+
+impl<T: FromStr + Clone + Debug> RecipientCommand<T>
+where
+    T::Err: Display,
+{
+    /// Creates recipients that run `command` with `shell`.
+    pub fn new(command: String, fallback: Option<T>, cache: Duration, shell: Shell) -> Self {
+        Self {
+            command,
+            fallback,
+            cache,
+            shell,
+            cached: Mutex::new(None),
+        }
+    }
+
+    async fn resolve(&self, name: &str) -> Result<Vec<T>> {
+        if let Some((resolved_at, recipients)) = &*self.cached.lock().unwrap() {
+            if resolved_at.elapsed() < self.cache {
+                return Ok(recipients.clone());
+            }
+        }
+
+        match self.run().await {
+            Ok(recipients) => {
+                debug!("[{name}] Resolved recipients: {recipients:?}");
+                *self.cached.lock().unwrap() = Some((Instant::now(), recipients.clone()));
+                Ok(recipients)
+            }
+            Err(err) => {
+                warn!(
+                    "[{name}] Failed to resolve recipients with {:?}: {err}",
+                    self.command
+                );
+                // Prefer stale results over the static fallback.
+                if let Some((_, recipients)) = &*self.cached.lock().unwrap() {
+                    return Ok(recipients.clone());
+                }
+                match &self.fallback {
+                    None => Err(err),
+                    Some(fallback) => Ok(vec![fallback.clone()]),
+                }
+            }
+        }
+    }
+
+    async fn run(&self) -> Result<Vec<T>> {
+        let output = self
+            .shell
+            .command(&self.command)
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .map_err(|err| anyhow!("Failed to spawn: {err}"))?;
+        if !output.status.success() {
+            bail!("Command exited with {}", output.status);
+        }
+
+        let stdout = String::from_utf8(output.stdout)
+            .map_err(|err| anyhow!("Output is not valid UTF-8: {err}"))?;
+        let recipients = stdout
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                line.parse()
+                    .map_err(|err| anyhow!("Failed to parse {line:?}: {err}"))
+            })
+            .collect::<Result<Vec<T>>>()?;
+        if recipients.is_empty() {
+            bail!("Command did not output any recipients.");
+        }
+        Ok(recipients)
+    }
+}
@@ -0,0 +1,164 @@
+//! `scenario`, which runs a sequence of HTTP requests as a check.
+
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::{anyhow, bail, Result};
+use log::debug;
+use regex::Regex;
+use reqwest::{Client, Method};
+use tera::Tera;
+use toml::Value;
+
+use crate::template;
+
+/// An HTTP request, as a step of `scenario` or of `actions`.
+pub struct StepConfig {
+    /// Identifies the step in errors, as `step`.
+    pub name: String,
+    /// The HTTP method.
+    pub method: Method,
+    /// The URL (template).
+    pub url: String,
+    /// The name and value (template) of each header.
+    pub headers: Vec<(String, String)>,
+    /// The body of the request (template).
+    pub body: Option<String>,
+    /// How long the request may take.
+    pub timeout: Duration,
+    /// If unset, any successful status is accepted.
+    pub expect_status: Option<u16>,
+    /// A regex that the response body must match.
+    pub expect_body: Option<Regex>,
+    /// Named capture groups become variables for the following steps.
+    pub extract: Option<Regex>,
+}
+
+/// A sequence of HTTP requests that share cookies and variables, such as logging in, fetching a
+/// page, and logging out.
+pub struct Scenario {
+    steps: Vec<StepConfig>,
+    templates: Tera,
+}
+
+impl Scenario {
+    /// Parses the templates of each step.
+    pub fn new(steps: Vec<StepConfig>) -> Result<Self> {
+        let mut templates = template::new_engine();
+        for (i, step) in steps.iter().enumerate() {
+            add_templates(&mut templates, &i.to_string(), step)?;
+        }
+        Ok(Self { steps, templates })
+    }
+
+    /// Runs every step in order. If a step fails, returns the variables extracted so far along
+    /// with `step` (the name of the failed step) and `err`.
+    pub async fn run(
+        &self,
+        name: &str,
+        global: &HashMap<String, Value>,
+    ) -> Option<HashMap<String, Value>> {
+        let client = match Client::builder().cookie_store(true).build() {
+            Ok(client) => client,
+            Err(err) => {
+                return Some(HashMap::from([
+                    ("step".into(), "".into()),
+                    ("err".into(), err.to_string().into()),
+                ]))
+            }
+        };
+
+        let mut variables = HashMap::new();
+        for (i, step) in self.steps.iter().enumerate() {
+            debug!("[{name}] Running step `{}`", step.name);
+            if let Err(err) = send(
+                &client,
+                &self.templates,
+                &i.to_string(),
+                step,
+                &mut variables,
+                global,
+            )
+            .await
+            {
+                variables.insert("step".into(), step.name.as_str().into());
+                variables.insert("err".into(), format!("{err:#}").into());
+                return Some(variables);
+            }
+        }
+        None
+    }
+}
+
+/// Registers the templates of a request under names starting with `prefix`.
+pub fn add_templates(templates: &mut Tera, prefix: &str, step: &StepConfig) -> Result<()> {
+    template::add(templates, &format!("{prefix}.url"), &step.url)?;
+    for (j, (_, value)) in step.headers.iter().enumerate() {
+        template::add(templates, &format!("{prefix}.header.{j}"), value)?;
+    }
+    if let Some(body) = &step.body {
+        template::add(templates, &format!("{prefix}.body"), body)?;
+    }
+    Ok(())
+}
+
+/// Sends a request whose templates were registered by `add_templates`, and checks the response.
+/// Sets `status` and the named capture groups of `extract`.
+pub async fn send(
+    client: &Client,
+    templates: &Tera,
+    prefix: &str,
+    step: &StepConfig,
+    variables: &mut HashMap<String, Value>,
+    global: &HashMap<String, Value>,
+) -> Result<()> {
+    let context = template::context(variables, global);
+    let url = template::render(templates, &format!("{prefix}.url"), &context)?;
+    let mut request = client
+        .request(step.method.clone(), &url)
+        .timeout(step.timeout);
+    for (j, (header, _)) in step.headers.iter().enumerate() {
+        let value = template::render(templates, &format!("{prefix}.header.{j}"), &context)?;
+        request = request.header(header, value);
+    }
+    if step.body.is_some() {
+        request = request.body(template::render(
+            templates,
+            &format!("{prefix}.body"),
+            &context,
+        )?);
+    }
+
+    let response = request.send().await?;
+    let status = response.status();
+    variables.insert("status".into(), (status.as_u16() as i64).into());
+    match step.expect_status {
+        None if !status.is_success() => bail!("{url} returned {status}"),
+        Some(expected) if expected != status.as_u16() => {
+            bail!("{url} returned {status}; expected {expected}")
+        }
+        _ => {}
+    }
+
+    let body = response
+        .text()
+        .await
+        .map_err(|err| anyhow!("Failed to read response from {url}: {err}"))?;
+    if let Some(regex) = &step.expect_body {
+        if !regex.is_match(&body) {
+            bail!("Response from {url} does not match {:?}", regex.as_str());
+        }
+    }
+    if let Some(regex) = &step.extract {
+        let captures = match regex.captures(&body) {
+            Some(captures) => captures,
+            None => bail!("Failed to extract {:?} from {url}", regex.as_str()),
+        };
+        for capture_name in regex.capture_names().flatten() {
+            if let Some(capture) = captures.name(capture_name) {
+                variables.insert(capture_name.to_owned(), capture.as_str().into());
+            }
+        }
+    }
+
+    Ok(())
+}
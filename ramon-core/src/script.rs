@@ -0,0 +1,195 @@
+//! Rhai scripts, for the `script` action and `filter` scripts.
+
+use std::{
+    collections::HashMap,
+    fs,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail, Result};
+use log::info;
+use regex::Regex;
+use reqwest::Client;
+use rhai::{Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+use tokio::{runtime::Handle, task};
+use toml::Value;
+
+use crate::variables::Globals;
+
+/// Stops scripts that would otherwise run forever, such as an infinite loop.
+const MAX_OPERATIONS: u64 = 10_000_000;
+/// How long `http_get` and `http_post` wait for a response.
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+type RhaiResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// A compiled script and the engine that runs it.
+#[derive(Clone)]
+pub struct Script {
+    engine: Arc<Engine>,
+    ast: Arc<AST>,
+}
+
+impl Script {
+    /// Compiles a script. `print` and `debug` in the script are logged with the monitor's name.
+    pub fn compile(monitor: &str, source: &str) -> Result<Self> {
+        let engine = engine(monitor);
+        let ast = engine.compile(source).map_err(|err| anyhow!("{err}"))?;
+        Ok(Self {
+            engine: Arc::new(engine),
+            ast: Arc::new(ast),
+        })
+    }
+
+    /// Runs the script with the local variables in its scope, and the global variables in the
+    /// map `vars`, since `var` is reserved in Rhai. Changes the script makes to `vars` are applied
+    /// to the global variables.
+    pub async fn run(&self, local: &HashMap<String, Value>, globals: &Globals) -> Result<Value> {
+        let script = self.clone();
+        let local = local.clone();
+        let global = globals.lock().unwrap().values.clone();
+        // Scripts may block, such as while waiting for `http_get`.
+        let (result, global_after) = task::spawn_blocking(move || -> Result<_> {
+            let mut scope = Scope::new();
+            for (name, value) in &local {
+                scope.push_constant_dynamic(name.as_str(), rhai::serde::to_dynamic(value)?);
+            }
+            scope.push_dynamic("vars", rhai::serde::to_dynamic(&global)?);
+            let result = script
+                .engine
+                .eval_ast_with_scope::<Dynamic>(&mut scope, &script.ast)
+                .map_err(|err| anyhow!("{err}"))?;
+            let global_after = scope.get_value::<Map>("vars").unwrap_or_default();
+            Ok((to_value(&result)?, (global, global_after)))
+        })
+        .await??;
+
+        let (global, global_after) = global_after;
+        let mut variables = globals.lock().unwrap();
+        for name in global.keys() {
+            if !global_after.contains_key(name.as_str()) {
+                variables.values.remove(name);
+            }
+        }
+        for (name, value) in global_after {
+            let value = to_value(&value)?;
+            if global.get(name.as_str()) != Some(&value) {
+                variables.values.insert(name.into(), value);
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// Sets the entries of a map returned by a script as variables, or any other value as
+/// `script_result`.
+pub fn set_variables(result: Value, variables: &mut HashMap<String, Value>) {
+    match result {
+        Value::Table(table) => variables.extend(table),
+        Value::String(result) if result.is_empty() => {}
+        result => {
+            variables.insert("script_result".into(), result);
+        }
+    }
+}
+
+/// Converts a value returned by a script to a variable. `()` becomes an empty string.
+fn to_value(dynamic: &Dynamic) -> Result<Value> {
+    if dynamic.is_unit() {
+        return Ok(Value::String(String::new()));
+    }
+    rhai::serde::from_dynamic(dynamic).map_err(|err| anyhow!("{err}"))
+}
+
+fn engine(monitor: &str) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    let name = monitor.to_owned();
+    engine.on_print(move |text| info!("[{name}] {text}"));
+    let name = monitor.to_owned();
+    engine.on_debug(move |text, _, _| info!("[{name}] {text}"));
+
+    engine.register_fn("now", now);
+    engine.register_fn("regex_match", regex_match);
+    engine.register_fn("regex_captures", regex_captures);
+    engine.register_fn("http_get", |url: &str| {
+        http(reqwest::Method::GET, url, None)
+    });
+    engine.register_fn("http_post", |url: &str, body: &str| {
+        http(reqwest::Method::POST, url, Some(body.to_owned()))
+    });
+    engine
+}
+
+/// Returns the number of seconds since the Unix epoch.
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+fn regex_match(text: &str, pattern: &str) -> RhaiResult<bool> {
+    let regex = Regex::new(pattern).map_err(|err| err.to_string())?;
+    Ok(regex.is_match(text))
+}
+
+/// Returns the capture groups of the first match by name, or by index if they're unnamed, or an
+/// empty map if the pattern doesn't match.
+fn regex_captures(text: &str, pattern: &str) -> RhaiResult<Map> {
+    let regex = Regex::new(pattern).map_err(|err| err.to_string())?;
+    let mut map = Map::new();
+    let Some(captures) = regex.captures(text) else {
+        return Ok(map);
+    };
+    for (i, name) in regex.capture_names().enumerate() {
+        let Some(capture) = captures.get(i) else {
+            continue;
+        };
+        let key = name.map_or(i.to_string(), str::to_owned);
+        map.insert(key.into(), capture.as_str().into());
+    }
+    Ok(map)
+}
+
+/// Sends a request and returns a map with its `status` and `body`. Scripts run on a blocking
+/// thread, so waiting for the response doesn't stall other monitors.
+fn http(method: reqwest::Method, url: &str, body: Option<String>) -> RhaiResult<Map> {
+    let request = async {
+        let mut request = Client::new().request(method, url).timeout(HTTP_TIMEOUT);
+        if let Some(body) = body {
+            request = request.body(body);
+        }
+        let response = request.send().await?;
+        let status = response.status().as_u16() as i64;
+        let body = response.text().await?;
+        Ok::<_, reqwest::Error>(Map::from_iter([
+            ("status".into(), status.into()),
+            ("body".into(), body.into()),
+        ]))
+    };
+    Handle::current()
+        .block_on(request)
+        .map_err(|err| err.to_string().into())
+}
+
+/// Parses a script, which is either its code or a table with the `file` to read it from.
+pub fn parse(monitor: &str, key: &str, value: Value) -> Result<Script> {
+    let source = match value {
+        Value::String(source) => source,
+        Value::Table(mut table) => {
+            let path = match table.remove("file") {
+                Some(Value::String(path)) => path,
+                _ => bail!("Key `{key}.file` must be a string."),
+            };
+            if let Some(unknown) = table.keys().next() {
+                bail!("Unknown key `{key}.{unknown}`.");
+            }
+            fs::read_to_string(&path)
+                .map_err(|err| anyhow!("Failed to read `{key}.file` {path:?}: {err}"))?
+        }
+        _ => bail!("Key `{key}` must be a string or a table."),
+    };
+    Script::compile(monitor, &source).map_err(|err| anyhow!("Failed to parse `{key}`: {err}"))
+}
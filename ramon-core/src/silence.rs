@@ -0,0 +1,169 @@
+//! Mutes the notifications of a monitor for a while, as requested by `ramon silence`. When the
+//! silence ends, a notification summarizes what was suppressed.
+
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Result};
+use log::{info, warn};
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    aggregator::format_utc,
+    clock::SharedClock,
+    config::{Notification, Severity},
+    status,
+};
+
+static MONITORS: LazyLock<Mutex<HashMap<String, Target>>> = LazyLock::new(Default::default);
+
+struct Target {
+    /// The name of the notification config the monitor sends to.
+    r#type: String,
+    aggregator_tx: Sender<Notification>,
+    /// Times the end of silences, so that they follow the monitor's clock.
+    clock: SharedClock,
+    silence: Option<Silence>,
+}
+
+struct Silence {
+    /// Seconds since the Unix epoch.
+    since: u64,
+    suppressed: u64,
+    /// Distinguishes this silence from later ones, so an expiry timer doesn't end a silence that
+    /// was extended or replaced.
+    id: u64,
+}
+
+/// Makes a monitor that sends notifications silenceable.
+pub fn register(
+    name: &str,
+    r#type: String,
+    aggregator_tx: Sender<Notification>,
+    clock: SharedClock,
+) {
+    MONITORS.lock().unwrap().insert(
+        name.to_owned(),
+        Target {
+            r#type,
+            aggregator_tx,
+            clock,
+            silence: None,
+        },
+    );
+}
+
+/// Returns whether a notification of `name` should be suppressed, counting it if so.
+pub fn suppress(name: &str) -> bool {
+    let mut monitors = MONITORS.lock().unwrap();
+    match monitors
+        .get_mut(name)
+        .and_then(|target| target.silence.as_mut())
+    {
+        None => false,
+        Some(silence) => {
+            silence.suppressed += 1;
+            true
+        }
+    }
+}
+
+/// Silences `name` for `duration`, extending its silence if it's already silenced. Returns when
+/// the silence ends, in seconds since the Unix epoch.
+pub fn silence(name: &str, duration: Duration) -> Result<u64> {
+    let now = status::now();
+    let until = now + duration.as_secs();
+    let (id, clock) = {
+        let mut monitors = MONITORS.lock().unwrap();
+        let target = target(&mut monitors, name)?;
+        let clock = target.clock.clone();
+        let silence = target.silence.get_or_insert(Silence {
+            since: now,
+            suppressed: 0,
+            id: 0,
+        });
+        silence.id += 1;
+        (silence.id, clock)
+    };
+    status::update(name, |status| status.silenced_until = Some(until));
+    info!("[{name}] Silenced until {}", format_utc(until));
+
+    let name = name.to_owned();
+    let deadline = clock.now() + duration;
+    tokio::spawn(async move {
+        clock.sleep_until(deadline).await;
+        let mut monitors = MONITORS.lock().unwrap();
+        let expired = monitors
+            .get(&name)
+            .and_then(|target| target.silence.as_ref())
+            .is_some_and(|silence| silence.id == id);
+        if expired {
+            end(&mut monitors, &name);
+        }
+    });
+    Ok(until)
+}
+
+/// Ends the silence of `name` early. Returns how many notifications were suppressed.
+pub fn unsilence(name: &str) -> Result<u64> {
+    let mut monitors = MONITORS.lock().unwrap();
+    if target(&mut monitors, name)?.silence.is_none() {
+        bail!("Monitor `{name}` isn't silenced");
+    }
+    Ok(end(&mut monitors, name))
+}
+
+fn target<'a>(monitors: &'a mut HashMap<String, Target>, name: &str) -> Result<&'a mut Target> {
+    monitors
+        .get_mut(name)
+        .ok_or_else(|| anyhow!("Unknown monitor `{name}`, or it doesn't have `notify`"))
+}
+
+/// Removes the silence of `name` and sends a notification summarizing it.
+fn end(monitors: &mut HashMap<String, Target>, name: &str) -> u64 {
+    let target = monitors.get_mut(name).unwrap();
+    let silence = target.silence.take().unwrap();
+    status::update(name, |status| status.silenced_until = None);
+    info!(
+        "[{name}] No longer silenced; suppressed {} notifications",
+        silence.suppressed
+    );
+
+    let now = status::now();
+    let plural = match silence.suppressed {
+        1 => "",
+        _ => "s",
+    };
+    let notification = Notification {
+        r#type: target.r#type.clone(),
+        monitor: name.to_owned(),
+        severity: Severity::Info,
+        title: format!(
+            "Suppressed {} notification{plural} while {name} was silenced",
+            silence.suppressed
+        ),
+        body: format!(
+            "`{name}` was silenced from {} to {}.",
+            format_utc(silence.since),
+            format_utc(now)
+        ),
+        timestamp: now,
+        variables: HashMap::from([
+            ("suppressed".into(), (silence.suppressed as i64).into()),
+            ("silenced_since".into(), (silence.since as i64).into()),
+        ]),
+        highlight: None,
+        resolved: false,
+    };
+    let aggregator_tx = target.aggregator_tx.clone();
+    let name = name.to_owned();
+    tokio::spawn(async move {
+        if let Err(err) = aggregator_tx.send(notification).await {
+            warn!("[{name}] Failed to send the summary of its silence: {err}");
+        }
+    });
+    silence.suppressed
+}
@@ -0,0 +1,84 @@
+//! Sends notifications as text messages with Twilio.
+
+use anyhow::Result;
+
+use crate::{
+    config::{Notification, Severity},
+    error::{Error, ErrorKind},
+    notifier::{Delivery, Notifier},
+    push::truncate,
+    recipients::Recipients,
+    source::BoxFuture,
+};
+
+/// The longest message Twilio accepts.
+pub const MAX_LEN: usize = 1600;
+
+/// The `sms` channel, which sends text messages with Twilio.
+pub struct SmsConfig {
+    /// The SID of the Twilio account.
+    pub account_sid: String,
+    /// The auth token of the Twilio account.
+    pub auth_token: String,
+    /// The Twilio phone number to send from.
+    pub from: String,
+    /// The phone numbers to send to, which may be resolved by a command when sending.
+    pub to: Recipients<String>,
+    /// Messages are truncated to this many characters. A single SMS holds 160.
+    pub max_len: usize,
+    /// Notifications from monitors with a lower severity aren't sent by SMS.
+    pub min_severity: Severity,
+}
+
+impl Notifier for SmsConfig {
+    fn channel(&self) -> &'static str {
+        "sms"
+    }
+
+    fn accepts(&self, notification: &Notification) -> bool {
+        notification.severity >= self.min_severity
+    }
+
+    fn send<'a>(
+        &'a self,
+        notification: &'a Notification,
+        delivery: &'a Delivery,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(send(notification, self, delivery.name))
+    }
+}
+
+/// Sends one message to each recipient, reporting failures as the notification config `name`.
+async fn send(notification: &Notification, config: &SmsConfig, name: &str) -> Result<()> {
+    let recipients = config.to.resolve(name).await.map_err(|err| {
+        Error::new(
+            ErrorKind::Recipients,
+            format!("Failed to resolve recipients: {err}"),
+        )
+    })?;
+    let text = match notification.body.is_empty() {
+        true => notification.title.clone(),
+        false => format!("{}\n{}", notification.title, notification.body),
+    };
+    let text = truncate(&text, config.max_len);
+    let url = format!(
+        "https://api.twilio.com/2010-04-01/Accounts/{}/Messages.json",
+        config.account_sid
+    );
+    let client = reqwest::Client::new();
+    for to in &recipients {
+        let res = client
+            .post(&url)
+            .basic_auth(&config.account_sid, Some(&config.auth_token))
+            .form(&[("From", &config.from), ("To", to), ("Body", &text)])
+            .send()
+            .await
+            .and_then(|res| res.error_for_status());
+        if let Err(err) = res {
+            return Err(
+                Error::new(ErrorKind::Sms, format!("Failed to send SMS to {to}: {err}")).into(),
+            );
+        }
+    }
+    Ok(())
+}
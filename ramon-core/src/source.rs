@@ -0,0 +1,53 @@
+//! The `Source` trait, which every source of a monitor's events implements: log files, the
+//! journal, syslog listeners, MQTT subscriptions, commands, and `every`.
+
+use std::{future::Future, pin::Pin};
+
+use anyhow::Result;
+use log::error;
+use tokio::sync::mpsc::Sender;
+
+use crate::health;
+
+/// What a source sends to its monitor.
+pub use crate::monitor::Event;
+
+/// A future that can be returned from the methods of traits.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Where a monitor's events come from. A source is opened before its monitor starts, then runs in
+/// the background, sending each record (usually a line) to its monitor as an [`Event`].
+pub trait Source: Send + 'static {
+    /// What the source is called in health checks and errors (e.g. `log`).
+    fn kind(&self) -> &'static str;
+
+    /// Prepares the source, such as by spawning a process. Errors stop ramon from starting, and
+    /// this runs before ramon drops privileges.
+    fn open(&mut self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Sends events to `event_tx` until the source ends, or until sending fails because the
+    /// monitor has stopped.
+    fn run(self: Box<Self>, event_tx: Sender<Event>) -> BoxFuture<'static, Result<()>>;
+}
+
+/// Opens `source`, then runs it in the background, reporting it as attached to the monitor `name`
+/// until it ends.
+pub(crate) async fn spawn(
+    name: &str,
+    mut source: Box<dyn Source>,
+    event_tx: Sender<Event>,
+) -> Result<()> {
+    source.open().await?;
+    let kind = source.kind();
+    let attachment = health::attach(name, kind);
+    let name = name.to_owned();
+    tokio::spawn(async move {
+        let _attachment = attachment;
+        if let Err(err) = source.run(event_tx).await {
+            error!("[{name}] Source `{kind}`: {err}");
+        }
+    });
+    Ok(())
+}
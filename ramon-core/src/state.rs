@@ -0,0 +1,168 @@
+//! Persisted state files. Each file starts with a header containing the version of its format, so
+//! that files written by older versions of ramon can be migrated, and files written by newer
+//! versions are not misread.
+
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use log::{debug, error, info};
+use tokio::fs::{create_dir_all, read_to_string, rename, write};
+
+use crate::{
+    config::Severity,
+    error::{Error, ErrorKind},
+    meta, platform,
+};
+
+const HEADER_PREFIX: &str = "# ramon state v";
+/// Non-essential state is not written while less space than this is available, so that ramon
+/// keeps alerting rather than filling the disk.
+const MIN_AVAILABLE_SPACE: u64 = 100 * 1024 * 1024;
+/// How often `check_disk_pressure` should run.
+pub const DISK_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+static DISK_PRESSURE: AtomicBool = AtomicBool::new(false);
+/// Set by `ramon replay`, so that backtesting neither reads nor changes the state of the running
+/// instance.
+static DETACHED: AtomicBool = AtomicBool::new(false);
+
+/// Converts the contents of a state file from one version to the next.
+pub type Migration = fn(&str) -> Result<String>;
+
+/// A kind of state file (e.g. uptime history). Its current version is the number of migrations,
+/// where `migrations[0]` converts files from before versioning was introduced.
+pub struct Format {
+    /// Names the files of this kind (e.g. `uptime_<monitor>`).
+    pub kind: &'static str,
+    /// The migrations from each earlier version to the next.
+    pub migrations: &'static [Migration],
+    /// Whether the state is needed to behave correctly (e.g. to not repeat notifications), as
+    /// opposed to statistics, which are not written while the disk is nearly full.
+    pub essential: bool,
+}
+
+impl Format {
+    fn version(&self) -> usize {
+        self.migrations.len()
+    }
+
+    /// Returns the path of the state of the monitor `name`.
+    pub fn path(&self, name: &str) -> PathBuf {
+        platform::cache_dir().join(format!("{}_{name}", self.kind))
+    }
+
+    /// Reads the state of a monitor, migrating it to the current version. Returns `None` if the
+    /// file does not exist.
+    pub async fn read(&self, name: &str) -> Result<Option<String>> {
+        if DETACHED.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+        let path = self.path(name);
+        let contents = match read_to_string(&path).await {
+            Ok(contents) => contents,
+            Err(_) => return Ok(None),
+        };
+
+        let (version, contents) = match contents.strip_prefix(HEADER_PREFIX) {
+            None => (0, contents.as_str()),
+            Some(rest) => {
+                let (version, contents) = rest.split_once('\n').unwrap_or((rest, ""));
+                let version = version
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|err| anyhow!("Invalid header in {path:?}: {err}"))?;
+                (version, contents)
+            }
+        };
+        if version > self.version() {
+            return Err(Error::new(
+                ErrorKind::StateVersion,
+                format!(
+                    "{path:?} was written by a newer version of ramon (format v{version}, but this version only supports up to v{})",
+                    self.version()
+                ),
+            )
+            .into());
+        }
+
+        let mut contents = contents.to_owned();
+        for (i, migration) in self.migrations.iter().enumerate().skip(version) {
+            contents = migration(&contents)
+                .map_err(|err| anyhow!("Failed to migrate {path:?} to v{}: {err}", i + 1))?;
+            info!("[{name}] Migrated {path:?} to v{}.", i + 1);
+        }
+        Ok(Some(contents))
+    }
+
+    /// Atomically replaces the state of a monitor.
+    pub async fn write(&self, name: &str, contents: &str) -> Result<()> {
+        if DETACHED.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let _ = create_dir_all(platform::cache_dir()).await;
+        if !self.essential && under_disk_pressure() {
+            debug!("[{name}] Skipping write of {} state.", self.kind);
+            return Ok(());
+        }
+
+        let path = self.path(name);
+        let tmp_path = platform::cache_dir().join(format!("{}_{name}.new", self.kind));
+        let contents = format!("{HEADER_PREFIX}{}\n{contents}", self.version());
+        write(&tmp_path, contents)
+            .await
+            .map_err(|err| anyhow!("Failed to write {tmp_path:?}: {err}"))?;
+        rename(&tmp_path, &path)
+            .await
+            .map_err(|err| anyhow!("Failed to rename {tmp_path:?}: {err}"))?;
+        Ok(())
+    }
+}
+
+/// Makes every state start empty and never be written.
+pub fn detach() {
+    DETACHED.store(true, Ordering::Relaxed);
+}
+
+/// Returns whether the filesystem holding the cache directory was nearly full when it was last
+/// checked. Non-essential writes, such as the audit log, are skipped while it is.
+pub fn under_disk_pressure() -> bool {
+    DISK_PRESSURE.load(Ordering::Relaxed)
+}
+
+/// Checks whether the filesystem holding the cache directory is nearly full, logging and
+/// reporting whenever this changes. It's checked every `DISK_CHECK_INTERVAL` rather than when
+/// writing, so that it's reported even if nothing is written.
+pub fn check_disk_pressure() {
+    let Ok(stats) = fs4::statvfs(platform::cache_dir()) else {
+        return;
+    };
+    let available = stats.available_space();
+    let pressure = available < MIN_AVAILABLE_SPACE;
+    if DISK_PRESSURE.swap(pressure, Ordering::Relaxed) != pressure {
+        match pressure {
+            true => {
+                let err = Error::new(
+                    ErrorKind::DiskPressure,
+                    format!(
+                        "Only {} MiB is available in {:?}; pausing non-essential writes.",
+                        available / 1024 / 1024,
+                        platform::cache_dir()
+                    ),
+                );
+                error!("{err}");
+                let title = "The disk holding ramon's cache is nearly full".into();
+                meta::report(meta::MONITOR, Severity::Warning, title, &err);
+            }
+            false => info!("Disk space is available again; resuming non-essential writes."),
+        }
+    }
+}
+
+/// A migration for formats that only gained a header.
+pub fn unchanged(contents: &str) -> Result<String> {
+    Ok(contents.to_owned())
+}
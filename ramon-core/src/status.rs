@@ -0,0 +1,152 @@
+//! The state of each monitor and aggregator, as reported by `ramon status`.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{LazyLock, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use toml::{Table, Value};
+
+static STATUS: LazyLock<Mutex<Status>> = LazyLock::new(Default::default);
+/// When the first monitor was added, in seconds since the Unix epoch.
+static STARTED: LazyLock<u64> = LazyLock::new(now);
+
+/// The state of every monitor and notification config, as reported by `ramon status`.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Status {
+    /// The status of each monitor, by name.
+    pub monitors: BTreeMap<String, MonitorStatus>,
+    /// The number of notifications waiting to be aggregated or delivered, by notification config.
+    pub queues: BTreeMap<String, usize>,
+    /// The number of notifications dropped because too many deliveries were waiting, by
+    /// notification config.
+    #[serde(default)]
+    pub dropped: BTreeMap<String, u64>,
+}
+
+/// What a monitor has done since ramon started.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct MonitorStatus {
+    /// Seconds since the Unix epoch.
+    pub last_event: Option<u64>,
+    /// How far into its log file the monitor has read, in bytes.
+    pub cursor: Option<u64>,
+    /// How many lines the monitor has received.
+    #[serde(default)]
+    pub lines: u64,
+    /// How many times every condition was met.
+    pub matches: u64,
+    /// When every condition was last met, in seconds since the Unix epoch.
+    #[serde(default)]
+    pub last_match: Option<u64>,
+    /// Whether the monitor is in a problem state: every condition was met for an event that
+    /// didn't `resolve`, and no check of `every` has failed or event resolved since.
+    #[serde(default)]
+    pub firing: bool,
+    /// How the last run of `exec` ended (e.g. `exit status: 1`).
+    pub last_exec: Option<String>,
+    /// How many times `exec` ran.
+    #[serde(default)]
+    pub execs: u64,
+    /// How many runs of `exec` failed or couldn't be spawned.
+    #[serde(default)]
+    pub exec_failures: u64,
+    /// How many runs of `exec` were dropped or coalesced by `exec_overflow`.
+    #[serde(default)]
+    pub execs_skipped: u64,
+    /// When `ramon silence` stops muting the monitor, in seconds since the Unix epoch.
+    #[serde(default)]
+    pub silenced_until: Option<u64>,
+    /// Whether the monitor was paused through the API, so it ignores every event.
+    #[serde(default)]
+    pub paused: bool,
+}
+
+/// Updates the status of a monitor, adding it if it isn't known yet.
+pub fn update(name: &str, f: impl FnOnce(&mut MonitorStatus)) {
+    LazyLock::force(&STARTED);
+    let mut status = STATUS.lock().unwrap();
+    f(status.monitors.entry(name.to_owned()).or_default());
+}
+
+/// Sets how many notifications the notification config `name` is waiting to aggregate or
+/// deliver.
+pub fn set_queue(name: &str, len: usize) {
+    STATUS.lock().unwrap().queues.insert(name.to_owned(), len);
+}
+
+/// Counts notifications that the notification config `name` dropped.
+pub fn add_dropped(name: &str, count: u64) {
+    *STATUS
+        .lock()
+        .unwrap()
+        .dropped
+        .entry(name.to_owned())
+        .or_default() += count;
+}
+
+/// Returns a copy of the status of everything.
+pub fn snapshot() -> Status {
+    STATUS.lock().unwrap().clone()
+}
+
+/// Returns the counters of a monitor as a table, for the local variable `stats`.
+pub fn stats(name: &str) -> Value {
+    let status = STATUS.lock().unwrap();
+    let monitor = status.monitors.get(name).cloned().unwrap_or_default();
+    let mut table = Table::from_iter([
+        ("lines".into(), Value::Integer(monitor.lines as i64)),
+        ("matches".into(), Value::Integer(monitor.matches as i64)),
+        ("execs".into(), Value::Integer(monitor.execs as i64)),
+        (
+            "exec_failures".into(),
+            Value::Integer(monitor.exec_failures as i64),
+        ),
+    ]);
+    if let Some(last_match) = monitor.last_match {
+        table.insert("last_match".into(), Value::Integer(last_match as i64));
+    }
+    Value::Table(table)
+}
+
+/// Returns the state of each monitor of a tenant as a table, by the name of the monitor without
+/// its tenant, for `composite`.
+pub fn variables(tenant: Option<&str>) -> HashMap<String, Value> {
+    let now = now();
+    let status = STATUS.lock().unwrap();
+    let mut variables = HashMap::new();
+    for (name, monitor) in &status.monitors {
+        let name = match tenant {
+            None => name.as_str(),
+            Some(tenant) => match name
+                .strip_prefix(tenant)
+                .and_then(|name| name.strip_prefix('.'))
+            {
+                None => continue,
+                Some(name) => name,
+            },
+        };
+        let since = |time: Option<u64>| {
+            let time = time.unwrap_or(*STARTED);
+            Value::Integer(now.saturating_sub(time) as i64)
+        };
+        let table = Table::from_iter([
+            ("firing".into(), Value::Boolean(monitor.firing)),
+            ("matches".into(), Value::Integer(monitor.matches as i64)),
+            ("since_match".into(), since(monitor.last_match)),
+            ("since_event".into(), since(monitor.last_event)),
+        ]);
+        variables.insert(name.to_owned(), Value::Table(table));
+    }
+    variables
+}
+
+/// Returns the current time in seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,269 @@
+//! `source = "syslog"`, which receives syslog messages over UDP and TCP.
+
+use std::{collections::HashMap, net::SocketAddr};
+
+use anyhow::{bail, Result};
+use log::{debug, error, warn};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, BufReader},
+    net::{TcpListener, TcpStream, UdpSocket},
+    sync::mpsc::Sender,
+};
+use toml::Value;
+
+use crate::{
+    error::{Error, ErrorKind},
+    monitor::Event,
+    source::{BoxFuture, Source},
+};
+
+const FACILITIES: [&str; 24] = [
+    "kern",
+    "user",
+    "mail",
+    "daemon",
+    "auth",
+    "syslog",
+    "lpr",
+    "news",
+    "uucp",
+    "cron",
+    "authpriv",
+    "ftp",
+    "ntp",
+    "security",
+    "console",
+    "solaris-cron",
+    "local0",
+    "local1",
+    "local2",
+    "local3",
+    "local4",
+    "local5",
+    "local6",
+    "local7",
+];
+
+const SEVERITIES: [&str; 8] = [
+    "emerg", "alert", "crit", "err", "warning", "notice", "info", "debug",
+];
+
+/// Messages longer than this are truncated, and TCP frames longer than this are rejected.
+const MAX_MESSAGE_LEN: usize = 64 * 1024;
+/// The longest octet count a TCP frame can start with, including the space after it.
+const MAX_PREFIX_LEN: usize = 16;
+
+/// Receives syslog messages (RFC 3164 or RFC 5424) over UDP and TCP.
+pub struct SyslogListener {
+    name: String,
+    udp: UdpSocket,
+    tcp: TcpListener,
+}
+
+impl SyslogListener {
+    /// Binds both UDP and TCP on `addr`, reporting errors as the monitor `name`.
+    pub async fn new(name: String, addr: SocketAddr) -> Result<Self> {
+        let bind_err = |err| {
+            Error::new(
+                ErrorKind::SyslogBind,
+                format!("Failed to bind syslog listener to {addr}: {err}"),
+            )
+        };
+        let udp = UdpSocket::bind(addr).await.map_err(bind_err)?;
+        let tcp = TcpListener::bind(addr).await.map_err(bind_err)?;
+        Ok(Self { name, udp, tcp })
+    }
+
+    async fn start(self, event_tx: Sender<Event>) -> Result<()> {
+        let Self { name, udp, tcp } = self;
+
+        let udp_name = name.clone();
+        let udp_tx = event_tx.clone();
+        tokio::spawn(async move {
+            let mut buf = vec![0; MAX_MESSAGE_LEN];
+            loop {
+                let (len, peer) = match udp.recv_from(&mut buf).await {
+                    Ok(received) => received,
+                    Err(err) => {
+                        error!("[{udp_name}] Failed to receive syslog message: {err}");
+                        continue;
+                    }
+                };
+                let message = String::from_utf8_lossy(&buf[..len]);
+                if udp_tx.send(parse(&message, peer)).await.is_err() {
+                    return;
+                }
+            }
+        });
+
+        loop {
+            let (stream, peer) = tcp.accept().await?;
+            debug!("[{name}] Accepted syslog connection from {peer}");
+            let name = name.clone();
+            let event_tx = event_tx.clone();
+            tokio::spawn(async move {
+                if let Err(err) = read_stream(stream, peer, event_tx).await {
+                    warn!("[{name}] Syslog connection from {peer}: {err}");
+                }
+            });
+        }
+    }
+}
+
+impl Source for SyslogListener {
+    fn kind(&self) -> &'static str {
+        "source"
+    }
+
+    fn run(self: Box<Self>, event_tx: Sender<Event>) -> BoxFuture<'static, Result<()>> {
+        Box::pin(self.start(event_tx))
+    }
+}
+
+/// Reads messages framed either by octet counting or by newlines (RFC 6587).
+async fn read_stream(stream: TcpStream, peer: SocketAddr, event_tx: Sender<Event>) -> Result<()> {
+    let mut reader = BufReader::new(stream);
+    loop {
+        let buf = reader.fill_buf().await?;
+        let Some(first) = buf.first() else {
+            return Ok(());
+        };
+
+        // Both reads are limited, so a peer can't make ramon buffer an endless frame.
+        let mut frame = Vec::new();
+        if first.is_ascii_digit() {
+            (&mut reader)
+                .take(MAX_PREFIX_LEN as u64)
+                .read_until(b' ', &mut frame)
+                .await?;
+            if frame.last() != Some(&b' ') {
+                bail!("Invalid frame length.");
+            }
+            let len = match std::str::from_utf8(&frame)?.trim_end().parse::<usize>() {
+                Ok(len) if len <= MAX_MESSAGE_LEN => len,
+                _ => bail!("Invalid frame length."),
+            };
+            frame = vec![0; len];
+            reader.read_exact(&mut frame).await?;
+        } else {
+            (&mut reader)
+                .take(MAX_MESSAGE_LEN as u64 + 1)
+                .read_until(b'\n', &mut frame)
+                .await?;
+            if frame.len() > MAX_MESSAGE_LEN && frame.last() != Some(&b'\n') {
+                bail!("Frame longer than {MAX_MESSAGE_LEN} bytes.");
+            }
+        }
+
+        let message = String::from_utf8_lossy(&frame);
+        let message = message.trim_end_matches(['\r', '\n']);
+        if message.is_empty() {
+            continue;
+        }
+        if event_tx.send(parse(message, peer)).await.is_err() {
+            return Ok(());
+        }
+    }
+}
+
+/// Parses a syslog message into its text and its header fields. Messages without a valid header
+/// are passed through as-is.
+fn parse(message: &str, peer: SocketAddr) -> Event {
+    let message = message.trim_end_matches(['\r', '\n', '\0']);
+    let mut variables = HashMap::new();
+    variables.insert("peer".into(), peer.ip().to_string().into());
+
+    let Some((priority, rest)) = message
+        .strip_prefix('<')
+        .and_then(|message| message.split_once('>'))
+        .and_then(|(priority, rest)| Some((priority.parse::<usize>().ok()?, rest)))
+        .filter(|(priority, _)| *priority < FACILITIES.len() * 8)
+    else {
+        return Event::NewMessage(message.to_owned(), variables);
+    };
+    variables.insert("priority".into(), (priority as i64).into());
+    variables.insert("facility".into(), FACILITIES[priority / 8].into());
+    variables.insert("severity".into(), SEVERITIES[priority % 8].into());
+
+    let text = match rest.strip_prefix("1 ") {
+        Some(rest) => parse_rfc5424(rest, &mut variables),
+        None => parse_rfc3164(rest, &mut variables),
+    };
+    Event::NewMessage(text.to_owned(), variables)
+}
+
+/// `TIMESTAMP HOSTNAME APP-NAME PROCID MSGID STRUCTURED-DATA MSG`
+fn parse_rfc5424<'a>(rest: &'a str, variables: &mut HashMap<String, Value>) -> &'a str {
+    let mut fields = rest.splitn(6, ' ');
+    for name in ["timestamp", "hostname", "app_name", "proc_id", "msg_id"] {
+        match fields.next() {
+            None => return "",
+            Some("-") => {}
+            Some(field) => {
+                variables.insert(name.into(), field.into());
+            }
+        }
+    }
+    let rest = fields.next().unwrap_or_default();
+    let text = match rest.strip_prefix('-') {
+        Some(text) => text,
+        None => skip_structured_data(rest),
+    };
+    let text = text.strip_prefix(' ').unwrap_or(text);
+    // Strip the UTF-8 byte order mark.
+    text.strip_prefix('\u{feff}').unwrap_or(text)
+}
+
+/// Returns the text after the structured data elements (e.g. `[id key="value"]`).
+fn skip_structured_data(rest: &str) -> &str {
+    let mut in_element = false;
+    let mut in_value = false;
+    let mut escaped = false;
+    for (i, c) in rest.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_value => escaped = true,
+            '"' if in_element => in_value = !in_value,
+            '[' if !in_value => in_element = true,
+            ']' if !in_value => in_element = false,
+            ' ' if !in_element => return &rest[i..],
+            _ => {}
+        }
+    }
+    ""
+}
+
+/// `Mmm dd hh:mm:ss HOSTNAME TAG[PID]: MSG`
+fn parse_rfc3164<'a>(rest: &'a str, variables: &mut HashMap<String, Value>) -> &'a str {
+    let mut rest = rest;
+    // The timestamp is always 15 characters (e.g. `Jan  2 15:04:05`).
+    if rest.len() > 16
+        && rest.is_char_boundary(15)
+        && rest.as_bytes()[15] == b' '
+        && rest.as_bytes()[9] == b':'
+    {
+        variables.insert("timestamp".into(), rest[..15].into());
+        rest = &rest[16..];
+        if let Some((hostname, after)) = rest.split_once(' ') {
+            variables.insert("hostname".into(), hostname.into());
+            rest = after;
+        }
+    }
+
+    let Some((tag, text)) = rest.split_once(": ") else {
+        return rest;
+    };
+    if tag.contains(' ') {
+        return rest;
+    }
+    match tag.split_once('[') {
+        Some((app_name, proc_id)) => {
+            variables.insert("app_name".into(), app_name.into());
+            variables.insert("proc_id".into(), proc_id.trim_end_matches(']').into());
+        }
+        None => {
+            variables.insert("app_name".into(), tag.into());
+        }
+    }
+    text
+}
@@ -0,0 +1,176 @@
+//! Reports readiness and status to systemd, and pings its watchdog (`sd_notify`). Also starts,
+//! stops, and restarts units over D-Bus for the `systemd` action.
+
+use std::{env, fmt, process, str::FromStr, time::Duration};
+
+use anyhow::{bail, Result};
+
+/// How long to wait for a job to finish, such as a unit restarting.
+#[cfg(target_os = "linux")]
+const JOB_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Sends newline-separated assignments (e.g. `READY=1`) to the socket in `$NOTIFY_SOCKET`. Does
+/// nothing if ramon wasn't started by systemd with `Type=notify`.
+#[cfg(target_os = "linux")]
+pub fn notify(state: &str) {
+    use std::{
+        os::{
+            linux::net::SocketAddrExt,
+            unix::net::{SocketAddr, UnixDatagram},
+        },
+        path::Path,
+    };
+
+    let Some(socket_path) = env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    let send = || {
+        let path = socket_path.as_encoded_bytes();
+        // A leading `@` refers to the abstract namespace.
+        let addr = match path.strip_prefix(b"@") {
+            Some(name) => SocketAddr::from_abstract_name(name)?,
+            None => SocketAddr::from_pathname(Path::new(&socket_path))?,
+        };
+        UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &addr)
+    };
+    if let Err(err) = send() {
+        log::warn!("Failed to notify systemd: {err}");
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn notify(_state: &str) {}
+
+/// Returns how often to ping the watchdog, which is half of `WatchdogSec`, if it's enabled for
+/// this process.
+pub fn watchdog_interval() -> Option<Duration> {
+    let timeout = env::var("WATCHDOG_USEC").ok()?.parse::<u64>().ok()?;
+    if let Ok(pid) = env::var("WATCHDOG_PID") {
+        if pid.parse() != Ok(process::id()) {
+            return None;
+        }
+    }
+    Some(Duration::from_micros(timeout / 2))
+}
+
+/// What the `systemd` action does to the unit.
+#[derive(Clone, Copy)]
+pub enum Operation {
+    /// Starts the unit.
+    Start,
+    /// Stops the unit.
+    Stop,
+    /// Restarts the unit.
+    Restart,
+    /// Reloads the configuration of the unit.
+    Reload,
+}
+
+impl Operation {
+    /// The method of `org.freedesktop.systemd1.Manager` that performs the operation.
+    #[cfg(target_os = "linux")]
+    fn method(self) -> &'static str {
+        match self {
+            Operation::Start => "StartUnit",
+            Operation::Stop => "StopUnit",
+            Operation::Restart => "RestartUnit",
+            Operation::Reload => "ReloadUnit",
+        }
+    }
+}
+
+impl FromStr for Operation {
+    type Err = anyhow::Error;
+
+    fn from_str(operation: &str) -> Result<Self> {
+        Ok(match operation {
+            "start" => Operation::Start,
+            "stop" => Operation::Stop,
+            "restart" => Operation::Restart,
+            "reload" => Operation::Reload,
+            _ => bail!("Unknown operation {operation:?}."),
+        })
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let operation = match self {
+            Operation::Start => "start",
+            Operation::Stop => "stop",
+            Operation::Restart => "restart",
+            Operation::Reload => "reload",
+        };
+        f.write_str(operation)
+    }
+}
+
+/// The `systemd` action: an operation on the unit named by a template.
+pub struct UnitAction {
+    /// What to do to the unit.
+    pub operation: Operation,
+    /// The name of the unit (template), like `nginx.service`.
+    pub unit: String,
+}
+
+/// Warns if the `systemd` action is unlikely to be allowed. Managing units requires root, or a
+/// polkit rule that grants `org.freedesktop.systemd1.manage-units`.
+pub fn check_permission(monitor: &str) {
+    #[cfg(not(windows))]
+    // SAFETY: `geteuid` has no preconditions.
+    if unsafe { libc::geteuid() } != 0 {
+        log::warn!(
+            "[{monitor}] ramon isn't running as root, so the `systemd` action requires polkit to grant org.freedesktop.systemd1.manage-units."
+        );
+    }
+    #[cfg(windows)]
+    let _ = monitor;
+}
+
+/// Performs the operation on `unit` and waits for its job to finish. Returns the result of the job
+/// (e.g. `done` or `failed`).
+#[cfg(target_os = "linux")]
+pub async fn manage(operation: Operation, unit: &str) -> Result<String> {
+    use anyhow::anyhow;
+    use futures_util::StreamExt;
+    use zbus::{zvariant::OwnedObjectPath, Connection, Proxy};
+
+    let connection = Connection::system()
+        .await
+        .map_err(|err| anyhow!("Failed to connect to the system bus: {err}"))?;
+    let manager = Proxy::new(
+        &connection,
+        "org.freedesktop.systemd1",
+        "/org/freedesktop/systemd1",
+        "org.freedesktop.systemd1.Manager",
+    )
+    .await?;
+    // Signals are only sent to subscribed clients. Subscribing before starting the job ensures that
+    // its removal isn't missed.
+    manager.call::<_, _, ()>("Subscribe", &()).await?;
+    let mut job_removed = manager.receive_signal("JobRemoved").await?;
+    let job: OwnedObjectPath = manager
+        .call(operation.method(), &(unit, "replace"))
+        .await
+        .map_err(|err| anyhow!("Failed to {operation} {unit}: {err}"))?;
+
+    let wait = async {
+        while let Some(signal) = job_removed.next().await {
+            let (_, removed, _, result) = signal
+                .body()
+                .deserialize::<(u32, OwnedObjectPath, String, String)>()?;
+            if removed == job {
+                return Ok(result);
+            }
+        }
+        bail!("The system bus closed the connection.")
+    };
+    tokio::time::timeout(JOB_TIMEOUT, wait)
+        .await
+        .map_err(|_| anyhow!("Timed out waiting for {unit} to {operation}."))?
+}
+
+#[cfg(not(target_os = "linux"))]
+pub async fn manage(_operation: Operation, _unit: &str) -> Result<String> {
+    bail!("The `systemd` action is only supported on Linux.")
+}
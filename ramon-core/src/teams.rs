@@ -0,0 +1,97 @@
+//! Sends notifications to a Microsoft Teams incoming webhook as Adaptive Cards.
+
+use anyhow::Result;
+use serde_json::{json, Value};
+
+use crate::{
+    config::{Notification, Severity},
+    error::{Error, ErrorKind},
+    notifier::{Delivery, Notifier},
+    source::BoxFuture,
+};
+
+/// The `teams` channel.
+pub struct TeamsConfig {
+    /// The URL of an incoming webhook.
+    pub url: String,
+}
+
+impl Notifier for TeamsConfig {
+    fn channel(&self) -> &'static str {
+        "teams"
+    }
+
+    fn send<'a>(
+        &'a self,
+        notification: &'a Notification,
+        _delivery: &'a Delivery,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(send(notification, &self.url))
+    }
+}
+
+async fn send(notification: &Notification, url: &str) -> Result<()> {
+    let res = reqwest::Client::new()
+        .post(url)
+        .json(&card(notification))
+        .send()
+        .await
+        .and_then(|res| res.error_for_status());
+    match res {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            Err(Error::new(ErrorKind::Teams, format!("Failed to post to Teams: {err}")).into())
+        }
+    }
+}
+
+/// Builds a message with an Adaptive Card whose title is colored by severity.
+fn card(notification: &Notification) -> Value {
+    let color = match notification.severity {
+        Severity::Info => "Accent",
+        Severity::Warning => "Warning",
+        Severity::Critical => "Attention",
+    };
+    let mut facts = Vec::new();
+    if !notification.monitor.is_empty() {
+        facts.push(json!({ "title": "Monitor", "value": notification.monitor }));
+    }
+    facts.push(json!({ "title": "Severity", "value": notification.severity.as_str() }));
+
+    let mut body = vec![
+        json!({
+            "type": "TextBlock",
+            "text": notification.title,
+            "size": "Medium",
+            "weight": "Bolder",
+            "color": color,
+            "wrap": true,
+        }),
+        json!({ "type": "FactSet", "facts": facts }),
+    ];
+    if !notification.body.is_empty() {
+        body.push(json!({ "type": "TextBlock", "text": notification.body, "wrap": true }));
+    }
+    if let Some(highlight) = &notification.highlight {
+        body.push(json!({
+            "type": "TextBlock",
+            "text": highlight.plain(),
+            "fontType": "Monospace",
+            "wrap": true,
+        }));
+    }
+
+    json!({
+        "type": "message",
+        "attachments": [{
+            "contentType": "application/vnd.microsoft.card.adaptive",
+            "content": {
+                "$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+                "type": "AdaptiveCard",
+                "version": "1.4",
+                "body": body,
+                "msteams": { "width": "Full" },
+            },
+        }],
+    })
+}
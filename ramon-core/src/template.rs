@@ -0,0 +1,127 @@
+//! The template engine that renders titles, bodies, commands, and emails.
+
+use std::{collections::HashMap, error::Error};
+
+use anyhow::Result;
+use tera::{
+    ast::{ExprVal, Node},
+    Context, Template, Tera,
+};
+use toml::Value;
+
+use crate::{
+    aggregator::format_utc,
+    config::Notification,
+    dns,
+    error::{Error as RamonError, ErrorKind},
+};
+
+/// The name of the template for the HTML body of emails. It ends with `.html` so that variables are
+/// escaped.
+pub const EMAIL_TEMPLATE: &str = "email.html";
+/// The default HTML body of emails, which lists aggregated notifications in a table.
+pub const DEFAULT_EMAIL_TEMPLATE: &str = include_str!("email.html");
+
+/// The name of the template for lines appended to the `file` of a notification config.
+pub const FILE_TEMPLATE: &str = "file";
+
+/// Returns the keys of the webhook payload, along with `time`, the formatted timestamp.
+pub fn notification_value(
+    notification: &Notification,
+) -> Result<serde_json::Value, serde_json::Error> {
+    let mut value = serde_json::to_value(notification)?;
+    value["time"] = format_utc(notification.timestamp).into();
+    Ok(value)
+}
+
+/// Creates a template engine with Ramon's custom filters registered.
+pub fn new_engine() -> Tera {
+    let mut tera = Tera::default();
+    tera.register_filter("json", json);
+    tera
+}
+
+/// Parses `template` and registers it under `name`.
+pub fn add(tera: &mut Tera, name: &str, template: &str) -> Result<()> {
+    tera.add_raw_template(name, template).map_err(|err| {
+        RamonError::new(
+            ErrorKind::Template,
+            format!("Failed to parse template `{name}`: {}", describe(&err)),
+        )
+        .into()
+    })
+}
+
+/// Renders the template registered under `name`.
+pub fn render(tera: &Tera, name: &str, context: &Context) -> Result<String> {
+    tera.render(name, context).map_err(|err| {
+        RamonError::new(
+            ErrorKind::Template,
+            format!("Failed to render template `{name}`: {}", describe(&err)),
+        )
+        .into()
+    })
+}
+
+/// Builds the template context. Local variables are accessible by name, and global variables
+/// are accessible through `var` (e.g. `{{ var.nginx_log }}`). The variables describing the host
+/// are accessible by name too, unless a local variable has the same name.
+pub fn context(local: &HashMap<String, Value>, global: &HashMap<String, Value>) -> Context {
+    let mut context = Context::new();
+    for (name, value) in dns::HOST.iter() {
+        context.insert(name, value);
+    }
+    for (name, value) in local {
+        context.insert(name, value);
+    }
+    context.insert("var", global);
+    context
+}
+
+/// Returns the variables that `template` outputs directly, such as `ip` for `{{ ip }}` or
+/// `var.count` for `{{ var.count }}`. Variables with a `default` filter or within tags (e.g.
+/// `{% if %}`) are left out, since they may be optional.
+pub fn variables(template: &str) -> Vec<String> {
+    // Templates that fail to parse are reported when they're added.
+    let Ok(template) = Template::new("", None, template) else {
+        return Vec::new();
+    };
+    template
+        .ast
+        .into_iter()
+        .filter_map(|node| match node {
+            Node::VariableBlock(_, expr) if !expr.has_default_filter() => match expr.val {
+                ExprVal::Ident(ident) => Some(ident),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns whether `template` has variables or tags, so that it renders differently than it's
+/// written. Text that isn't a valid template, such as the Go templates of `docker --format`, has
+/// neither.
+pub fn has_placeholders(template: &str) -> bool {
+    Template::new("", None, template).is_ok_and(|template| {
+        template
+            .ast
+            .iter()
+            .any(|node| !matches!(node, Node::Text(_)))
+    })
+}
+
+/// Tera only describes the outermost error in its `Display` impl, which is rarely the useful part.
+fn describe(err: &tera::Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        message += &format!(": {err}");
+        source = err.source();
+    }
+    message
+}
+
+fn json(value: &tera::Value, _: &HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+    Ok(tera::Value::String(serde_json::to_string(value)?))
+}
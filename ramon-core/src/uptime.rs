@@ -0,0 +1,138 @@
+//! `uptime`, which tracks the percentage of successful checks of a periodic monitor.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use toml::Value;
+
+use crate::state::{self, Format};
+
+const HOUR: u64 = 60 * 60;
+/// One `<hour> <successful checks> <total checks>` bucket per line.
+const FORMAT: Format = Format {
+    kind: "uptime",
+    migrations: &[state::unchanged],
+    essential: false,
+};
+const WINDOWS: [(&str, u64); 3] = [("24h", 24), ("7d", 7 * 24), ("30d", 30 * 24)];
+
+/// Tracks the percentage of successful checks of a periodic monitor over the last 30 days.
+///
+/// Checks are counted in hourly buckets, which are persisted to
+/// `uptime_<monitor name>` in the cache directory each time a new hour starts.
+pub struct Uptime {
+    buckets: VecDeque<Bucket>,
+}
+
+struct Bucket {
+    /// Hours since the Unix epoch.
+    hour: u64,
+    up: u32,
+    total: u32,
+}
+
+impl Uptime {
+    /// Loads the history of checks of the monitor `name`.
+    pub async fn load(name: &str) -> Result<Self> {
+        let mut buckets = VecDeque::new();
+        if let Some(contents) = FORMAT.read(name).await? {
+            for line in contents.lines() {
+                let fields = line
+                    .split(' ')
+                    .map(str::parse)
+                    .collect::<Result<Vec<u64>, _>>();
+                if let Ok([hour, up, total]) = fields.as_deref() {
+                    buckets.push_back(Bucket {
+                        hour: *hour,
+                        up: *up as u32,
+                        total: *total as u32,
+                    });
+                }
+            }
+        }
+        let mut uptime = Self { buckets };
+        uptime.prune(current_hour());
+        Ok(uptime)
+    }
+
+    /// Records the result of a check. Returns true if a new hour started, in which case the
+    /// history should be stored.
+    pub fn record(&mut self, up: bool) -> bool {
+        let hour = current_hour();
+        let new_hour = self.buckets.back().is_none_or(|bucket| bucket.hour != hour);
+        if new_hour {
+            self.buckets.push_back(Bucket {
+                hour,
+                up: 0,
+                total: 0,
+            });
+            self.prune(hour);
+        }
+
+        let bucket = self.buckets.back_mut().unwrap();
+        bucket.total += 1;
+        if up {
+            bucket.up += 1;
+        }
+
+        new_hour
+    }
+
+    /// Returns the percentage of successful checks within the last `hours` hours.
+    pub fn percentage(&self, hours: u64) -> Option<f64> {
+        let since = current_hour().saturating_sub(hours);
+        let (up, total) = self
+            .buckets
+            .iter()
+            .filter(|bucket| bucket.hour > since)
+            .fold((0, 0), |(up, total), bucket| {
+                (up + bucket.up as u64, total + bucket.total as u64)
+            });
+        match total {
+            0 => None,
+            total => Some(up as f64 * 100.0 / total as f64),
+        }
+    }
+
+    /// Returns `uptime_24h`, `uptime_7d`, and `uptime_30d` for use in templates.
+    pub fn variables(&self) -> HashMap<String, Value> {
+        WINDOWS
+            .iter()
+            .filter_map(|(window, hours)| {
+                let percentage = self.percentage(*hours)?;
+                Some((format!("uptime_{window}"), Value::Float(percentage)))
+            })
+            .collect()
+    }
+
+    /// Saves the history of checks of the monitor `name`.
+    pub async fn store(&self, name: &str) -> Result<()> {
+        let contents = self
+            .buckets
+            .iter()
+            .map(|bucket| format!("{} {} {}\n", bucket.hour, bucket.up, bucket.total))
+            .collect::<String>();
+        FORMAT.write(name, &contents).await
+    }
+
+    fn prune(&mut self, hour: u64) {
+        let (_, max_hours) = WINDOWS[WINDOWS.len() - 1];
+        while self
+            .buckets
+            .front()
+            .is_some_and(|bucket| bucket.hour + max_hours <= hour)
+        {
+            self.buckets.pop_front();
+        }
+    }
+}
+
+fn current_hour() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() / HOUR)
+        .unwrap_or_default()
+}
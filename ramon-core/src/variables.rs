@@ -0,0 +1,189 @@
+//! The global variables of a tenant, which the `set` and `push` actions can change while ramon
+//! runs.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Result};
+use log::{debug, warn};
+use tokio::sync::mpsc::Sender;
+use toml::Value;
+
+use crate::{
+    clock::SharedClock,
+    expression::{Expression, Literal},
+    monitor::Event,
+};
+
+/// The global variables of a tenant, shared by its monitors.
+pub type Globals = Arc<Mutex<Variables>>;
+
+/// The global variables of a tenant, and the timers that expire them.
+#[derive(Default)]
+pub struct Variables {
+    /// The value of each variable, by name.
+    pub values: HashMap<String, Value>,
+    /// The ID of the pending expiry of each variable set with a TTL. Setting the variable again
+    /// replaces or removes its ID, so the old timer leaves it alone.
+    expiries: HashMap<String, u64>,
+    next_expiry_id: u64,
+    /// The monitors to send an event to when a variable expires, by variable.
+    listeners: HashMap<String, Vec<Sender<Event>>>,
+}
+
+impl Variables {
+    /// Creates the global variables of a tenant with their initial values.
+    pub fn new(values: HashMap<String, Value>) -> Globals {
+        Arc::new(Mutex::new(Self {
+            values,
+            ..Default::default()
+        }))
+    }
+
+    /// Sends an event to `event_tx` whenever `variable` expires.
+    pub fn on_expire(&mut self, variable: String, event_tx: Sender<Event>) {
+        self.listeners.entry(variable).or_default().push(event_tx);
+    }
+}
+
+/// A change to a global variable, made by `set` or `push`.
+pub struct Assignment {
+    /// The name of the global variable.
+    pub variable: String,
+    /// Computes the new value from the local and global variables.
+    pub expression: Expression,
+    /// Whether the value is appended to an array instead of replacing the variable.
+    pub push: bool,
+    /// How long until the variable is removed.
+    pub ttl: Option<Duration>,
+}
+
+/// Parses the `set` or `push` table of a monitor, whose keys are global variables and whose values
+/// are expressions, or for `set`, tables with the keys `value` and `ttl`.
+pub fn parse(key: &str, value: Value, push: bool) -> Result<Vec<Assignment>> {
+    let Value::Table(table) = value else {
+        bail!("Key `{key}` must be a table.");
+    };
+    let mut assignments = Vec::with_capacity(table.len());
+    for (variable, value) in table {
+        let (expression, ttl) = match value {
+            Value::String(expression) => (expression, None),
+            Value::Table(mut table) if !push => {
+                let expression = match table.remove("value") {
+                    None => bail!("Key `{key}.{variable}.value` must be set."),
+                    Some(Value::String(expression)) => expression,
+                    Some(_) => bail!("Key `{key}.{variable}.value` must be a string."),
+                };
+                let ttl = match table.remove("ttl") {
+                    None => None,
+                    Some(Value::String(ttl)) => Some(
+                        duration_str::parse(ttl)
+                            .map_err(|err| anyhow!("Invalid `{key}.{variable}.ttl`:\n{err}"))?,
+                    ),
+                    Some(_) => bail!("Key `{key}.{variable}.ttl` must be a string."),
+                };
+                if let Some(unknown) = table.keys().next() {
+                    bail!("Unknown key `{key}.{variable}.{unknown}`.");
+                }
+                (expression, ttl)
+            }
+            _ if push => bail!("Key `{key}.{variable}` must be a string."),
+            _ => bail!("Key `{key}.{variable}` must be a string or a table."),
+        };
+        let expression = expression
+            .parse()
+            .map_err(|err| anyhow!("Failed to parse `{key}.{variable}`: {err}"))?;
+        assignments.push(Assignment {
+            variable,
+            expression,
+            push,
+            ttl,
+        });
+    }
+    Ok(assignments)
+}
+
+/// Evaluates each assignment in order and stores the results. The variables stay locked
+/// throughout, so counters like `var.errors + 1` aren't raced by other monitors.
+pub fn assign(
+    assignments: &[Assignment],
+    local: &HashMap<String, Value>,
+    globals: &Globals,
+    clock: &SharedClock,
+) -> Result<()> {
+    let mut variables = globals.lock().unwrap();
+    for assignment in assignments {
+        let value = to_value(assignment.expression.evaluate(local, &variables.values)?);
+        let name = assignment.variable.clone();
+        if assignment.push {
+            match variables
+                .values
+                .entry(name.clone())
+                .or_insert_with(|| Value::Array(Vec::new()))
+            {
+                Value::Array(array) => array.push(value),
+                _ => bail!("Cannot push to `var.{name}` because it isn't an array."),
+            }
+            continue;
+        }
+        variables.values.insert(name.clone(), value);
+
+        let Some(ttl) = assignment.ttl else {
+            variables.expiries.remove(&name);
+            continue;
+        };
+        variables.next_expiry_id += 1;
+        let id = variables.next_expiry_id;
+        variables.expiries.insert(name.clone(), id);
+        let globals = globals.clone();
+        let clock = clock.clone();
+        tokio::spawn(async move {
+            clock.sleep_until(clock.now() + ttl).await;
+            expire(&globals, name, id).await;
+        });
+    }
+    Ok(())
+}
+
+/// Removes `name` if it hasn't been set again since the expiry with this ID was scheduled, and
+/// sends its last value to the monitors listening for its expiry.
+async fn expire(globals: &Globals, name: String, id: u64) {
+    let (value, listeners) = {
+        let mut variables = globals.lock().unwrap();
+        if variables.expiries.get(&name) != Some(&id) {
+            return;
+        }
+        variables.expiries.remove(&name);
+        let Some(value) = variables.values.remove(&name) else {
+            return;
+        };
+        let listeners = variables.listeners.get(&name).cloned().unwrap_or_default();
+        (value, listeners)
+    };
+    debug!("`var.{name}` expired.");
+
+    for event_tx in listeners {
+        let variables = HashMap::from([
+            ("variable".into(), name.as_str().into()),
+            ("value".into(), value.clone()),
+        ]);
+        if let Err(err) = event_tx.send(Event::Internal(variables)).await {
+            warn!("Failed to send the expiry of `var.{name}`: {err}");
+        }
+    }
+}
+
+/// Converts the result of an expression to a variable. Whole numbers become integers.
+fn to_value(literal: Literal) -> Value {
+    match literal {
+        Literal::Number(number) if number.fract() == 0.0 && number.abs() < i64::MAX as f64 => {
+            Value::Integer(number as i64)
+        }
+        Literal::Number(number) => Value::Float(number),
+        Literal::String(string) => Value::String(string),
+        Literal::Boolean(boolean) => Value::Boolean(boolean),
+    }
+}
@@ -0,0 +1,81 @@
+//! Posts notifications as JSON to a webhook.
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{
+    config::{Notification, WebhookConfig},
+    error::{Error, ErrorKind},
+    highlight::{self, Highlight},
+    notifier::{Delivery, Notifier},
+    source::BoxFuture,
+};
+
+impl Notifier for WebhookConfig {
+    fn channel(&self) -> &'static str {
+        "webhook"
+    }
+
+    fn send<'a>(
+        &'a self,
+        notification: &'a Notification,
+        delivery: &'a Delivery,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move { post(&for_chat(notification), self, delivery.name).await })
+    }
+
+    /// If `batch` is set, the aggregated notifications are posted as a JSON array.
+    fn send_digest<'a>(
+        &'a self,
+        digest: &'a Notification,
+        queue: &'a [Notification],
+        delivery: &'a Delivery,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            match self.batch {
+                true => {
+                    let batch = queue.iter().map(for_chat).collect::<Vec<Notification>>();
+                    post(&batch, self, delivery.name).await
+                }
+                false => post(digest, self, delivery.name).await,
+            }
+        })
+    }
+}
+
+/// Posts the payload to each URL of the webhook, reporting failures as the notification config
+/// `name`.
+async fn post(payload: &impl Serialize, webhook: &WebhookConfig, name: &str) -> Result<()> {
+    let urls = webhook.urls.resolve(name).await.map_err(|err| {
+        Error::new(
+            ErrorKind::Recipients,
+            format!("Failed to resolve webhook URLs: {err}"),
+        )
+    })?;
+    let client = reqwest::Client::new();
+    for url in urls {
+        let res = client
+            .post(&url)
+            .json(payload)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status());
+        if let Err(err) = res {
+            return Err(
+                Error::new(ErrorKind::Webhook, format!("Failed to post webhook: {err}")).into(),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Webhooks usually post to chat services, so highlights are rendered as Markdown code blocks.
+fn for_chat(notification: &Notification) -> Notification {
+    let mut notification = notification.clone();
+    notification.body = highlight::append(
+        &notification.body,
+        notification.highlight.as_ref(),
+        Highlight::markdown,
+    );
+    notification
+}
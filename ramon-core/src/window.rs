@@ -0,0 +1,71 @@
+//! Computes statistics of a numeric variable over a sliding window, for `window`.
+
+use std::{collections::VecDeque, time::Duration};
+
+use tokio::time::Instant;
+use toml::{Table, Value};
+
+/// `window`.
+pub struct WindowConfig {
+    /// The variable whose values are aggregated (e.g. `latency_ms`).
+    pub variable: String,
+    /// How long values stay in the window.
+    pub duration: Duration,
+}
+
+/// The values of a variable within the last `duration`.
+pub struct Window {
+    config: WindowConfig,
+    /// The values within the window and when they were recorded, oldest first.
+    samples: VecDeque<(Instant, f64)>,
+}
+
+impl Window {
+    /// Creates an empty window.
+    pub fn new(config: WindowConfig) -> Self {
+        Self {
+            config,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Returns the name of the variable whose values are aggregated.
+    pub fn variable(&self) -> &str {
+        &self.config.variable
+    }
+
+    /// Records a value and returns the statistics of the window as a table.
+    pub fn record(&mut self, now: Instant, value: f64) -> Value {
+        self.samples.push_back((now, value));
+        while let Some((time, _)) = self.samples.front() {
+            if now.duration_since(*time) <= self.config.duration {
+                break;
+            }
+            self.samples.pop_front();
+        }
+
+        let mut values = self
+            .samples
+            .iter()
+            .map(|(_, value)| *value)
+            .collect::<Vec<f64>>();
+        values.sort_by(f64::total_cmp);
+        let count = values.len();
+        // Nearest-rank percentiles.
+        let percentile =
+            |p: f64| values[((p / 100.0 * count as f64).ceil() as usize).clamp(1, count) - 1];
+        let table = Table::from_iter([
+            ("count".into(), Value::Integer(count as i64)),
+            ("min".into(), Value::Float(values[0])),
+            ("max".into(), Value::Float(values[count - 1])),
+            (
+                "avg".into(),
+                Value::Float(values.iter().sum::<f64>() / count as f64),
+            ),
+            ("p50".into(), Value::Float(percentile(50.0))),
+            ("p95".into(), Value::Float(percentile(95.0))),
+            ("p99".into(), Value::Float(percentile(99.0))),
+        ]);
+        Value::Table(table)
+    }
+}
@@ -0,0 +1,190 @@
+//! Cooldowns, thresholds, and aggregation, driven by a simulated clock.
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use ramon_core::{
+    clock::{SharedClock, SimulatedClock, Ticker},
+    config::{self, Severity},
+    internal::{state, status},
+    notifier::Delivery,
+    source::BoxFuture,
+    Aggregator, Monitor, Notification, NotificationConfig, Notifier,
+};
+use tokio::{
+    sync::mpsc::{channel, unbounded_channel, Receiver, UnboundedSender},
+    time::{timeout, Instant},
+};
+
+/// Builds the monitor `name` from `keys`, which reads lines only from `Monitor::replay`.
+async fn monitor(
+    name: &str,
+    keys: &str,
+) -> Result<(Monitor, Receiver<Notification>, Arc<SimulatedClock>)> {
+    state::detach();
+    let clock = Arc::new(SimulatedClock::new(Instant::now()));
+    let shared_clock: SharedClock = clock.clone();
+    let doc = format!(
+        "[notify.default]\nexec = \"true\"\n\n[monitor.{name}]\nlog = \"/dev/null\"\nnotify = \"{{{{ line }}}}\"\n{keys}"
+    );
+    let config = config::parse(&doc, &shared_clock)?;
+    let tenant = config.tenants.into_iter().next().unwrap();
+    let mut monitor_config = tenant.monitors.into_iter().next().unwrap();
+    monitor_config.log = None;
+    let (notify_tx, notify_rx) = channel(64);
+    let monitor = Monitor::new(monitor_config, notify_tx, tenant.variables, shared_clock).await?;
+    Ok((monitor, notify_rx, clock))
+}
+
+/// Returns the titles of the notifications sent so far.
+fn titles(notify_rx: &mut Receiver<Notification>) -> Vec<String> {
+    let mut titles = Vec::new();
+    while let Ok(notification) = notify_rx.try_recv() {
+        titles.push(notification.title);
+    }
+    titles
+}
+
+#[tokio::test]
+async fn cooldown() -> Result<()> {
+    let (mut monitor, mut notify_rx, clock) =
+        monitor("cooldown", "match_log = \"boom\"\ncooldown = \"1m\"").await?;
+
+    monitor.replay("boom 1".into()).await?;
+    monitor.replay("boom 2".into()).await?;
+    assert_eq!(titles(&mut notify_rx), ["boom 1"]);
+
+    clock.advance(Duration::from_secs(59));
+    monitor.replay("boom 3".into()).await?;
+    assert!(titles(&mut notify_rx).is_empty());
+
+    clock.advance(Duration::from_secs(2));
+    monitor.replay("boom 4".into()).await?;
+    assert_eq!(titles(&mut notify_rx), ["boom 4"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn threshold() -> Result<()> {
+    let (mut monitor, mut notify_rx, clock) =
+        monitor("threshold", "match_log = \"boom\"\nthreshold = \"3/1m\"").await?;
+
+    monitor.replay("boom 1".into()).await?;
+    monitor.replay("boom 2".into()).await?;
+    assert!(titles(&mut notify_rx).is_empty());
+    monitor.replay("boom 3".into()).await?;
+    assert_eq!(titles(&mut notify_rx), ["boom 3"]);
+
+    // The first two of the last three events are more than a minute before the third.
+    clock.advance(Duration::from_secs(61));
+    monitor.replay("boom 4".into()).await?;
+    monitor.replay("boom 5".into()).await?;
+    clock.advance(Duration::from_secs(61));
+    monitor.replay("boom 6".into()).await?;
+    assert!(titles(&mut notify_rx).is_empty());
+    Ok(())
+}
+
+fn firing(name: &str) -> bool {
+    status::snapshot().monitors[name].firing
+}
+
+#[tokio::test]
+async fn firing_is_held() -> Result<()> {
+    let (mut monitor, _notify_rx, clock) = monitor(
+        "firing",
+        "match_log = '(?<state>up|down)'\ncooldown = \"1m\"\nresolve = 'state == \"up\"'",
+    )
+    .await?;
+
+    monitor.replay("down".into()).await?;
+    assert!(firing("firing"));
+    monitor.replay("unrelated".into()).await?;
+    assert!(firing("firing"));
+    // Skipped by the cooldown.
+    monitor.replay("up".into()).await?;
+    assert!(firing("firing"));
+
+    clock.advance(Duration::from_secs(61));
+    monitor.replay("up".into()).await?;
+    assert!(!firing("firing"));
+    Ok(())
+}
+
+/// Records the titles of each delivery.
+struct Recorder(UnboundedSender<Vec<String>>);
+
+impl Notifier for Recorder {
+    fn channel(&self) -> &'static str {
+        "recorder"
+    }
+
+    fn send<'a>(
+        &'a self,
+        notification: &'a Notification,
+        _delivery: &'a Delivery,
+    ) -> BoxFuture<'a, Result<()>> {
+        let _ = self.0.send(vec![notification.title.clone()]);
+        Box::pin(async { Ok(()) })
+    }
+
+    fn send_digest<'a>(
+        &'a self,
+        _digest: &'a Notification,
+        queue: &'a [Notification],
+        _delivery: &'a Delivery,
+    ) -> BoxFuture<'a, Result<()>> {
+        let titles = queue
+            .iter()
+            .map(|notification| notification.title.clone())
+            .collect();
+        let _ = self.0.send(titles);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+#[tokio::test]
+async fn aggregation() -> Result<()> {
+    state::detach();
+    let clock = Arc::new(SimulatedClock::new(Instant::now()));
+    let shared_clock: SharedClock = clock.clone();
+    let (delivery_tx, mut delivery_rx) = unbounded_channel();
+    let config = NotificationConfig {
+        name: "aggregation".into(),
+        min_severity: Severity::Info,
+        group_by_monitor: false,
+        notifiers: vec![Box::new(Recorder(delivery_tx))],
+        retries: 0,
+        retry_delay: Duration::from_secs(1),
+        clock: shared_clock.clone(),
+    };
+    let mut interval = Ticker::new(shared_clock, Duration::from_secs(60 * 60));
+    // The first tick completes immediately.
+    interval.tick().await;
+    let (aggregator, notify_tx) = Aggregator::new(config, Some(interval), None);
+    aggregator.spawn();
+
+    for title in ["a", "b", "c"] {
+        notify_tx
+            .send(Notification {
+                r#type: "aggregation".into(),
+                monitor: "aggregation".into(),
+                severity: Severity::Warning,
+                title: title.into(),
+                body: String::new(),
+                timestamp: 0,
+                variables: Default::default(),
+                highlight: None,
+                resolved: false,
+            })
+            .await?;
+    }
+    // Wait until the aggregator has received every notification.
+    drop(notify_tx.reserve().await?);
+    assert!(delivery_rx.try_recv().is_err());
+
+    clock.advance(Duration::from_secs(60 * 60));
+    let digest = timeout(Duration::from_secs(5), delivery_rx.recv()).await?;
+    assert_eq!(digest.unwrap(), ["a", "b", "c"]);
+    Ok(())
+}
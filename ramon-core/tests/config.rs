@@ -0,0 +1,38 @@
+//! Keys that the config rejects.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use ramon_core::{
+    clock::{SharedClock, SystemClock},
+    config::{self, Config},
+};
+
+fn parse(monitor: &str) -> Result<Config> {
+    let clock: SharedClock = Arc::new(SystemClock);
+    let doc = format!(
+        "[notify.default]\nexec = \"true\"\n\n[monitor.test]\nlog = \"/dev/null\"\nmatch_log = '(?<ip>\\S+)'\n{monitor}"
+    );
+    config::parse(&doc, &clock)
+}
+
+#[tokio::test]
+async fn shell_exec_is_not_a_template() {
+    let err = parse("exec = \"echo {{ ip }}\"").err().unwrap();
+    assert!(err
+        .to_string()
+        .contains("monitor.test.exec: shell commands can't use"));
+    let err = parse("actions = [{ exec = \"{% if ip %}true{% endif %}\" }]")
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("shell commands can't use"));
+}
+
+#[tokio::test]
+async fn shell_exec_gets_variables_from_the_environment() -> Result<()> {
+    parse("exec = 'echo \"$ip\"'")?;
+    parse("exec = [\"echo\", \"{{ ip }}\"]")?;
+    // Not a Tera template, so it's passed to the shell as is.
+    parse("exec = \"docker ps --format '{{.Names}}'\"")?;
+    Ok(())
+}
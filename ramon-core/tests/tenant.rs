@@ -0,0 +1,107 @@
+//! Routing the monitors of a tenant to their notification configs.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use ramon_core::{
+    clock::{SharedClock, SystemClock},
+    config,
+};
+
+#[tokio::test]
+async fn aggregator_for() -> Result<()> {
+    let clock: SharedClock = Arc::new(SystemClock);
+    let config = config::parse(
+        r#"
+        [notify.default]
+        exec = "true"
+
+        [notify.pager]
+        exec = "true"
+
+        [monitor.quiet]
+        log = "/dev/null"
+
+        [monitor.loud]
+        log = "/dev/null"
+        notify = { type = "pager", title = "{{ line }}" }
+        "#,
+        &clock,
+    )?;
+    let tenant = &config.tenants[0];
+    for monitor in &tenant.monitors {
+        let expected = match monitor.name.as_str() {
+            "quiet" => "default",
+            _ => "pager",
+        };
+        let aggregator_tx = tenant.aggregator_for(monitor)?;
+        assert!(aggregator_tx.same_channel(&tenant.aggregator_txs[expected]));
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn severity_routes() -> Result<()> {
+    let clock: SharedClock = Arc::new(SystemClock);
+    let config = config::parse(
+        r#"
+        [notify.default]
+        exec = "true"
+
+        [notify.pager]
+        exec = "true"
+
+        [notify.oncall]
+        exec = "true"
+        severity = ["critical"]
+
+        [notify.warning]
+        exec = "true"
+
+        [monitor.paged]
+        log = "/dev/null"
+        severity = "critical"
+        notify = { type = "pager", title = "{{ line }}" }
+
+        [monitor.warned]
+        log = "/dev/null"
+        severity = "warning"
+        notify = { title = "{{ line }}" }
+
+        [monitor.informed]
+        log = "/dev/null"
+        severity = "info"
+        notify = { type = "pager", title = "{{ line }}" }
+        "#,
+        &clock,
+    )?;
+    let tenant = &config.tenants[0];
+    for monitor in &tenant.monitors {
+        let expected = match monitor.name.as_str() {
+            "paged" => "oncall",
+            "warned" => "warning",
+            _ => "pager",
+        };
+        let aggregator_tx = tenant.aggregator_for(monitor)?;
+        assert!(aggregator_tx.same_channel(&tenant.aggregator_txs[expected]));
+    }
+
+    let err = config::parse(
+        r#"
+        [notify.default]
+        exec = "true"
+        severity = ["info", "critical"]
+
+        [notify.oncall]
+        exec = "true"
+        severity = ["critical"]
+        "#,
+        &clock,
+    )
+    .err()
+    .unwrap();
+    assert!(err
+        .to_string()
+        .contains("notify.oncall.severity: `critical` is already routed to `default`"));
+    Ok(())
+}
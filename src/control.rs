@@ -0,0 +1,233 @@
+//! A Unix socket that lets `ramon status` and `ramon silence` query and control a running
+//! instance. Each connection sends one command on a line and receives one JSON response.
+
+use std::{env, fs, os::unix::net, path::PathBuf, sync::OnceLock};
+
+use anyhow::{anyhow, bail, Result};
+use log::{debug, warn};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+};
+
+use ramon_core::{
+    error::{Error, ErrorKind},
+    internal::{
+        aggregator::format_utc,
+        silence,
+        status::{self, Status},
+    },
+};
+
+/// The control socket set by `--socket`.
+static SOCKET_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// Uses `path` instead of the default control socket.
+pub fn set_socket_path(path: PathBuf) {
+    let _ = SOCKET_PATH.set(path);
+}
+
+/// Where the control socket is created: `/run/ramon.sock` for root, and the runtime directory of
+/// other users, which they can write to.
+fn socket_path() -> PathBuf {
+    if let Some(path) = SOCKET_PATH.get() {
+        return path.clone();
+    }
+    // SAFETY: `geteuid` has no preconditions.
+    let root = unsafe { libc::geteuid() } == 0;
+    match env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) if !root && !dir.is_empty() => PathBuf::from(dir).join("ramon.sock"),
+        _ => "/run/ramon.sock".into(),
+    }
+}
+
+pub struct ControlServer {
+    listener: UnixListener,
+}
+
+impl ControlServer {
+    /// Binds the control socket, failing with `AlreadyRunning` if another instance is listening
+    /// on it.
+    pub fn new() -> Result<Self, Error> {
+        let path = socket_path();
+        if net::UnixStream::connect(&path).is_ok() {
+            return Err(Error::new(
+                ErrorKind::AlreadyRunning,
+                format!("Another instance of ramon is listening on {path:?}"),
+            ));
+        }
+        // A socket left behind by an instance that didn't exit cleanly prevents binding.
+        let _ = fs::remove_file(&path);
+        // Only the user running ramon (usually root) can connect, from the moment the socket is
+        // created. Nothing else creates files yet, since no monitor has started.
+        // SAFETY: `umask` has no preconditions.
+        let umask = unsafe { libc::umask(0o177) };
+        let res = UnixListener::bind(&path);
+        // SAFETY: As above.
+        unsafe { libc::umask(umask) };
+        let listener = res.map_err(|err| {
+            Error::new(
+                ErrorKind::ControlSocket,
+                format!("Failed to create control socket {path:?}: {err}"),
+            )
+        })?;
+        Ok(Self { listener })
+    }
+
+    pub async fn start(self) -> Result<()> {
+        loop {
+            let (stream, _) = self.listener.accept().await?;
+            tokio::spawn(async move {
+                if let Err(err) = handle(stream).await {
+                    debug!("Control connection: {err}");
+                }
+            });
+        }
+    }
+}
+
+async fn handle(stream: UnixStream) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut command = String::new();
+    BufReader::new(reader).read_line(&mut command).await?;
+    let response = execute(command.trim()).unwrap_or_else(|err| {
+        warn!("Control command {:?}: {err}", command.trim());
+        json!({ "error": err.to_string() })
+    });
+    writer.write_all(format!("{response}\n").as_bytes()).await?;
+    Ok(())
+}
+
+fn execute(command: &str) -> Result<Value> {
+    let (command, args) = command.split_once(' ').unwrap_or((command, ""));
+    match command {
+        "status" => Ok(serde_json::to_value(status::snapshot())?),
+        // The monitor comes last, since its name may contain spaces.
+        "silence" => {
+            let (duration, monitor) = args
+                .split_once(' ')
+                .ok_or_else(|| anyhow!("Expected a duration and a monitor"))?;
+            let duration = duration_str::parse(duration)
+                .map_err(|err| anyhow!("Failed to parse duration: {err}"))?;
+            Ok(json!({ "until": silence::silence(monitor, duration)? }))
+        }
+        "unsilence" => Ok(json!({ "suppressed": silence::unsilence(args)? })),
+        _ => bail!("Unknown command {command:?}"),
+    }
+}
+
+/// Sends a command to the running instance and returns its response.
+async fn request(command: &str) -> Result<Value> {
+    let path = socket_path();
+    let stream = UnixStream::connect(&path).await.map_err(|err| {
+        Error::new(
+            ErrorKind::ControlSocket,
+            format!("Failed to connect to {path:?}: {err}"),
+        )
+    })?;
+    let (reader, mut writer) = stream.into_split();
+    writer.write_all(format!("{command}\n").as_bytes()).await?;
+    let mut response = String::new();
+    BufReader::new(reader).read_line(&mut response).await?;
+    let response: Value = serde_json::from_str(&response)?;
+    if let Some(err) = response.get("error").and_then(Value::as_str) {
+        bail!("{err}");
+    }
+    Ok(response)
+}
+
+/// Prints the status of the running instance as a table, or as JSON.
+pub async fn status(json: bool) -> Result<()> {
+    let response = request("status").await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&response)?);
+        return Ok(());
+    }
+
+    let status: Status = serde_json::from_value(response)?;
+    let mut rows = vec![[
+        "MONITOR".to_owned(),
+        "LAST EVENT".into(),
+        "CURSOR".into(),
+        "LINES".into(),
+        "MATCHES".into(),
+        "EXECS".into(),
+        "LAST EXEC".into(),
+        "SILENCED UNTIL".into(),
+    ]];
+    for (name, monitor) in status.monitors {
+        rows.push([
+            name,
+            monitor.last_event.map(format_utc).unwrap_or("-".into()),
+            monitor
+                .cursor
+                .map(|cursor| cursor.to_string())
+                .unwrap_or("-".into()),
+            monitor.lines.to_string(),
+            monitor.matches.to_string(),
+            match (monitor.exec_failures, monitor.execs_skipped) {
+                (0, 0) => monitor.execs.to_string(),
+                (failures, 0) => format!("{} ({failures} failed)", monitor.execs),
+                (0, skipped) => format!("{} ({skipped} skipped)", monitor.execs),
+                (failures, skipped) => {
+                    format!("{} ({failures} failed, {skipped} skipped)", monitor.execs)
+                }
+            },
+            monitor.last_exec.unwrap_or("-".into()),
+            monitor.silenced_until.map(format_utc).unwrap_or("-".into()),
+        ]);
+    }
+    print_table(&rows);
+
+    if !status.queues.is_empty() {
+        println!();
+        let mut rows = vec![["NOTIFY".to_owned(), "QUEUED".into()]];
+        for (name, len) in status.queues {
+            let queued = match status.dropped.get(&name) {
+                None => len.to_string(),
+                Some(dropped) => format!("{len} ({dropped} dropped)"),
+            };
+            rows.push([name, queued]);
+        }
+        print_table(&rows);
+    }
+    Ok(())
+}
+
+/// Mutes the notifications of a monitor of the running instance for `duration`.
+pub async fn silence(monitor: &str, duration: &str) -> Result<()> {
+    let response = request(&format!("silence {duration} {monitor}")).await?;
+    let until = response["until"].as_u64().unwrap_or_default();
+    println!("Silenced `{monitor}` until {}", format_utc(until));
+    Ok(())
+}
+
+pub async fn unsilence(monitor: &str) -> Result<()> {
+    let response = request(&format!("unsilence {monitor}")).await?;
+    let suppressed = response["suppressed"].as_u64().unwrap_or_default();
+    let plural = match suppressed {
+        1 => "",
+        _ => "s",
+    };
+    println!("Unsilenced `{monitor}`, which suppressed {suppressed} notification{plural}");
+    Ok(())
+}
+
+fn print_table<const N: usize>(rows: &[[String; N]]) {
+    let mut widths = [0; N];
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in rows {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect::<Vec<String>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+    }
+}
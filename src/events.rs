@@ -0,0 +1,65 @@
+//! `ramon events`, which prints the matches and notification deliveries recorded in the `events`
+//! database.
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Result};
+use serde_json::Value;
+
+use ramon_core::{
+    clock::{SharedClock, SystemClock},
+    config,
+    internal::{
+        aggregator::format_utc,
+        events::{self, EventsConfig},
+        status,
+    },
+};
+
+/// Prints the recorded events, oldest first, of `monitor` (or every monitor) within `since` (e.g.
+/// `24h`), as lines or as JSON lines.
+pub async fn run(monitor: Option<&str>, since: Option<&str>, json: bool) -> Result<()> {
+    let clock: SharedClock = Arc::new(SystemClock);
+    let config = config::load(&clock).await?;
+    let Some(EventsConfig { path, .. }) = config.events else {
+        bail!("The config doesn't set `events`, so no events are recorded.");
+    };
+    let since = match since {
+        None => 0,
+        Some(since) => {
+            let duration = duration_str::parse(since)
+                .map_err(|err| anyhow!("Failed to parse `--since`: {err}"))?;
+            status::now().saturating_sub(duration.as_secs())
+        }
+    };
+
+    for event in events::query(&path, monitor, since)? {
+        if json {
+            println!("{}", serde_json::to_string(&event)?);
+        } else {
+            println!(
+                "{}  [{}] {} {}",
+                format_utc(event.timestamp),
+                event.monitor,
+                event.event,
+                describe(&event.details)
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Describes the details of an event as `key=value` pairs.
+fn describe(details: &Value) -> String {
+    let Value::Object(details) = details else {
+        return String::new();
+    };
+    details
+        .iter()
+        .map(|(key, value)| match value {
+            Value::String(value) => format!("{key}={value:?}"),
+            value => format!("{key}={value}"),
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
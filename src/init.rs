@@ -0,0 +1,238 @@
+//! `ramon init`, which writes a starter ramon.toml with a monitor for one log file and a
+//! notification config for one channel. Each value is asked for, unless it's given by a flag or
+//! `--non-interactive` is set, and is checked as soon as it's entered.
+
+use std::{
+    io::{stdin, stdout, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use anyhow::{anyhow, bail, Result};
+use clap::Args;
+use lettre::message::Mailbox;
+use regex::Regex;
+use toml::Value;
+
+use ramon_core::{
+    clock::{SharedClock, SystemClock},
+    config,
+    internal::platform,
+};
+
+/// The sample `match_log`, which matches failed SSH logins.
+const DEFAULT_REGEX: &str = r"Failed password for (?:invalid user )?(?<user>\S+) from (?<ip>\S+)";
+
+/// The flags of `ramon init`. Values that aren't given are asked for.
+#[derive(Args)]
+pub struct InitArgs {
+    /// Don't ask for anything; values that aren't given are their defaults
+    #[arg(long)]
+    non_interactive: bool,
+    /// Overwrite the config if it exists
+    #[arg(long)]
+    force: bool,
+    /// The log file to monitor
+    #[arg(long, value_name = "FILE")]
+    log: Option<String>,
+    /// The regex of the lines to notify about
+    #[arg(long = "match", value_name = "REGEX")]
+    regex: Option<String>,
+    /// How to notify
+    #[arg(long, value_parser = ["email", "webhook", "desktop"])]
+    notify: Option<String>,
+    /// The sender of emails
+    #[arg(long, value_name = "ADDRESS")]
+    from: Option<String>,
+    /// The recipient of emails
+    #[arg(long, value_name = "ADDRESS")]
+    to: Option<String>,
+    /// The URL of the webhook
+    #[arg(long, value_name = "URL")]
+    webhook: Option<String>,
+}
+
+impl InitArgs {
+    /// Returns the value of `flag` if it's given, and otherwise asks for it with `question`. An
+    /// empty answer is `default`, which is also used without asking if `--non-interactive` is set.
+    fn get<T>(
+        &self,
+        value: &Option<String>,
+        flag: &str,
+        question: &str,
+        default: Option<&str>,
+        parse: impl Fn(&str) -> Result<T>,
+    ) -> Result<T> {
+        if let Some(value) = value {
+            return parse(value).map_err(|err| anyhow!("--{flag}: {err}"));
+        }
+        if self.non_interactive {
+            let Some(default) = default else {
+                bail!("--{flag} is required with --non-interactive");
+            };
+            return parse(default).map_err(|err| anyhow!("--{flag}: {err}"));
+        }
+        loop {
+            match default {
+                None => print!("{question}: "),
+                Some(default) => print!("{question} [{default}]: "),
+            }
+            stdout().flush()?;
+            let mut answer = String::new();
+            if stdin().read_line(&mut answer)? == 0 {
+                bail!("--{flag}: no answer was given");
+            }
+            let answer = match (answer.trim(), default) {
+                ("", Some(default)) => default,
+                ("", None) => continue,
+                (answer, _) => answer,
+            };
+            match parse(answer) {
+                Ok(value) => return Ok(value),
+                Err(err) => println!("{err}"),
+            }
+        }
+    }
+}
+
+/// A notification channel of the generated config.
+enum Channel {
+    Email { from: Mailbox, to: Mailbox },
+    Webhook(String),
+    Desktop,
+}
+
+pub async fn run(options: InitArgs) -> Result<()> {
+    let output = platform::config_path();
+    if !options.force && output.exists() {
+        bail!(
+            "{} already exists; use --force to overwrite it",
+            output.display()
+        );
+    }
+
+    let log = options.get(
+        &options.log,
+        "log",
+        "Log file to monitor",
+        Some("/var/log/auth.log"),
+        parse_log,
+    )?;
+    let regex = options.get(
+        &options.regex,
+        "match",
+        "Regex of the lines to notify about",
+        Some(DEFAULT_REGEX),
+        parse_regex,
+    )?;
+    let notify = options.get(
+        &options.notify,
+        "notify",
+        "How to notify (email, webhook, or desktop)",
+        Some("email"),
+        |notify| match notify {
+            "email" | "webhook" | "desktop" => Ok(notify.to_owned()),
+            _ => bail!("{notify:?} isn't one of email, webhook, or desktop"),
+        },
+    )?;
+    let channel = match notify.as_str() {
+        "email" => {
+            let default_from = format!("ramon@{}", hostname());
+            Channel::Email {
+                from: options.get(
+                    &options.from,
+                    "from",
+                    "Sender address",
+                    Some(&default_from),
+                    parse_mailbox,
+                )?,
+                to: options.get(&options.to, "to", "Recipient address", None, parse_mailbox)?,
+            }
+        }
+        "webhook" => Channel::Webhook(options.get(
+            &options.webhook,
+            "webhook",
+            "Webhook URL",
+            None,
+            parse_url,
+        )?),
+        _ => Channel::Desktop,
+    };
+
+    let doc = generate(&log, &regex, &channel);
+    let clock: SharedClock = Arc::new(SystemClock);
+    config::parse(&doc, &clock).map_err(|err| anyhow!("The generated config is invalid: {err}"))?;
+
+    std::fs::write(&output, doc)
+        .map_err(|err| anyhow!("Failed to write {}: {err}", output.display()))?;
+    println!("Wrote {}.", output.display());
+    println!("Run `ramon test-notify default` to send a test notification.");
+    Ok(())
+}
+
+/// Returns ramon.toml with a notification config for `channel` and a monitor for `log`, which is
+/// named after the file.
+fn generate(log: &str, regex: &str, channel: &Channel) -> String {
+    let quote = |value: &str| Value::String(value.to_owned()).to_string();
+    let name = Path::new(log)
+        .file_stem()
+        .map(|stem| {
+            stem.to_string_lossy()
+                .replace(|c: char| !c.is_alphanumeric(), "_")
+        })
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "log".into());
+
+    let mut doc = String::from("[notify.default]\n");
+    match channel {
+        Channel::Email { from, to } => {
+            doc += &format!("from = {}\n", quote(&from.to_string()));
+            doc += &format!("to = {}\n", quote(&to.to_string()));
+            doc += "# Without smtp_host, emails are sent to the SMTP server on localhost.\n";
+            doc += "# smtp_host = \"smtp.example.com\"\n";
+            doc += "# username = \"ramon\"\n";
+            doc += "# password = \"hunter2\"\n";
+        }
+        Channel::Webhook(url) => doc += &format!("webhook = {}\n", quote(url)),
+        Channel::Desktop => doc += "desktop = true\n",
+    }
+    doc += "# Combine notifications sent within 10 seconds of each other.\n";
+    doc += "aggregate = \"10s\"\n";
+    doc += &format!("\n[monitor.{name}]\n");
+    doc += &format!("log = {}\n", quote(log));
+    doc += &format!("match_log = {}\n", quote(regex));
+    doc += "notify = \"{{ match }}\"\n";
+    doc
+}
+
+fn parse_log(log: &str) -> Result<String> {
+    match std::fs::metadata(log) {
+        Ok(metadata) if metadata.is_file() => Ok(log.to_owned()),
+        Ok(_) => bail!("{log} isn't a file"),
+        Err(err) => bail!("Failed to open {log}: {err}"),
+    }
+}
+
+fn parse_regex(regex: &str) -> Result<String> {
+    Regex::new(regex).map_err(|err| anyhow!("Failed to parse the regex: {err}"))?;
+    Ok(regex.to_owned())
+}
+
+fn parse_mailbox(mailbox: &str) -> Result<Mailbox> {
+    mailbox
+        .parse()
+        .map_err(|err| anyhow!("Failed to parse {mailbox:?}: {err}"))
+}
+
+fn parse_url(url: &str) -> Result<String> {
+    let parsed =
+        reqwest::Url::parse(url).map_err(|err| anyhow!("Failed to parse {url:?}: {err}"))?;
+    if !["http", "https"].contains(&parsed.scheme()) {
+        bail!("{url:?} isn't an http or https URL");
+    }
+    Ok(url.to_owned())
+}
+
+fn hostname() -> String {
+    dns_lookup::get_hostname().unwrap_or_else(|_| "localhost".into())
+}
@@ -0,0 +1,68 @@
+//! Sets up ramon's own log, as text or as JSON lines.
+
+use std::{io::Write, sync::LazyLock, time::Instant};
+
+use clap::ValueEnum;
+use env_logger::{Builder, Env};
+use log::LevelFilter;
+use serde_json::{Map, Value};
+
+static STARTED: LazyLock<Instant> = LazyLock::new(Instant::now);
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+/// Sets up the log. `level`, if set, overrides the level of ramon's own messages set by `RUST_LOG`.
+pub fn init(format: LogFormat, level: Option<LevelFilter>) {
+    let mut builder =
+        Builder::from_env(Env::default().default_filter_or("ramon=info,ramon_core=info"));
+    if let Some(level) = level {
+        builder.filter_module("ramon", level);
+        builder.filter_module("ramon_core", level);
+    }
+    if let LogFormat::Json = format {
+        LazyLock::force(&STARTED);
+        builder.format(|buf, record| {
+            let message = record.args().to_string();
+            let target = record.target();
+            let component = target
+                .strip_prefix("ramon_core::")
+                .or_else(|| target.strip_prefix("ramon::"))
+                .unwrap_or(target);
+            let mut line = Map::new();
+            line.insert(
+                "timestamp".into(),
+                buf.timestamp_millis().to_string().into(),
+            );
+            line.insert(
+                "uptime_ms".into(),
+                (STARTED.elapsed().as_millis() as u64).into(),
+            );
+            line.insert("level".into(), record.level().as_str().into());
+            line.insert("component".into(), component.into());
+            // Messages about a monitor or notification config start with its name in brackets.
+            let message = match split_name(&message) {
+                Some((name, message)) => {
+                    let key = match component {
+                        "aggregator" => "notify",
+                        _ => "monitor",
+                    };
+                    line.insert(key.into(), name.into());
+                    message
+                }
+                None => &message,
+            };
+            line.insert("message".into(), message.into());
+            writeln!(buf, "{}", Value::Object(line))
+        });
+    }
+    builder.init();
+}
+
+fn split_name(message: &str) -> Option<(&str, &str)> {
+    let (name, message) = message.strip_prefix('[')?.split_once("] ")?;
+    Some((name, message))
+}
@@ -1,72 +1,399 @@
-mod aggregator;
-mod config;
-mod log_watcher;
-mod monitor;
+#[cfg(unix)]
+mod control;
+mod events;
+mod init;
+mod logging;
+mod replay;
+mod selftest;
+mod telemetry;
+mod test_notify;
+mod test_regex;
 
-use anyhow::{anyhow, Result};
-use log::error;
-use monitor::Monitor;
-use std::process::exit;
-use tokio::{fs::File, io::AsyncReadExt};
+use anyhow::{anyhow, bail, Result};
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use init::InitArgs;
+use log::{error, info, warn, LevelFilter};
+use logging::LogFormat;
+use ramon_core::{
+    clock::{SharedClock, SystemClock},
+    config::{self, MonitorConfig, Tenant},
+    error::{Error, ErrorKind},
+    internal::{
+        api, audit, exec_limit, health, ingest::IngestServer, meta, overrides, platform, state,
+        systemd,
+    },
+    Monitor,
+};
+use std::{collections::HashSet, mem, path::PathBuf, process::exit, sync::Arc};
+use tokio::{
+    select,
+    task::JoinSet,
+    time::{interval, Instant, Interval},
+};
+
+/// A lightweight server monitoring framework.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// The config file, which the directory `ramon.d` next to it is merged into [default:
+    /// /etc/ramon.toml]
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// The control socket of the running instance [default: /run/ramon.sock for root, and
+    /// $XDG_RUNTIME_DIR/ramon.sock for other users]
+    #[cfg(unix)]
+    #[arg(long, global = true, value_name = "FILE")]
+    socket: Option<PathBuf>,
+    /// The format of ramon's own log
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+    /// The level of ramon's own log (e.g. `debug`), which overrides `RUST_LOG` [default: info]
+    #[arg(long, global = true, value_name = "LEVEL")]
+    log_level: Option<LevelFilter>,
+    /// Without a command, ramon runs every monitor.
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the monitors
+    Run(RunArgs),
+    /// Check the config without running anything
+    Check,
+    /// Print the config, with its includes, templates, defaults, and patterns applied
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Write a starter config
+    Init(InitArgs),
+    /// Check that ramon can watch a log file and deliver notifications on this machine
+    Selftest,
+    /// Show the matches and notification deliveries recorded in the event database
+    Events {
+        /// Only show the events of this monitor
+        #[arg(long)]
+        monitor: Option<String>,
+        /// Only show events within this long ago (e.g. `24h`)
+        #[arg(long, value_name = "DURATION")]
+        since: Option<String>,
+        /// Print each event as a line of JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Run a saved log file through a monitor, without performing its actions
+    Replay {
+        #[arg(long)]
+        monitor: String,
+        file: String,
+    },
+    /// Send a test notification through a notification config
+    TestNotify { target: String },
+    /// Show whether a monitor's regexes match lines, which are read from stdin if none are given
+    TestRegex {
+        #[arg(long)]
+        monitor: String,
+        lines: Vec<String>,
+    },
+    /// Show the state of each monitor of the running instance
+    #[cfg(unix)]
+    Status {
+        #[arg(long)]
+        json: bool,
+    },
+    /// Mute the notifications of a monitor of the running instance
+    #[cfg(unix)]
+    Silence {
+        monitor: String,
+        #[arg(long = "for", value_name = "DURATION")]
+        duration: String,
+    },
+    /// End the silence of a monitor of the running instance
+    #[cfg(unix)]
+    Unsilence { monitor: String },
+    /// Print a completion script for a shell
+    Completions { shell: Shell },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print the resolved config, with credentials redacted
+    Dump,
+}
+
+#[derive(Args, Default)]
+struct RunArgs {
+    /// Don't perform the actions of any monitor
+    #[arg(long)]
+    dry_run: bool,
+    #[command(flatten)]
+    selection: Selection,
+}
 
 #[tokio::main]
 async fn main() {
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("ramon=info"))
-        .init();
+    let cli = Cli::parse();
+    logging::init(cli.log_format, cli.log_level);
+    if let Some(config) = cli.config {
+        platform::set_config_path(config);
+    }
+    #[cfg(unix)]
+    if let Some(socket) = cli.socket {
+        control::set_socket_path(socket);
+    }
 
-    if let Err(err) = run().await {
+    let command = cli.command.unwrap_or(Command::Run(RunArgs::default()));
+    let res = match command {
+        Command::Run(args) => run(args.dry_run, args.selection).await,
+        Command::Check => check().await,
+        Command::Config(ConfigCommand::Dump) => config::dump().await.map(|dump| print!("{dump}")),
+        Command::Init(args) => init::run(args).await,
+        Command::Selftest => selftest::run().await,
+        Command::Events {
+            monitor,
+            since,
+            json,
+        } => events::run(monitor.as_deref(), since.as_deref(), json).await,
+        Command::Replay { monitor, file } => replay::run(&monitor, &file).await,
+        Command::TestNotify { target } => test_notify::run(&target).await,
+        Command::TestRegex { monitor, lines } => {
+            let lines = lines.iter().map(String::as_str).collect::<Vec<&str>>();
+            test_regex::run(&monitor, &lines).await
+        }
+        #[cfg(unix)]
+        Command::Status { json } => control::status(json).await,
+        #[cfg(unix)]
+        Command::Silence { monitor, duration } => control::silence(&monitor, &duration).await,
+        #[cfg(unix)]
+        Command::Unsilence { monitor } => control::unsilence(&monitor).await,
+        Command::Completions { shell } => {
+            clap_complete::generate(shell, &mut Cli::command(), "ramon", &mut std::io::stdout());
+            Ok(())
+        }
+    };
+    if let Err(err) = res {
         eprintln!("{err}");
         exit(1);
     }
 }
 
-async fn run() -> Result<()> {
-    let mut doc = String::new();
-    File::open("/etc/ramon.toml")
-        .await
-        .map_err(|err| anyhow!("Failed to open /etc/ramon.toml: {err}"))?
-        .read_to_string(&mut doc)
-        .await
-        .map_err(|err| anyhow!("Failed to read /etc/ramon.toml: {err}"))?;
-    let config = config::parse(&doc).map_err(|err| {
-        anyhow!(
-            r#"Failed to parse ramon.toml: {err}
-
-Refer to https://github.com/reujab/ramon#specification-wip"#
-        )
-    })?;
+/// Which monitors to run.
+#[derive(Args, Default)]
+struct Selection {
+    /// Only run these monitors
+    #[arg(long, value_delimiter = ',', value_name = "MONITOR,...")]
+    only: Option<Vec<String>>,
+    /// Don't run these monitors
+    #[arg(long, value_delimiter = ',', value_name = "MONITOR,...")]
+    skip: Vec<String>,
+}
+
+impl Selection {
+    /// Fails if a monitor named by `--only` or `--skip` doesn't exist, which is likely a typo.
+    fn check(&self, tenants: &[Tenant]) -> Result<()> {
+        let names = tenants
+            .iter()
+            .flat_map(|tenant| &tenant.monitors)
+            .map(|monitor| monitor.name.as_str())
+            .collect::<HashSet<&str>>();
+        let mut selected = self.only.iter().flatten().chain(&self.skip);
+        match selected.find(|name| !names.contains(name.as_str())) {
+            None => Ok(()),
+            Some(name) => bail!("Unknown monitor `{name}` in `--only` or `--skip`."),
+        }
+    }
+
+    /// Returns why a monitor doesn't run, if it doesn't.
+    fn skip_reason(&self, monitor: &MonitorConfig) -> Option<&'static str> {
+        if !monitor.enabled {
+            Some("disabled")
+        } else if !monitor.for_this_host {
+            Some("not for this host")
+        } else if self.skip.contains(&monitor.name) {
+            Some("--skip")
+        } else if self
+            .only
+            .as_ref()
+            .is_some_and(|only| !only.contains(&monitor.name))
+        {
+            Some("not in --only")
+        } else {
+            None
+        }
+    }
+}
+
+/// Parses the config, and reports how many monitors it has.
+async fn check() -> Result<()> {
+    let clock: SharedClock = Arc::new(SystemClock);
+    let config = config::load(&clock).await?;
+    let count = config
+        .tenants
+        .iter()
+        .map(|tenant| tenant.monitors.len())
+        .sum::<usize>();
+    let plural = match count {
+        1 => "",
+        _ => "s",
+    };
+    println!("The config is valid, with {count} monitor{plural}.");
+    Ok(())
+}
+
+/// Runs every selected monitor. When `dry_run` is true, no monitor performs its actions.
+async fn run(dry_run: bool, selection: Selection) -> Result<()> {
+    let clock: SharedClock = Arc::new(SystemClock);
+    let config = config::load(&clock).await?;
+    selection.check(&config.tenants)?;
+    if let Some(endpoint) = &config.otlp {
+        telemetry::init(endpoint)?;
+    }
+    #[cfg(unix)]
+    let control_server = match control::ControlServer::new() {
+        Err(err) if err.kind == ErrorKind::AlreadyRunning => return Err(err.into()),
+        Err(err) => {
+            // `ramon status` is unavailable, but monitoring isn't affected.
+            warn!("{err}");
+            None
+        }
+        Ok(server) => Some(server),
+    };
 
     // Process monitors.
-    let mut monitors = Vec::with_capacity(config.monitors.len());
-    for monitor_config in config.monitors {
-        let name = monitor_config.name.clone();
-        let aggregator_id = match &monitor_config.notify {
-            None => "default",
-            Some(notify) => &notify.r#type,
-        };
-        let aggregator = config.aggregator_txs.get(aggregator_id).ok_or(anyhow!(
-            "Could not find notification config for {aggregator_id:?}"
-        ))?;
-        let monitor = Monitor::new(monitor_config, aggregator.clone())
+    let mut monitors = Vec::new();
+    for mut tenant in config.tenants {
+        if let Some(name) = &tenant.name {
+            info!("Loading tenant `{name}`");
+        }
+        tenant.spawn_aggregators();
+        for mut monitor_config in mem::take(&mut tenant.monitors) {
+            if let Some(reason) = selection.skip_reason(&monitor_config) {
+                info!("Skipping monitor `{}` ({reason})", monitor_config.name);
+                continue;
+            }
+            monitor_config.dry_run |= dry_run;
+            let name = monitor_config.name.clone();
+            let aggregator = tenant.aggregator_for(&monitor_config)?;
+            let monitor = Monitor::new(
+                monitor_config,
+                aggregator,
+                tenant.variables.clone(),
+                clock.clone(),
+            )
             .await
             .map_err(|err| anyhow!("Monitor `{}`: {err}", name))?;
-        monitors.push(monitor);
+            monitors.push(monitor);
+        }
+    }
+    if let Some(path) = &config.audit {
+        audit::init(path)?;
     }
-    let mut handles = Vec::with_capacity(monitors.len());
+    if let Some(events) = &config.events {
+        ramon_core::internal::events::init(events)?;
+    }
+    if let Some(max_processes) = config.max_processes {
+        exec_limit::init(max_processes);
+    }
+    if let (Some((name, notify_tx)), false) = (config.self_notify, dry_run) {
+        meta::init(name, notify_tx);
+    }
+    if let Some(ingest) = config.ingest {
+        let server = IngestServer::new(ingest).await?;
+        tokio::spawn(async move {
+            if let Err(err) = server.start().await {
+                error!("Ingest server: {err}");
+            }
+        });
+    }
+    if let Some(api) = config.api {
+        if let Some(path) = api.overrides {
+            overrides::load(path)?;
+        }
+        let server = api::ApiServer::new(api.bind, api.token).await?;
+        tokio::spawn(async move {
+            if let Err(err) = server.start().await {
+                error!("API server: {err}");
+            }
+        });
+    }
+    if let Some(bind) = config.health {
+        let server = health::HealthServer::new(bind).await?;
+        tokio::spawn(async move {
+            if let Err(err) = server.start().await {
+                error!("Health server: {err}");
+            }
+        });
+    }
+    #[cfg(unix)]
+    if let Some(server) = control_server {
+        tokio::spawn(async move {
+            if let Err(err) = server.start().await {
+                error!("Control socket: {err}");
+            }
+        });
+    }
+    // Log files, sockets, and listeners are opened by now.
+    if let Some(credentials) = config.drop_privileges {
+        platform::drop_privileges(credentials).map_err(|err| {
+            Error::new(
+                ErrorKind::DropPrivileges,
+                format!("Failed to drop privileges: {err}"),
+            )
+        })?;
+        info!("Dropped privileges");
+    }
+
+    let count = monitors.len();
+    let mut handles = JoinSet::new();
     for mut monitor in monitors {
-        let handle = tokio::spawn(async move {
+        handles.spawn(async move {
+            let _health = health::run(&monitor.name);
             let res = monitor.start().await;
             if let Err(err) = &res {
                 error!("[{}] {err}", monitor.name);
+                systemd::notify(&format!("STATUS=Monitor `{}` stopped: {err}", monitor.name));
             }
             error!("[{}] Monitor exited early.", monitor.name);
+            if let Err(err) = &res {
+                let title = format!("Monitor `{}` stopped", monitor.name);
+                meta::report_fatal(&monitor.name, title, err).await;
+            }
             res
         });
-        handles.push(handle);
     }
-    for handle in handles {
-        handle.await??;
+    let plural = match count {
+        1 => "",
+        _ => "s",
+    };
+    systemd::notify(&format!("READY=1\nSTATUS=Watching {count} monitor{plural}"));
+    health::ready();
+
+    // The watchdog is pinged from here rather than from its own task, so that it notices if the
+    // runtime stops making progress.
+    let mut watchdog = systemd::watchdog_interval().map(interval);
+    let mut disk_check = interval(state::DISK_CHECK_INTERVAL);
+    loop {
+        select! {
+            res = handles.join_next() => match res {
+                None => break,
+                Some(res) => {
+                    if let Err(err) = res.map_err(anyhow::Error::from).and_then(|res| res) {
+                        systemd::notify("STOPPING=1");
+                        return Err(err);
+                    }
+                }
+            },
+            Some(_) = tick(&mut watchdog) => systemd::notify("WATCHDOG=1"),
+            _ = disk_check.tick() => state::check_disk_pressure(),
+        }
     }
 
     Ok(())
 }
+
+async fn tick(interval: &mut Option<Interval>) -> Option<Instant> {
+    match interval {
+        None => None,
+        Some(interval) => Some(interval.tick().await),
+    }
+}
@@ -0,0 +1,249 @@
+//! `ramon replay`, which runs a saved log file through a monitor and prints what it would have
+//! done, without running `exec` or `ban`, or sending notifications.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufRead, BufReader},
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Result};
+use flate2::read::MultiGzDecoder;
+use regex::{Captures, Regex};
+use tokio::{
+    sync::mpsc::{channel, unbounded_channel, Receiver, UnboundedReceiver},
+    time::Instant,
+};
+
+use ramon_core::{
+    clock::{SharedClock, SimulatedClock},
+    config::{self, value_to_string, Notification},
+    internal::{monitor::Trace, state},
+    Monitor,
+};
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// e.g. `2024-05-01T12:00:03`
+static ISO_8601: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(\d{4})-(\d{2})-(\d{2})[T ](\d{2}):(\d{2}):(\d{2})").unwrap());
+/// e.g. `01/May/2024:12:00:03`, as written by nginx and Apache.
+static COMMON_LOG: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(\d{2})/([A-Z][a-z]{2})/(\d{4}):(\d{2}):(\d{2}):(\d{2})").unwrap()
+});
+/// e.g. `May  1 12:00:03`, as written by syslog daemons. The year is missing.
+static SYSLOG: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^([A-Z][a-z]{2}) +(\d{1,2}) (\d{2}):(\d{2}):(\d{2})").unwrap());
+
+pub async fn run(monitor_name: &str, path: &str) -> Result<()> {
+    // Unique values and the like start empty, and the running instance's are left alone.
+    state::detach();
+
+    let start = Instant::now();
+    let clock = Arc::new(SimulatedClock::new(start));
+    let shared_clock: SharedClock = clock.clone();
+    let config = config::load(&shared_clock).await?;
+    let (mut monitor_config, variables) = config
+        .tenants
+        .into_iter()
+        .flat_map(|tenant| {
+            let variables = tenant.variables;
+            tenant
+                .monitors
+                .into_iter()
+                .map(move |monitor| (monitor, variables.clone()))
+        })
+        .find(|(monitor, _)| monitor.name == monitor_name)
+        .ok_or_else(|| anyhow!("Unknown monitor `{monitor_name}`"))?;
+    if monitor_config.log.is_none()
+        && monitor_config.service.is_none()
+        && monitor_config.source.is_none()
+    {
+        bail!("Monitor `{monitor_name}` doesn't read lines");
+    }
+    // The lines come from the file instead.
+    monitor_config.log = None;
+    monitor_config.service = None;
+    monitor_config.source = None;
+    monitor_config.every = None;
+    monitor_config.dry_run = false;
+
+    let (notify_tx, mut notify_rx) = channel(64);
+    let (trace_tx, mut trace_rx) = unbounded_channel();
+    let mut monitor = Monitor::new(monitor_config, notify_tx, variables, shared_clock).await?;
+    monitor.trace_to(trace_tx);
+
+    let file = File::open(path).map_err(|err| anyhow!("Failed to open {path:?}: {err}"))?;
+    let mut reader = BufReader::new(file);
+    let is_gzip = reader.fill_buf()?.starts_with(&[0x1f, 0x8b]);
+    let mut reader: Box<dyn BufRead> = match is_gzip {
+        true => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        false => Box::new(reader),
+    };
+
+    let mut first_timestamp = None;
+    let mut counts = Counts::default();
+    let mut buf = Vec::new();
+    let mut line_number = 0;
+    loop {
+        buf.clear();
+        if reader.read_until(b'\n', &mut buf)? == 0 {
+            break;
+        }
+        line_number += 1;
+        let line = String::from_utf8_lossy(&buf)
+            .trim_end_matches(['\n', '\r'])
+            .to_owned();
+
+        if let Some(timestamp) = timestamp(&line) {
+            let first_timestamp = *first_timestamp.get_or_insert(timestamp);
+            clock.set(start + Duration::from_secs(timestamp.saturating_sub(first_timestamp)));
+        }
+
+        monitor.replay(line.clone()).await?;
+        counts.report(
+            &format!("{line_number}: {line}"),
+            &mut trace_rx,
+            &mut notify_rx,
+        );
+    }
+    monitor.finish_replay().await?;
+    counts.report("End of file:", &mut trace_rx, &mut notify_rx);
+
+    println!(
+        "\nLines: {line_number}, matches: {}, threshold reached: {}, actions run: {}, notifications: {}",
+        counts.matches, counts.threshold_firings, counts.fired, counts.notifications
+    );
+    Ok(())
+}
+
+#[derive(Default)]
+struct Counts {
+    matches: usize,
+    threshold_firings: usize,
+    fired: usize,
+    notifications: usize,
+}
+
+impl Counts {
+    /// Prints what the monitor did since the last report, under `heading` if it did anything.
+    fn report(
+        &mut self,
+        heading: &str,
+        trace_rx: &mut UnboundedReceiver<Trace>,
+        notify_rx: &mut Receiver<Notification>,
+    ) {
+        let mut steps = Vec::new();
+        while let Ok(trace) = trace_rx.try_recv() {
+            steps.push(self.describe(trace));
+        }
+        while let Ok(notification) = notify_rx.try_recv() {
+            self.notifications += 1;
+            let mut step = format!(
+                "notification ({}): {}",
+                notification.severity.as_str(),
+                notification.title
+            );
+            if !notification.body.is_empty() {
+                step += &format!(
+                    "\n        {}",
+                    notification.body.replace('\n', "\n        ")
+                );
+            }
+            steps.push(step);
+        }
+        if steps.is_empty() {
+            return;
+        }
+        println!("{heading}");
+        for step in steps {
+            println!("    {step}");
+        }
+    }
+
+    fn describe(&mut self, trace: Trace) -> String {
+        match trace {
+            Trace::Matched(pattern_index) => {
+                self.matches += 1;
+                format!("matched pattern {pattern_index}")
+            }
+            Trace::Threshold(false) => "threshold not reached".into(),
+            Trace::Threshold(true) => {
+                self.threshold_firings += 1;
+                "threshold reached".into()
+            }
+            Trace::CoolingDown => "cooling down".into(),
+            Trace::Inactive => "inactive".into(),
+            Trace::SampledOut => "skipped by `sample`".into(),
+            Trace::Filtered(key) => format!("filtered out by `{key}`"),
+            Trace::Fired(variables) => {
+                self.fired += 1;
+                let variables = variables
+                    .into_iter()
+                    .map(|(var, val)| (var, value_to_string(val)))
+                    .collect::<BTreeMap<String, String>>();
+                format!("actions run with {variables:?}")
+            }
+            Trace::Exec(command) => format!("exec: {command}"),
+            Trace::Step(step) => step,
+        }
+    }
+}
+
+/// Returns the time a line was logged, in seconds since an arbitrary epoch, if it has a timestamp
+/// in a known format. Time zones are ignored.
+fn timestamp(line: &str) -> Option<u64> {
+    let number = |captures: &Captures, i: usize| captures[i].parse::<i64>().ok();
+    let month = |name: &str| Some(MONTHS.iter().position(|month| *month == name)? as i64 + 1);
+    let [year, month, day, hours, minutes, seconds] =
+        if let Some(captures) = ISO_8601.captures(line) {
+            let field = |i| number(&captures, i);
+            [
+                field(1)?,
+                field(2)?,
+                field(3)?,
+                field(4)?,
+                field(5)?,
+                field(6)?,
+            ]
+        } else if let Some(captures) = COMMON_LOG.captures(line) {
+            let field = |i| number(&captures, i);
+            [
+                field(3)?,
+                month(&captures[2])?,
+                field(1)?,
+                field(4)?,
+                field(5)?,
+                field(6)?,
+            ]
+        } else if let Some(captures) = SYSLOG.captures(line) {
+            let field = |i| number(&captures, i);
+            [
+                1970,
+                month(&captures[1])?,
+                field(2)?,
+                field(3)?,
+                field(4)?,
+                field(5)?,
+            ]
+        } else {
+            return None;
+        };
+    let days = days_from_civil(year, month, day);
+    u64::try_from(days * 86400 + hours * 3600 + minutes * 60 + seconds).ok()
+}
+
+/// Howard Hinnant's `days_from_civil`, the inverse of the calculation in `format_utc`.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
@@ -0,0 +1,210 @@
+//! `ramon selftest` checks the whole pipeline (watch, match, aggregate, and deliver) on the local
+//! machine by writing to a temporary log file and delivering the notification to fake SMTP and
+//! HTTP servers.
+
+use std::{
+    process,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail, Result};
+use log::debug;
+use tokio::{
+    fs::{create_dir_all, remove_dir_all, OpenOptions},
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{self, Sender},
+    time::timeout,
+};
+
+use ramon_core::{
+    clock::{SharedClock, SystemClock},
+    config,
+    internal::state,
+    Monitor,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+pub async fn run() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("ramon-selftest-{}", process::id()));
+    create_dir_all(&dir)
+        .await
+        .map_err(|err| anyhow!("Failed to create {dir:?}: {err}"))?;
+    let res = run_in(&dir).await;
+    let _ = remove_dir_all(&dir).await;
+    match res {
+        Ok(()) => {
+            println!("Self-test passed.");
+            Ok(())
+        }
+        Err(err) => bail!("Self-test failed: {err}"),
+    }
+}
+
+async fn run_in(dir: &std::path::Path) -> Result<()> {
+    let (smtp_tx, mut smtp_rx) = mpsc::channel(1);
+    let smtp = TcpListener::bind("127.0.0.1:0").await?;
+    let smtp_port = smtp.local_addr()?.port();
+    tokio::spawn(serve(smtp, smtp_tx, fake_smtp));
+
+    let (http_tx, mut http_rx) = mpsc::channel(1);
+    let http = TcpListener::bind("127.0.0.1:0").await?;
+    let http_addr = http.local_addr()?;
+    tokio::spawn(serve(http, http_tx, fake_http));
+    println!("Started fake SMTP server on port {smtp_port} and fake HTTP server on {http_addr}.");
+
+    let log_path = dir.join("selftest.log");
+    let mut log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+        .await
+        .map_err(|err| anyhow!("Failed to create {log_path:?}: {err}"))?;
+
+    let log_path_str = log_path
+        .to_str()
+        .ok_or(anyhow!("{log_path:?} isn't valid UTF-8."))?;
+    let doc = format!(
+        r#"
+[notify.default]
+from = "ramon@localhost"
+to = "selftest@localhost"
+smtp_port = {smtp_port}
+webhook = "http://{http_addr}/"
+
+[monitor.selftest]
+log = {}
+match_log = 'ramon selftest (?<token>\w+)'
+notify = {{ title = "Self-test {{{{ token }}}}", body = "The self-test notification was delivered." }}
+"#,
+        // A TOML string, escaped, since the path may contain quotes.
+        toml::Value::String(log_path_str.to_owned())
+    );
+    let clock: SharedClock = Arc::new(SystemClock);
+    let mut tenant = config::parse(&doc, &clock)?
+        .tenants
+        .pop()
+        .ok_or(anyhow!("Failed to load the self-test config."))?;
+    let monitor_config = tenant
+        .monitors
+        .pop()
+        .ok_or(anyhow!("Failed to load the self-test monitor."))?;
+    // The monitor and its aggregator must not touch the running instance's state and queues.
+    state::detach();
+    tenant.spawn_aggregators();
+    let aggregator = tenant.aggregator_for(&monitor_config)?;
+    let mut monitor = Monitor::new(monitor_config, aggregator, tenant.variables, clock).await?;
+    let monitor = tokio::spawn(async move { monitor.start().await });
+    println!("Watching {log_path:?}.");
+
+    let token = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or_default()
+        .to_string();
+    log.write_all(format!("ramon selftest {token}\n").as_bytes())
+        .await?;
+    log.flush().await?;
+    println!("Wrote a test line.");
+
+    let res = async {
+        for (channel, rx) in [("webhook", &mut http_rx), ("email", &mut smtp_rx)] {
+            loop {
+                let received = timeout(TIMEOUT, rx.recv())
+                    .await
+                    .map_err(|_| anyhow!("The {channel} was not delivered within {TIMEOUT:?}."))?
+                    .ok_or(anyhow!("The fake {channel} server stopped."))?;
+                if received.contains(&token) {
+                    println!("The {channel} was delivered.");
+                    break;
+                }
+                debug!("Ignoring unexpected {channel}: {received}");
+            }
+        }
+        Ok(())
+    }
+    .await;
+    monitor.abort();
+    res
+}
+
+/// Accepts connections, sending every message received by `handle`.
+async fn serve<F>(listener: TcpListener, tx: Sender<String>, handle: fn(TcpStream) -> F)
+where
+    F: std::future::Future<Output = Result<String>> + Send + 'static,
+{
+    while let Ok((stream, _)) = listener.accept().await {
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            match handle(stream).await {
+                Ok(message) => {
+                    let _ = tx.send(message).await;
+                }
+                Err(err) => debug!("Fake server: {err}"),
+            }
+        });
+    }
+}
+
+/// Accepts a single email and returns its data.
+async fn fake_smtp(stream: TcpStream) -> Result<String> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    writer.write_all(b"220 localhost ESMTP\r\n").await?;
+    while let Some(line) = lines.next_line().await? {
+        let command = line.get(..4).unwrap_or_default().to_ascii_uppercase();
+        match command.as_str() {
+            "DATA" => {
+                writer
+                    .write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")
+                    .await?;
+                let mut data = String::new();
+                while let Some(line) = lines.next_line().await? {
+                    if line == "." {
+                        break;
+                    }
+                    data += &line;
+                    data += "\n";
+                }
+                writer.write_all(b"250 OK\r\n").await?;
+                return Ok(data);
+            }
+            "QUIT" => {
+                writer.write_all(b"221 Bye\r\n").await?;
+                break;
+            }
+            _ => writer.write_all(b"250 OK\r\n").await?,
+        }
+    }
+    bail!("The connection closed before any data was sent.")
+}
+
+/// Accepts a single request and returns its body.
+async fn fake_http(stream: TcpStream) -> Result<String> {
+    let mut reader = BufReader::new(stream);
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            bail!("The connection closed before the headers ended.");
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse()?;
+            }
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).await?;
+    reader
+        .get_mut()
+        .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+        .await?;
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
@@ -0,0 +1,35 @@
+//! Exports the spans of the event pipeline (reading a log, matching a line, running actions,
+//! running `exec`, and delivering a notification) to an OpenTelemetry collector over OTLP/HTTP.
+//! The exporter is only built with the `otel` feature.
+
+use anyhow::Result;
+
+/// Sends spans to the collector at `endpoint` (e.g. `http://localhost:4318`).
+#[cfg(feature = "otel")]
+pub fn init(endpoint: &str) -> Result<()> {
+    use anyhow::anyhow;
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+    use opentelemetry_sdk::{trace::SdkTracerProvider, Resource};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        .build()
+        .map_err(|err| anyhow!("Failed to create OTLP exporter: {err}"))?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name("ramon").build())
+        .build();
+    let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("ramon"));
+    tracing::subscriber::set_global_default(tracing_subscriber::registry().with(layer))?;
+    opentelemetry::global::set_tracer_provider(provider);
+    log::info!("Exporting traces to {endpoint}");
+    Ok(())
+}
+
+#[cfg(not(feature = "otel"))]
+pub fn init(_endpoint: &str) -> Result<()> {
+    anyhow::bail!("otlp: ramon was built without the `otel` feature")
+}
@@ -0,0 +1,51 @@
+//! `ramon test-notify`, which sends a sample notification through every channel of a
+//! notification config and reports whether each delivered it.
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{bail, Result};
+
+use ramon_core::{
+    clock::{SharedClock, SystemClock},
+    config::{self, Notification, Severity},
+    internal::{meta, status},
+    Aggregator,
+};
+
+pub async fn run(target: &str) -> Result<()> {
+    let clock: SharedClock = Arc::new(SystemClock);
+    let mut config = config::load_notify_config(target, &clock).await?;
+    // Report the first error instead of waiting for every retry.
+    config.retries = 0;
+
+    let notification = Notification {
+        r#type: config.name.clone(),
+        monitor: meta::MONITOR.into(),
+        severity: Severity::Info,
+        title: "Test notification".into(),
+        body: format!("This notification was sent by `ramon test-notify {target}`."),
+        timestamp: status::now(),
+        variables: HashMap::new(),
+        highlight: None,
+        resolved: false,
+    };
+    let results = Aggregator::deliver(&notification, &config).await;
+    if results.is_empty() {
+        bail!("Notification config `{target}` doesn't have any channels");
+    }
+
+    let mut failures = 0;
+    for (channel, res) in &results {
+        match res {
+            Ok(()) => println!("{channel}: delivered"),
+            Err(err) => {
+                failures += 1;
+                println!("{channel}: {err}");
+            }
+        }
+    }
+    if failures > 0 {
+        bail!("{failures} of {} channels failed", results.len());
+    }
+    Ok(())
+}
@@ -0,0 +1,77 @@
+//! `ramon test-regex`, which shows how a monitor's `match_log` and `ignore_log` handle lines given
+//! on the command line or stdin.
+
+use std::{
+    io::{stdin, BufRead},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, bail, Result};
+
+use ramon_core::{
+    clock::{SharedClock, SystemClock},
+    config::{self, MonitorConfig},
+};
+
+pub async fn run(monitor_name: &str, lines: &[&str]) -> Result<()> {
+    let clock: SharedClock = Arc::new(SystemClock);
+    let config = config::load(&clock).await?;
+    let monitor = config
+        .tenants
+        .into_iter()
+        .flat_map(|tenant| tenant.monitors)
+        .find(|monitor| monitor.name == monitor_name)
+        .ok_or_else(|| anyhow!("Unknown monitor `{monitor_name}`"))?;
+    if monitor.match_log.is_none() && monitor.ignore_log.is_empty() {
+        bail!("Monitor `{monitor_name}` has neither `match_log` nor `ignore_log`");
+    }
+
+    if !lines.is_empty() {
+        for line in lines {
+            test(&monitor, line);
+        }
+        return Ok(());
+    }
+    for line in stdin().lock().lines() {
+        test(&monitor, &line?);
+    }
+    Ok(())
+}
+
+fn test(monitor: &MonitorConfig, line: &str) {
+    println!("{line}");
+    if let Some(i) = monitor
+        .ignore_log
+        .iter()
+        .position(|regex| regex.is_match(line))
+    {
+        println!("    ignored by `ignore_log` pattern {i}");
+        return;
+    }
+    let Some(match_log) = &monitor.match_log else {
+        println!("    not ignored");
+        return;
+    };
+    let Some(pattern_index) = match_log.set.matches(line).into_iter().next() else {
+        println!("    no match");
+        return;
+    };
+    let pattern = &match_log.patterns[pattern_index];
+    let Some(captures) = pattern.regex.captures(line) else {
+        println!("    no match");
+        return;
+    };
+    match &pattern.name {
+        None => println!("    matched pattern {pattern_index}"),
+        Some(name) => println!("    matched pattern {pattern_index} ({name})"),
+    }
+    if let Some(matched) = captures.get(0) {
+        println!("    match = {:?}", matched.as_str());
+    }
+    for capture_name in pattern.regex.capture_names().flatten() {
+        match captures.name(capture_name) {
+            Some(capture) => println!("    {capture_name} = {:?}", capture.as_str()),
+            None => println!("    {capture_name} (not captured)"),
+        }
+    }
+}